@@ -6,17 +6,17 @@ use std::{
 };
 
 
-const COPY_DIRS: &'static [&'static str] = &[
+const COPY_DIRS: &[&str] = &[
 
 ];
 
-const COPY_FILES: &'static [&'static str] = &[
+const COPY_FILES: &[&str] = &[
     "./assets/config.yaml"
 ];
 
 fn copy_file(from: &Path, to: &Path) {
     let path_str = from.as_os_str().to_str().unwrap_or_default();
-    fs::copy(from, to).expect(&format!("couldn't move file {}, cwd {:?}", path_str, std::env::current_dir()));
+    fs::copy(from, to).unwrap_or_else(|_| panic!("couldn't move file {}, cwd {:?}", path_str, std::env::current_dir()));
 }
 
 /// A helper function for recursively copying a directory.
@@ -1,10 +1,68 @@
+use egui::Pos2;
+
 use crate::models::QuestionResponse;
 
+/// names an agent on screen, see `AgentConfig`
+pub type AgentId = String;
+
+/// things happening to the app window, each has its own animation
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LifecycleEvent {
+    Started,
+    /// the character says goodbye, the app closes once it's done
+    QuitRequested,
+    /// collapsed, minimized once the animation is done
+    Hidden,
+    /// restored after being collapsed
+    Shown
+}
+
+/// the user doing something to the character itself
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Interaction {
+    Clicked,
+    /// opens the chat input
+    DoubleClicked,
+    /// picked up to move the window
+    Dragged
+}
+
+/// `Option<AgentId>` fields are `None` when the action is for every agent
 #[derive(Clone, Debug)]
 pub enum DispatchActions {
-    AskQuestion(String),
-    RespondToQuestion(QuestionResponse),
+    AskQuestion(Option<AgentId>, String),
+    RespondToQuestion(AgentId, QuestionResponse),
     QuestionTextChanged(String),
-    NewAnimationStarted(String),
-    NewFrameToRender
-}
\ No newline at end of file
+    NewAnimationStarted(AgentId, String),
+    NewFrameToRender,
+    Lifecycle(Option<AgentId>, LifecycleEvent),
+    /// the animation for a lifecycle event has played to the end
+    LifecycleAnimationDone(AgentId, LifecycleEvent),
+    Interaction(AgentId, Interaction),
+    /// switch the agent to the character pack at this index
+    SwitchCharacter(AgentId, usize),
+    /// forget the conversation so far and start over
+    NewConversation,
+    /// an agent showed a frame with a named `event`
+    AnimationEvent(AgentId, String),
+    /// talking to the agent's assistant failed, it keeps trying
+    AssistantError(AgentId, String),
+    /// the agent's latest answer has finished appearing on screen
+    AnswerRevealed(AgentId),
+    /// walk the window so its top left corner is here, in points on its monitor.
+    /// Kept on screen, and taken straight there with reduced motion
+    MoveTo(Pos2)
+}
+
+impl DispatchActions {
+    /// whether `agent` should react to this
+    pub fn is_for(&self, agent: &str) -> bool {
+        match self {
+            DispatchActions::AskQuestion(Some(to), _) | DispatchActions::Lifecycle(Some(to), _) => to == agent,
+            DispatchActions::RespondToQuestion(from, _) | DispatchActions::AssistantError(from, _) => from == agent,
+            DispatchActions::AnswerRevealed(from) => from == agent,
+            DispatchActions::Interaction(to, _) | DispatchActions::SwitchCharacter(to, _) => to == agent,
+            _ => true
+        }
+    }
+}
@@ -1,2 +1,14 @@
+pub mod atlas;
+pub mod audio;
+pub mod editor;
+pub mod effects;
+pub mod error;
+pub mod import;
 pub mod models;
-pub mod service;
\ No newline at end of file
+pub mod pack;
+pub mod preprocess;
+pub mod scheduler;
+pub mod service;
+pub mod sprite_sheet;
+pub mod validate;
+pub mod widget;
\ No newline at end of file
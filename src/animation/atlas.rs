@@ -0,0 +1,258 @@
+use std::collections::HashMap;
+
+use image::RgbaImage;
+
+use super::error::AnimationError;
+use super::models::AnimationConfig;
+use super::models::AtlasRegion;
+use super::models::SpriteSheetInfo;
+
+/// transparent gap between packed frames so linear filtering doesn't bleed
+const PADDING: u32 = 1;
+
+/// default atlas width, fits comfortably inside every gl driver's texture limit
+pub const DEFAULT_MAX_WIDTH: u32 = 2048;
+
+/// packed sprite sheet pages and the config pointing into them
+pub struct Atlas {
+    pub pages: Vec<RgbaImage>,
+    pub config: AnimationConfig,
+}
+
+/// a frame's pixels with the transparent border cut off
+struct Trimmed {
+    image: RgbaImage,
+    offset_x: u32,
+    offset_y: u32,
+}
+
+fn trim(sheet: &RgbaImage, region: &AtlasRegion) -> Trimmed {
+    let (x0, y0) = (region.x as u32, region.y as u32);
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (u32::MAX, u32::MAX, 0, 0);
+
+    for y in 0..region.height as u32 {
+        for x in 0..region.width as u32 {
+            if sheet.get_pixel(x0 + x, y0 + y)[3] != 0 {
+                min_x = min_x.min(x);
+                min_y = min_y.min(y);
+                max_x = max_x.max(x + 1);
+                max_y = max_y.max(y + 1);
+            }
+        }
+    }
+
+    if min_x == u32::MAX {
+        return Trimmed {
+            image: RgbaImage::new(0, 0),
+            offset_x: 0,
+            offset_y: 0,
+        };
+    }
+
+    let image =
+        image::imageops::crop_imm(sheet, x0 + min_x, y0 + min_y, max_x - min_x, max_y - min_y)
+            .to_image();
+    Trimmed {
+        image,
+        offset_x: min_x + region.offset_x as u32,
+        offset_y: min_y + region.offset_y as u32,
+    }
+}
+
+/// where a rectangle ended up
+#[derive(Clone, Copy, Default)]
+struct Placement {
+    page: usize,
+    x: u32,
+    y: u32,
+}
+
+/// lay rectangles out on shelves, tallest first, starting a new page when one fills up,
+/// returns the placements and the size of every page
+fn shelf_pack(
+    sizes: &[(u32, u32)],
+    max_size: [u32; 2],
+) -> Result<(Vec<Placement>, Vec<[u32; 2]>), AnimationError> {
+    let [max_width, max_height] = max_size;
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by_key(|i| std::cmp::Reverse(sizes[*i].1));
+
+    let mut placements = vec![Placement::default(); sizes.len()];
+    let mut pages = vec![[1, 1]];
+    let (mut x, mut y, mut shelf_height) = (0, 0, 0);
+
+    for i in order {
+        let (w, h) = sizes[i];
+        if w == 0 || h == 0 {
+            continue;
+        }
+        if w > max_width || h > max_height {
+            return Err(AnimationError::Format(format!(
+                "a {}x{} frame doesn't fit on a {}x{} page",
+                w, h, max_width, max_height
+            )));
+        }
+        if x > 0 && x + w > max_width {
+            y += shelf_height + PADDING;
+            x = 0;
+            shelf_height = 0;
+        }
+        if y.saturating_add(h) > max_height {
+            pages.push([1, 1]);
+            y = 0;
+            x = 0;
+            shelf_height = 0;
+        }
+
+        let page = pages.len() - 1;
+        placements[i] = Placement { page, x, y };
+        x += w + PADDING;
+        shelf_height = shelf_height.max(h);
+        pages[page][0] = pages[page][0].max(x - PADDING);
+        pages[page][1] = pages[page][1].max(y + h);
+    }
+
+    Ok((placements, pages))
+}
+
+/// trim, deduplicate and pack every frame used by `config` into compact pages no bigger than `max_size`,
+/// `sheets` are the pages the frames currently point into
+pub fn pack(
+    config: &AnimationConfig,
+    sheets: &[RgbaImage],
+    max_size: [u32; 2],
+) -> Result<Atlas, AnimationError> {
+    let Some(first) = sheets.first() else {
+        return Err(AnimationError::Format(
+            "there is no sprite sheet to pack".to_owned(),
+        ));
+    };
+    let cell_size = config
+        .sprite_sheet_info
+        .cell_size([first.width() as usize, first.height() as usize]);
+
+    // every distinct source region gets trimmed once
+    let mut trimmed: Vec<Trimmed> = Vec::new();
+    let mut by_region: HashMap<(usize, AtlasRegion), usize> = HashMap::new();
+    // identical pixels share a spot on the atlas
+    let mut unique: Vec<usize> = Vec::new();
+    let mut by_pixels: HashMap<(u32, u32, Vec<u8>), usize> = HashMap::new();
+    let mut packed_index: Vec<usize> = Vec::new();
+
+    // layers are packed like any other image
+    let all_images = config
+        .animations
+        .idle
+        .iter()
+        .chain(config.animations.action.iter())
+        .flat_map(|a| a.frames.iter())
+        .flat_map(|f| f.info.images());
+
+    for image in all_images {
+        let region = image.source_region(cell_size);
+        let sheet = sheets.get(image.page).ok_or_else(|| {
+            AnimationError::Format(format!(
+                "frame is on page {} but there are only {} pages",
+                image.page,
+                sheets.len()
+            ))
+        })?;
+        if region.x + region.width > sheet.width() as usize
+            || region.y + region.height > sheet.height() as usize
+        {
+            return Err(AnimationError::Format(format!(
+                "frame at {},{} is outside the {}x{} sprite sheet",
+                region.x,
+                region.y,
+                sheet.width(),
+                sheet.height()
+            )));
+        }
+
+        let key = (image.page, region.clone());
+        if by_region.contains_key(&key) {
+            continue;
+        }
+
+        let t = trim(sheet, &region);
+        let pixels_key = (t.image.width(), t.image.height(), t.image.as_raw().clone());
+        let packed = *by_pixels.entry(pixels_key).or_insert_with(|| {
+            unique.push(trimmed.len());
+            unique.len() - 1
+        });
+        by_region.insert(key, trimmed.len());
+        packed_index.push(packed);
+        trimmed.push(t);
+    }
+
+    let sizes: Vec<(u32, u32)> = unique
+        .iter()
+        .map(|i| (trimmed[*i].image.width(), trimmed[*i].image.height()))
+        .collect();
+    let (placements, page_sizes) = shelf_pack(&sizes, max_size)?;
+
+    let mut pages: Vec<RgbaImage> = page_sizes
+        .iter()
+        .map(|[width, height]| RgbaImage::new(*width, *height))
+        .collect();
+    for (u, i) in unique.iter().enumerate() {
+        let p = placements[u];
+        image::imageops::replace(
+            &mut pages[p.page],
+            &trimmed[*i].image,
+            p.x as i64,
+            p.y as i64,
+        );
+    }
+
+    // where an image's pixels went, as (page, region)
+    let packed = |page: usize, region: AtlasRegion| {
+        let t = by_region[&(page, region)];
+        let p = placements[packed_index[t]];
+        let (w, h) = sizes[packed_index[t]];
+        (
+            p.page,
+            AtlasRegion {
+                x: p.x as usize,
+                y: p.y as usize,
+                width: w as usize,
+                height: h as usize,
+                offset_x: trimmed[t].offset_x as usize,
+                offset_y: trimmed[t].offset_y as usize,
+            },
+        )
+    };
+
+    let mut out = config.clone();
+    let all_frames = out
+        .animations
+        .idle
+        .iter_mut()
+        .chain(out.animations.action.iter_mut())
+        .flat_map(|a| a.frames.iter_mut());
+    for frame in all_frames {
+        let info = &mut frame.info;
+        let (page, region) = packed(info.page, info.source_region(cell_size));
+        info.column = 0;
+        info.row = 0;
+        info.page = page;
+        info.region = Some(region);
+
+        for layer in &mut info.layers {
+            let (page, region) = packed(layer.page, layer.source_region(cell_size));
+            layer.column = 0;
+            layer.row = 0;
+            layer.page = page;
+            layer.region = Some(region);
+        }
+    }
+
+    out.sprite_sheet_info = SpriteSheetInfo {
+        columns: 1,
+        rows: 1,
+        frame_size: Some(cell_size),
+        ..config.sprite_sheet_info.clone()
+    };
+
+    Ok(Atlas { pages, config: out })
+}
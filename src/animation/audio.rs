@@ -0,0 +1,64 @@
+//! Where the sounds attached to frames go. Nothing here talks to the sound card and the app
+//! doesn't ship a backend yet, one implements `AudioSink` and is handed to
+//! `AnimationService::set_audio_sink`. Imported packs keep their sounds in `sounds/<name>.wav`.
+
+use std::sync::Mutex;
+
+/// plays frame sounds
+pub trait AudioSink: Send + Sync {
+    /// play the sound `name` from the character's data, `volume` is between 0 and 1
+    fn play(&self, name: &str, volume: f32);
+}
+
+/// drops every sound, the default
+#[derive(Default)]
+pub struct NullSink;
+
+impl AudioSink for NullSink {
+    fn play(&self, _name: &str, _volume: f32) {}
+}
+
+/// remembers what it was asked to play, for tests and headless runs
+#[derive(Default)]
+pub struct RecordingSink {
+    played: Mutex<Vec<(String, f32)>>,
+}
+
+impl RecordingSink {
+    /// the sounds played so far with their volume, oldest first
+    pub fn played(&self) -> Vec<(String, f32)> {
+        self.played.lock().unwrap().clone()
+    }
+}
+
+impl AudioSink for RecordingSink {
+    fn play(&self, name: &str, volume: f32) {
+        self.played.lock().unwrap().push((name.to_owned(), volume));
+    }
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct AudioSettings {
+    /// 0 to 1
+    pub volume: f32,
+    pub muted: bool,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        AudioSettings {
+            volume: 1.0,
+            muted: false,
+        }
+    }
+}
+
+impl AudioSettings {
+    /// the volume to play at, `None` when nothing would be heard
+    pub fn effective_volume(&self) -> Option<f32> {
+        if self.muted || self.volume <= 0.0 {
+            return None;
+        }
+        Some(self.volume.min(1.0))
+    }
+}
@@ -0,0 +1,582 @@
+use std::fs;
+use std::path::PathBuf;
+
+use egui::Color32;
+use egui::ColorImage;
+use egui::Context;
+use egui::Rect;
+use egui::Sense;
+use egui::Stroke;
+use egui::Ui;
+use egui::Vec2;
+use egui::ViewportBuilder;
+use egui::ViewportClass;
+use egui::ViewportId;
+
+use super::error::AnimationError;
+use super::import::ImportedCharacter;
+use super::models::AnimationCategory;
+use super::models::AnimationConfig;
+use super::models::AnimationInfo;
+use super::models::FrameInfo;
+use super::pack::user_packs_dir;
+use super::pack::write_pack;
+use super::pack::CharacterPack;
+use super::pack::PackSource;
+use super::service::load_image_as_color_image;
+use super::sprite_sheet::SpriteSheet;
+use super::validate::parse_and_validate;
+
+/// how big the preview is drawn
+const PREVIEW_SCALE: f32 = 2.0;
+
+/// where edits end up
+enum SaveTarget {
+    /// the animation yaml of a pack directory, overwritten in place
+    File(PathBuf),
+    /// packs that can't be edited in place are saved as a new pack directory
+    NewPack { dir: PathBuf, image_data: Vec<u8> },
+}
+
+/// Tool window for browsing a pack's animations and editing their frames.
+pub struct AnimationEditor {
+    name: String,
+    config: AnimationConfig,
+    sprite_sheet: SpriteSheet,
+    /// the decoded sheet, edits are validated against it before saving
+    image: ColorImage,
+    save_target: SaveTarget,
+    category: AnimationCategory,
+    animation: usize,
+    frame: usize,
+    playing: bool,
+    /// `ctx.input().time` the current frame was shown at
+    frame_started: f64,
+    dirty: bool,
+    /// saved since the last `show`
+    saved: bool,
+    status: Option<String>,
+    open: bool,
+}
+
+/// point branches through `map`, ones it maps to `None` are dropped
+pub fn remap_frame_refs(animation: &mut AnimationInfo, map: impl Fn(usize) -> Option<usize>) {
+    for frame in &mut animation.frames {
+        frame.branching.retain_mut(|b| match map(b.frame_index) {
+            Some(i) => {
+                b.frame_index = i;
+                true
+            }
+            None => false,
+        });
+        frame.exit_branch = frame.exit_branch.and_then(&map);
+    }
+}
+
+/// swap two frames, keeping branches pointing at the same frames
+pub fn swap_frames(animation: &mut AnimationInfo, a: usize, b: usize) {
+    animation.frames.swap(a, b);
+    remap_frame_refs(animation, |i| {
+        Some(if i == a {
+            b
+        } else if i == b {
+            a
+        } else {
+            i
+        })
+    });
+}
+
+/// copy frame `index` in after itself
+pub fn duplicate_frame(animation: &mut AnimationInfo, index: usize) {
+    remap_frame_refs(animation, |i| Some(if i > index { i + 1 } else { i }));
+    let mut frame = animation.frames[index].clone();
+    frame.branching.clear();
+    frame.exit_branch = None;
+    animation.frames.insert(index + 1, frame);
+}
+
+/// remove a frame, branches to it are dropped
+pub fn remove_frame(animation: &mut AnimationInfo, index: usize) {
+    animation.frames.remove(index);
+    remap_frame_refs(animation, |i| match i {
+        i if i == index => None,
+        i if i > index => Some(i - 1),
+        i => Some(i),
+    });
+}
+
+impl AnimationEditor {
+    /// the whole sheet is shown, so it has to fit in a single texture
+    pub fn new(ctx: &Context, pack: &CharacterPack) -> Result<Self, AnimationError> {
+        let image = load_image_as_color_image(&pack.image_data)?;
+        let max_side = ctx.input(|i| i.max_texture_side);
+        if image.width() > max_side || image.height() > max_side {
+            return Err(AnimationError::Format(format!(
+                "the {}x{} sprite sheet is too big to show in the editor, the limit is {}px",
+                image.width(),
+                image.height(),
+                max_side
+            )));
+        }
+
+        let (config, diagnostics) = parse_and_validate(&pack.animation_data, Some(&image));
+        let Some(config) = config else {
+            return Err(AnimationError::Invalid(diagnostics));
+        };
+
+        let metadata = &pack.info.metadata;
+        let save_target = match &pack.info.source {
+            PackSource::Directory(dir) => SaveTarget::File(dir.join(&metadata.animations)),
+            _ => SaveTarget::NewPack {
+                dir: user_packs_dir()
+                    .ok_or_else(|| AnimationError::MissingFile("character pack folder".to_owned()))?
+                    .join(format!("{} (edited)", metadata.name)),
+                image_data: pack.image_data.clone(),
+            },
+        };
+
+        let sprite_sheet = SpriteSheet::new(
+            ctx,
+            "animation_editor_sheet",
+            vec![image.clone()],
+            &config.sprite_sheet_info,
+        );
+        let category = if config.animations.idle.is_empty() {
+            AnimationCategory::Action
+        } else {
+            AnimationCategory::Idle
+        };
+
+        Ok(AnimationEditor {
+            name: metadata.name.to_owned(),
+            config,
+            sprite_sheet,
+            image,
+            save_target,
+            category,
+            animation: 0,
+            frame: 0,
+            playing: false,
+            frame_started: 0.0,
+            dirty: false,
+            saved: false,
+            status: None,
+            open: true,
+        })
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// the pack directory edits are written to
+    pub fn pack_dir(&self) -> Option<PathBuf> {
+        match &self.save_target {
+            SaveTarget::File(path) => path.parent().map(|p| p.to_path_buf()),
+            SaveTarget::NewPack { dir, .. } => Some(dir.to_owned()),
+        }
+    }
+
+    fn category_list(&mut self, category: AnimationCategory) -> &mut Vec<AnimationInfo> {
+        match category {
+            AnimationCategory::Idle => &mut self.config.animations.idle,
+            AnimationCategory::Action => &mut self.config.animations.action,
+        }
+    }
+
+    fn selected(&mut self) -> Option<&mut AnimationInfo> {
+        let index = self.animation;
+        self.category_list(self.category).get_mut(index)
+    }
+
+    fn select(&mut self, category: AnimationCategory, animation: usize) {
+        self.category = category;
+        self.animation = animation;
+        self.frame = 0;
+    }
+
+    /// write the edits out, unless they don't pass validation
+    fn save(&mut self) -> Result<(), AnimationError> {
+        let yaml = serde_yaml::to_string(&self.config)?;
+        let (config, diagnostics) = parse_and_validate(&yaml, Some(&self.image));
+        if config.is_none() {
+            return Err(AnimationError::Invalid(diagnostics));
+        }
+        match &self.save_target {
+            SaveTarget::File(path) => {
+                fs::write(path, yaml)?;
+                self.status = Some(format!("saved to {:?}", path));
+            }
+            SaveTarget::NewPack { dir, image_data } => {
+                let character = ImportedCharacter {
+                    name: format!("{} (edited)", self.name),
+                    config: self.config.clone(),
+                    image_data: image_data.clone(),
+                    sounds: Vec::new(),
+                };
+                let dir = write_pack(dir, &character, "")?;
+                self.status = Some(format!("saved as a new pack in {:?}", dir));
+                // from now on keep editing the copy
+                self.save_target = SaveTarget::File(dir.join("animations.yaml"));
+            }
+        }
+        self.dirty = false;
+        self.saved = true;
+        Ok(())
+    }
+
+    /// advance playback, returns how long until the next frame is due
+    fn tick(&mut self, now: f64) -> Option<f64> {
+        if !self.playing {
+            return None;
+        }
+        let frame = self.frame;
+        let animation = self.selected()?;
+        if animation.frames.is_empty() {
+            return None;
+        }
+        let frame = frame.min(animation.frames.len() - 1);
+        let duration = animation.frames[frame].duration as f64 / 1000.0;
+        let next = (frame + 1) % animation.frames.len();
+
+        let elapsed = now - self.frame_started;
+        if elapsed >= duration {
+            self.frame = next;
+            self.frame_started = now;
+            return Some(0.0);
+        }
+        Some(duration - elapsed)
+    }
+
+    fn animation_list(&mut self, ui: &mut Ui) {
+        let mut select = None;
+        for category in [AnimationCategory::Idle, AnimationCategory::Action] {
+            let title = match category {
+                AnimationCategory::Idle => "idle",
+                AnimationCategory::Action => "action",
+            };
+            let animations = self.category_list(category).clone();
+            egui::CollapsingHeader::new(format!("{} ({})", title, animations.len()))
+                .default_open(true)
+                .show(ui, |ui| {
+                    for (i, animation) in animations.iter().enumerate() {
+                        let selected = self.category == category && self.animation == i;
+                        if ui.selectable_label(selected, &animation.name).clicked() {
+                            select = Some((category, i));
+                        }
+                    }
+                });
+        }
+        if let Some((category, i)) = select {
+            self.select(category, i);
+        }
+    }
+
+    fn playback_controls(&mut self, ui: &mut Ui, frame_count: usize, now: f64) {
+        if frame_count == 0 {
+            return;
+        }
+        ui.horizontal(|ui| {
+            if ui.button(if self.playing { "⏸" } else { "▶" }).clicked() {
+                self.playing = !self.playing;
+                self.frame_started = now;
+            }
+            if ui.button("⏮").on_hover_text("previous frame").clicked() {
+                self.playing = false;
+                self.frame = (self.frame + frame_count - 1) % frame_count;
+            }
+            if ui.button("⏭").on_hover_text("next frame").clicked() {
+                self.playing = false;
+                self.frame = (self.frame + 1) % frame_count;
+            }
+            let mut frame = self.frame;
+            if ui
+                .add(egui::Slider::new(&mut frame, 0..=frame_count - 1).text("frame"))
+                .changed()
+            {
+                self.playing = false;
+                self.frame = frame;
+            }
+        });
+    }
+
+    fn frame_table(&mut self, ui: &mut Ui) {
+        let current = self.frame;
+        let Some(animation) = self.selected() else {
+            return;
+        };
+        let frame_count = animation.frames.len();
+
+        let mut select = None;
+        let mut swap = None;
+        let mut duplicate = None;
+        let mut remove = None;
+        let mut changed = false;
+
+        egui::Grid::new("animation-editor-frames")
+            .striped(true)
+            .show(ui, |ui| {
+                ui.label("#");
+                ui.label("column");
+                ui.label("row");
+                ui.label("duration (ms)");
+                ui.end_row();
+
+                for (i, frame) in animation.frames.iter_mut().enumerate() {
+                    if ui.selectable_label(i == current, i.to_string()).clicked() {
+                        select = Some(i);
+                    }
+                    if let Some(region) = &frame.info.region {
+                        ui.label(format!("{},{}", region.x, region.y));
+                        ui.label(format!("{}x{}", region.width, region.height));
+                    } else {
+                        changed |= ui
+                            .add(egui::DragValue::new(&mut frame.info.column))
+                            .changed();
+                        changed |= ui.add(egui::DragValue::new(&mut frame.info.row)).changed();
+                    }
+                    changed |= ui
+                        .add(egui::DragValue::new(&mut frame.duration).speed(10))
+                        .changed();
+                    ui.horizontal(|ui| {
+                        if ui
+                            .add_enabled(i > 0, egui::Button::new("⬆").small())
+                            .clicked()
+                        {
+                            swap = Some((i, i - 1));
+                        }
+                        if ui
+                            .add_enabled(i + 1 < frame_count, egui::Button::new("⬇").small())
+                            .clicked()
+                        {
+                            swap = Some((i, i + 1));
+                        }
+                        if ui
+                            .small_button("➕")
+                            .on_hover_text("duplicate frame")
+                            .clicked()
+                        {
+                            duplicate = Some(i);
+                        }
+                        if ui
+                            .add_enabled(frame_count > 1, egui::Button::new("🗑").small())
+                            .clicked()
+                        {
+                            remove = Some(i);
+                        }
+                    });
+                    ui.end_row();
+                }
+            });
+
+        if let Some((a, b)) = swap {
+            swap_frames(animation, a, b);
+            select = Some(b);
+        }
+        if let Some(i) = duplicate {
+            duplicate_frame(animation, i);
+            select = Some(i + 1);
+        }
+        if let Some(i) = remove {
+            remove_frame(animation, i);
+            select = Some(i.min(animation.frames.len().saturating_sub(1)));
+        }
+
+        self.dirty |= changed || swap.is_some() || duplicate.is_some() || remove.is_some();
+        if let Some(i) = select {
+            self.playing = false;
+            self.frame = i;
+        }
+    }
+
+    /// the full sheet with the current frame outlined, clicking a cell puts it in the frame
+    fn sheet_view(&mut self, ui: &mut Ui, info: &FrameInfo) {
+        let Some(texture) = self.sprite_sheet.texture(&FrameInfo::default()) else {
+            return;
+        };
+        let size = texture.size_vec2();
+        let texture_id = texture.id();
+        let cell_size = self.sprite_sheet.cell_size();
+        let region = info.source_region([cell_size.x as usize, cell_size.y as usize]);
+
+        let mut clicked_cell = None;
+        egui::ScrollArea::both()
+            .id_source("animation-editor-sheet")
+            .show(ui, |ui| {
+                let (rect, response) = ui.allocate_exact_size(size, Sense::click());
+                let painter = ui.painter_at(rect);
+                painter.rect_filled(rect, 0.0, Color32::from_gray(40));
+                painter.image(
+                    texture_id,
+                    rect,
+                    Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
+                    Color32::WHITE,
+                );
+
+                let highlight = Rect::from_min_size(
+                    rect.min + Vec2::new(region.x as f32, region.y as f32),
+                    Vec2::new(region.width as f32, region.height as f32),
+                );
+                painter.rect_stroke(highlight, 0.0, Stroke::new(2.0, Color32::YELLOW));
+
+                if let Some(pos) = response
+                    .interact_pointer_pos()
+                    .filter(|_| response.clicked())
+                {
+                    let pos = pos - rect.min;
+                    let cell = (
+                        (pos.x / cell_size.x) as usize,
+                        (pos.y / cell_size.y) as usize,
+                    );
+                    // sheets that don't divide evenly have a strip past the last cell
+                    let sheet = &self.config.sprite_sheet_info;
+                    clicked_cell = Some(cell)
+                        .filter(|(column, row)| *column < sheet.columns && *row < sheet.rows);
+                }
+            });
+
+        if let (Some((column, row)), None) = (clicked_cell, &info.region) {
+            let frame = self.frame;
+            if let Some(frame) = self.selected().and_then(|a| a.frames.get_mut(frame)) {
+                frame.info.column = column;
+                frame.info.row = row;
+                self.dirty = true;
+            }
+        }
+    }
+
+    fn animation_details(&mut self, ui: &mut Ui, now: f64) {
+        let category = self.category;
+        let Some(animation) = self.selected().cloned() else {
+            ui.label("pick an animation");
+            return;
+        };
+        if animation.frames.is_empty() {
+            ui.label(format!("{} has no frames", animation.name));
+            return;
+        }
+        self.frame = self.frame.min(animation.frames.len() - 1);
+        let frame = animation.frames[self.frame].clone();
+
+        ui.horizontal(|ui| {
+            ui.heading(&animation.name);
+            let mut new_category = category;
+            egui::ComboBox::from_id_source("animation-editor-category")
+                .selected_text(match category {
+                    AnimationCategory::Idle => "idle",
+                    AnimationCategory::Action => "action",
+                })
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut new_category, AnimationCategory::Idle, "idle");
+                    ui.selectable_value(&mut new_category, AnimationCategory::Action, "action");
+                });
+            if new_category != category {
+                let index = self.animation;
+                let moved = self.category_list(category).remove(index);
+                let list = self.category_list(new_category);
+                list.push(moved);
+                let index = list.len() - 1;
+                self.select(new_category, index);
+                self.dirty = true;
+            }
+        });
+
+        self.playback_controls(ui, animation.frames.len(), now);
+
+        ui.horizontal(|ui| {
+            let (rect, _) = ui.allocate_exact_size(
+                self.sprite_sheet.cell_size() * PREVIEW_SCALE,
+                Sense::hover(),
+            );
+            ui.painter().rect_filled(rect, 4.0, Color32::from_gray(40));
+            self.sprite_sheet
+                .paint_frame(ui.painter(), rect, &frame.info, false);
+
+            ui.vertical(|ui| {
+                ui.label(format!(
+                    "frame {} of {}",
+                    self.frame + 1,
+                    animation.frames.len()
+                ));
+                ui.label(format!(
+                    "column {}, row {}",
+                    frame.info.column, frame.info.row
+                ));
+                ui.label(format!("duration {}ms", frame.duration));
+                if let Some(exit) = frame.exit_branch {
+                    ui.label(format!("exits through frame {}", exit));
+                }
+            });
+        });
+
+        ui.separator();
+        ui.columns(2, |columns| {
+            egui::ScrollArea::vertical()
+                .id_source("animation-editor-table")
+                .show(&mut columns[0], |ui| {
+                    self.frame_table(ui);
+                });
+            self.sheet_view(&mut columns[1], &frame.info);
+        });
+    }
+
+    fn contents(&mut self, ui: &mut Ui, now: f64) {
+        egui::TopBottomPanel::top("animation-editor-toolbar").show_inside(ui, |ui| {
+            ui.horizontal(|ui| {
+                let label = if self.dirty {
+                    "💾 Save*"
+                } else {
+                    "💾 Save"
+                };
+                if ui.button(label).clicked() {
+                    if let Err(e) = self.save() {
+                        self.status = Some(format!("couldn't save: {}", e));
+                    }
+                }
+                if let Some(status) = &self.status {
+                    ui.label(status);
+                }
+            });
+        });
+        egui::SidePanel::left("animation-editor-list").show_inside(ui, |ui| {
+            egui::ScrollArea::vertical().show(ui, |ui| self.animation_list(ui));
+        });
+        egui::CentralPanel::default().show_inside(ui, |ui| self.animation_details(ui, now));
+    }
+
+    /// show the editor in its own window, returns true when the animations were just saved
+    pub fn show(&mut self, ctx: &Context) -> bool {
+        let title = format!("Animations - {}", self.name);
+
+        ctx.show_viewport_immediate(
+            ViewportId::from_hash_of("animation-editor"),
+            ViewportBuilder::default()
+                .with_title(title.to_owned())
+                .with_inner_size([1000.0, 700.0]),
+            |ctx, class| {
+                let now = ctx.input(|i| i.time);
+                if let Some(wait) = self.tick(now) {
+                    ctx.request_repaint_after(std::time::Duration::from_secs_f64(wait));
+                }
+
+                if class == ViewportClass::Embedded {
+                    // no multiple windows on this platform, show it inside the main one
+                    let mut open = self.open;
+                    egui::Window::new(title)
+                        .open(&mut open)
+                        .default_size([800.0, 500.0])
+                        .show(ctx, |ui| self.contents(ui, now));
+                    self.open = open;
+                    return;
+                }
+                if ctx.input(|i| i.viewport().close_requested()) {
+                    self.open = false;
+                }
+                egui::CentralPanel::default().show(ctx, |ui| self.contents(ui, now));
+            },
+        );
+
+        std::mem::take(&mut self.saved)
+    }
+}
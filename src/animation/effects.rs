@@ -0,0 +1,108 @@
+//! Effects painted around the sprite: a shadow and a glow behind it, sparkles in front.
+//! They're driven by `ui.input().time` so they keep moving between animation frames.
+
+use std::f32::consts::TAU;
+
+use egui::Color32;
+use egui::Painter;
+use egui::Pos2;
+use egui::Rect;
+use egui::Shape;
+use egui::Stroke;
+use egui::Vec2;
+
+use super::models::AnimationEffects;
+
+const SPARKLE_COUNT: usize = 8;
+const SPARKLE_COLOR: Color32 = Color32::from_rgb(255, 225, 120);
+const GLOW_COLOR: Color32 = Color32::from_rgb(120, 190, 255);
+/// rings drawn to fake a soft edge
+const GLOW_RINGS: usize = 6;
+
+/// whether the effects change over time and need a repaint every frame
+pub fn is_moving(effects: &AnimationEffects) -> bool {
+    effects.sparkles || effects.glow
+}
+
+/// the colour to multiply the sprite with
+pub fn tint(effects: &AnimationEffects) -> Color32 {
+    match effects.tint {
+        Some([r, g, b, a]) => Color32::from_rgba_unmultiplied(r, g, b, a),
+        None => Color32::WHITE,
+    }
+}
+
+fn ellipse(center: Pos2, radius: Vec2) -> Vec<Pos2> {
+    (0..32)
+        .map(|i| {
+            let angle = i as f32 / 32.0 * TAU;
+            center + Vec2::new(angle.cos() * radius.x, angle.sin() * radius.y)
+        })
+        .collect()
+}
+
+/// effects under the sprite, `rect` is where the sprite goes. Moving effects are left out
+/// when `reduced_motion` is on
+pub fn paint_behind(
+    painter: &Painter,
+    rect: Rect,
+    effects: &AnimationEffects,
+    time: f64,
+    reduced_motion: bool,
+) {
+    if effects.shadow {
+        let center = Pos2::new(rect.center().x, rect.bottom() - rect.height() * 0.06);
+        let radius = Vec2::new(rect.width() * 0.3, rect.height() * 0.05);
+        painter.add(Shape::convex_polygon(
+            ellipse(center, radius),
+            Color32::from_black_alpha(90),
+            Stroke::NONE,
+        ));
+    }
+
+    if effects.glow && !reduced_motion {
+        let pulse = (time as f32 * 3.0).sin() * 0.5 + 0.5;
+        let radius = rect.width().min(rect.height()) * (0.45 + 0.05 * pulse);
+        for ring in 0..GLOW_RINGS {
+            let t = ring as f32 / GLOW_RINGS as f32;
+            let alpha = (18.0 + 30.0 * pulse) * (1.0 - t);
+            painter.circle_filled(
+                rect.center(),
+                radius * (0.6 + 0.4 * t),
+                GLOW_COLOR.gamma_multiply(alpha / 255.0),
+            );
+        }
+    }
+}
+
+/// effects over the sprite
+pub fn paint_front(
+    painter: &Painter,
+    rect: Rect,
+    effects: &AnimationEffects,
+    time: f64,
+    reduced_motion: bool,
+) {
+    if !effects.sparkles || reduced_motion {
+        return;
+    }
+
+    let time = time as f32;
+    let size = rect.width().min(rect.height()) * 0.05;
+    for i in 0..SPARKLE_COUNT {
+        let offset = i as f32 / SPARKLE_COUNT as f32;
+        // each sparkle twinkles on its own beat while they all drift round the character
+        let twinkle = ((time * 2.0 + offset * 3.7) * TAU).sin() * 0.5 + 0.5;
+        let angle = offset * TAU + time * 0.6;
+        let center = rect.center()
+            + Vec2::new(
+                angle.cos() * rect.width() * 0.45,
+                angle.sin() * rect.height() * 0.45,
+            );
+        let color = SPARKLE_COLOR.gamma_multiply(twinkle);
+        let stroke = Stroke::new(size * 0.3, color);
+        let arm = size * (0.5 + twinkle);
+        painter.line_segment([center - Vec2::X * arm, center + Vec2::X * arm], stroke);
+        painter.line_segment([center - Vec2::Y * arm, center + Vec2::Y * arm], stroke);
+    }
+}
@@ -0,0 +1,71 @@
+use std::fmt;
+
+use super::validate::Diagnostic;
+
+#[derive(Debug)]
+pub enum AnimationError {
+    Io(std::io::Error),
+    Yaml(serde_yaml::Error),
+    Zip(zip::result::ZipError),
+    Json(serde_json::Error),
+    Image(image::ImageError),
+    /// the data is readable but doesn't make sense
+    Format(String),
+    /// a file the character pack refers to isn't there
+    MissingFile(String),
+    /// the animation config failed validation
+    Invalid(Vec<Diagnostic>),
+}
+
+impl fmt::Display for AnimationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AnimationError::Io(e) => write!(f, "io error: {}", e),
+            AnimationError::Yaml(e) => write!(f, "yaml error: {}", e),
+            AnimationError::Zip(e) => write!(f, "zip error: {}", e),
+            AnimationError::Json(e) => write!(f, "json error: {}", e),
+            AnimationError::Image(e) => write!(f, "image error: {}", e),
+            AnimationError::Format(msg) => write!(f, "bad format: {}", msg),
+            AnimationError::MissingFile(name) => write!(f, "missing file: {}", name),
+            AnimationError::Invalid(diagnostics) => {
+                write!(f, "invalid animation config")?;
+                for diagnostic in diagnostics {
+                    write!(f, "\n{}", diagnostic)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for AnimationError {}
+
+impl From<std::io::Error> for AnimationError {
+    fn from(e: std::io::Error) -> Self {
+        AnimationError::Io(e)
+    }
+}
+
+impl From<serde_yaml::Error> for AnimationError {
+    fn from(e: serde_yaml::Error) -> Self {
+        AnimationError::Yaml(e)
+    }
+}
+
+impl From<zip::result::ZipError> for AnimationError {
+    fn from(e: zip::result::ZipError) -> Self {
+        AnimationError::Zip(e)
+    }
+}
+
+impl From<serde_json::Error> for AnimationError {
+    fn from(e: serde_json::Error) -> Self {
+        AnimationError::Json(e)
+    }
+}
+
+impl From<image::ImageError> for AnimationError {
+    fn from(e: image::ImageError) -> Self {
+        AnimationError::Image(e)
+    }
+}
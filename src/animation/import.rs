@@ -0,0 +1,100 @@
+pub mod acs;
+pub mod clippyjs;
+pub mod remap;
+
+use image::codecs::png::CompressionType;
+use image::codecs::png::FilterType;
+use image::codecs::png::PngEncoder;
+use image::ImageEncoder;
+use image::RgbaImage;
+
+use super::error::AnimationError;
+use super::models::AnimationCategory;
+use super::models::AnimationConfig;
+use super::models::AnimationInfo;
+use super::models::AnimationSets;
+
+/// a character converted from another format, ready to be written out as a pack
+#[derive(Clone, Debug)]
+pub struct ImportedCharacter {
+    pub name: String,
+    pub config: AnimationConfig,
+    /// png encoded sprite sheet
+    pub image_data: Vec<u8>,
+    /// wave files by the name frames use for them in `sound`
+    pub sounds: Vec<(String, Vec<u8>)>,
+}
+
+/// animations with `pattern` in their name go into `category`
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug)]
+pub struct CategoryRule {
+    pub pattern: String,
+    pub category: AnimationCategory,
+    /// text taken out of the name, stripping "Idle" turns "IdleAtom" into "Atom"
+    #[serde(default)]
+    pub strip: String,
+}
+
+/// how imported animations get sorted into categories, the first matching rule wins
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug)]
+pub struct CategoryRules {
+    pub rules: Vec<CategoryRule>,
+    /// where animations no rule matches go
+    #[serde(default)]
+    pub default: AnimationCategory,
+}
+
+impl Default for CategoryRules {
+    /// anything with "Idle" in its name is an idle animation (minus the "Idle")
+    fn default() -> Self {
+        CategoryRules {
+            rules: vec![CategoryRule {
+                pattern: "Idle".to_owned(),
+                category: AnimationCategory::Idle,
+                strip: "Idle".to_owned(),
+            }],
+            default: AnimationCategory::Action,
+        }
+    }
+}
+
+pub fn categorise(animations: Vec<AnimationInfo>, rules: &CategoryRules) -> AnimationSets {
+    let mut idle = Vec::new();
+    let mut action = Vec::new();
+
+    for mut animation in animations {
+        let rule = rules
+            .rules
+            .iter()
+            .find(|r| animation.name.contains(&r.pattern));
+        let category = match rule {
+            Some(rule) => {
+                if !rule.strip.is_empty() {
+                    animation.name = animation.name.replace(&rule.strip, "");
+                }
+                rule.category
+            }
+            None => rules.default,
+        };
+
+        match category {
+            AnimationCategory::Idle => idle.push(animation),
+            AnimationCategory::Action => action.push(animation),
+        }
+    }
+
+    AnimationSets { idle, action }
+}
+
+pub fn encode_png(image: &RgbaImage) -> Result<Vec<u8>, AnimationError> {
+    let mut buff = Vec::new();
+    // sheets are written once and loaded on every start, worth the slower encode
+    PngEncoder::new_with_quality(&mut buff, CompressionType::Best, FilterType::Adaptive)
+        .write_image(
+            image.as_raw(),
+            image.width(),
+            image.height(),
+            image::ColorType::Rgba8,
+        )?;
+    Ok(buff)
+}
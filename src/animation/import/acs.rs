@@ -0,0 +1,657 @@
+//! Parser for Microsoft Agent `.acs` character files.
+//!
+//! Follows the layout described in "Microsoft Agent Character Data Specification"
+//! (Remy Lebeau), everything is little endian. Frames in an acs file are built from
+//! several images drawn at offsets, they get composited into cells of a uniform grid
+//! so the result can be used like any other sprite sheet.
+
+use std::collections::HashMap;
+
+use image::Rgba;
+use image::RgbaImage;
+
+use super::categorise;
+use super::encode_png;
+use super::CategoryRules;
+use super::ImportedCharacter;
+use crate::animation::error::AnimationError;
+use crate::animation::models::AnimationConfig;
+use crate::animation::models::AnimationFrame;
+use crate::animation::models::AnimationInfo;
+use crate::animation::models::FrameBranch;
+use crate::animation::models::FrameInfo;
+use crate::animation::models::SpriteSheetInfo;
+
+const ACS_SIGNATURE: u32 = 0xABCDABC3;
+
+/// largest image or character side accepted, real agents are a few hundred pixels across
+const MAX_SIDE: u16 = 2048;
+
+/// most pixels the composited sprite sheet can have, 256 MB as rgba
+const MAX_SHEET_PIXELS: usize = 1 << 26;
+
+/// character info flags
+const FLAG_VOICE_ENABLED: u32 = 0x0020;
+const FLAG_BALLOON_ENABLED: u32 = 0x0200;
+
+#[derive(Clone, Copy, Debug)]
+struct Locator {
+    offset: u32,
+    size: u32,
+}
+
+#[derive(Clone, Debug)]
+pub struct AcsCharacterInfo {
+    pub name: String,
+    pub description: String,
+    pub width: u16,
+    pub height: u16,
+    pub transparent_index: u8,
+    /// rgb entries, the image data indexes into this
+    pub palette: Vec<[u8; 3]>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AcsFrameImage {
+    pub image_index: u32,
+    pub x: i16,
+    pub y: i16,
+}
+
+#[derive(Clone, Debug)]
+pub struct AcsFrame {
+    pub images: Vec<AcsFrameImage>,
+    /// `None` when the frame has no sound
+    pub audio_index: Option<u16>,
+    /// in 1/100 of a second
+    pub duration: u16,
+    pub exit_branch: Option<u16>,
+    /// (frame index, probability in percent)
+    pub branches: Vec<(u16, u16)>,
+}
+
+#[derive(Clone, Debug)]
+pub struct AcsAnimation {
+    pub name: String,
+    pub return_animation: String,
+    pub frames: Vec<AcsFrame>,
+}
+
+/// a decoded image, one palette index per pixel, top row first
+#[derive(Clone, Debug)]
+pub struct AcsImage {
+    pub width: u16,
+    pub height: u16,
+    pub pixels: Vec<u8>,
+}
+
+#[derive(Clone, Debug)]
+pub struct AcsFile {
+    pub character: AcsCharacterInfo,
+    pub animations: Vec<AcsAnimation>,
+    pub images: Vec<AcsImage>,
+    /// wave files, `AcsFrame::audio_index` points in here
+    pub sounds: Vec<Vec<u8>>,
+}
+
+fn format_error(msg: impl Into<String>) -> AnimationError {
+    AnimationError::Format(msg.into())
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn at(data: &'a [u8], offset: u32) -> Result<Self, AnimationError> {
+        if offset as usize > data.len() {
+            return Err(format_error(format!(
+                "offset {} is past the end of the file",
+                offset
+            )));
+        }
+        Ok(Reader {
+            data,
+            pos: offset as usize,
+        })
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], AnimationError> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.data.len())
+            .ok_or_else(|| {
+                format_error(format!(
+                    "unexpected end of data reading {} bytes at {}",
+                    len, self.pos
+                ))
+            })?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    /// room to reserve for `count` items of at least `item_size` bytes each,
+    /// counts in the file can't be trusted for more than the data left
+    fn capacity(&self, count: usize, item_size: usize) -> usize {
+        count.min((self.data.len() - self.pos) / item_size.max(1))
+    }
+
+    fn skip(&mut self, len: usize) -> Result<(), AnimationError> {
+        self.bytes(len).map(|_| ())
+    }
+
+    fn u8(&mut self) -> Result<u8, AnimationError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, AnimationError> {
+        let b = self.bytes(2)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    fn i16(&mut self) -> Result<i16, AnimationError> {
+        Ok(self.u16()? as i16)
+    }
+
+    fn u32(&mut self) -> Result<u32, AnimationError> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn locator(&mut self) -> Result<Locator, AnimationError> {
+        Ok(Locator {
+            offset: self.u32()?,
+            size: self.u32()?,
+        })
+    }
+
+    /// utf-16 string, a character count followed by the characters and a null terminator
+    fn string(&mut self) -> Result<String, AnimationError> {
+        let len = self.u32()? as usize;
+        if len == 0 {
+            return Ok(String::new());
+        }
+        let raw = self.bytes(len * 2)?;
+        self.skip(2)?;
+        let chars: Vec<u16> = raw
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .collect();
+        Ok(String::from_utf16_lossy(&chars))
+    }
+
+    fn data_block(&mut self) -> Result<&'a [u8], AnimationError> {
+        let len = self.u32()? as usize;
+        self.bytes(len)
+    }
+}
+
+/// reads bits least significant first, the way the acs compression writes them
+struct BitReader<'a> {
+    data: &'a [u8],
+    bit: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn bit(&mut self) -> Result<u32, AnimationError> {
+        let byte = self
+            .data
+            .get(self.bit / 8)
+            .ok_or_else(|| format_error("compressed image data ended early"))?;
+        let bit = (byte >> (self.bit % 8)) & 1;
+        self.bit += 1;
+        Ok(bit as u32)
+    }
+
+    fn bits(&mut self, count: u32) -> Result<u32, AnimationError> {
+        let mut value = 0;
+        for i in 0..count {
+            value |= self.bit()? << i;
+        }
+        Ok(value)
+    }
+}
+
+/// undo the acs image compression, an lz77 style scheme packed at the bit level
+pub fn decompress(data: &[u8], expected_len: usize) -> Result<Vec<u8>, AnimationError> {
+    if data.first() != Some(&0) {
+        return Err(format_error(
+            "compressed data doesn't start with a zero byte",
+        ));
+    }
+
+    // literal bytes take 9 bits, don't trust `expected_len` for much more than that
+    let mut out = Vec::with_capacity(expected_len.min(data.len().saturating_mul(8)));
+    let mut reader = BitReader { data, bit: 8 };
+
+    while out.len() < expected_len {
+        if reader.bit()? == 0 {
+            out.push(reader.bits(8)? as u8);
+            continue;
+        }
+
+        let mut count = 2;
+        let offset = if reader.bit()? == 0 {
+            reader.bits(6)? + 1
+        } else if reader.bit()? == 0 {
+            reader.bits(9)? + 65
+        } else if reader.bit()? == 0 {
+            reader.bits(12)? + 577
+        } else {
+            let offset = reader.bits(20)?;
+            if offset == 0x000F_FFFF {
+                break;
+            }
+            count += 1;
+            offset + 4673
+        } as usize;
+
+        let mut run = 0;
+        while reader.bit()? == 1 {
+            run += 1;
+            if run > 11 {
+                return Err(format_error("bad run length in compressed data"));
+            }
+        }
+        if run > 0 {
+            count += reader.bits(run)? as usize + (1 << run) - 1;
+        }
+
+        if offset > out.len() {
+            return Err(format_error("compressed data refers back past the start"));
+        }
+        // byte by byte, the source and destination may overlap
+        for _ in 0..count {
+            out.push(out[out.len() - offset]);
+        }
+    }
+
+    if out.len() < expected_len {
+        return Err(format_error(
+            "compressed image is smaller than its dimensions",
+        ));
+    }
+    out.truncate(expected_len);
+    Ok(out)
+}
+
+fn read_character_info(data: &[u8], locator: Locator) -> Result<AcsCharacterInfo, AnimationError> {
+    let mut r = Reader::at(data, locator.offset)?;
+    let _minor_version = r.u16()?;
+    let _major_version = r.u16()?;
+    let localized = r.locator()?;
+    r.skip(16)?; // guid
+    let width = r.u16()?;
+    let height = r.u16()?;
+    let transparent_index = r.u8()?;
+    let flags = r.u32()?;
+    let _anim_set_major = r.u16()?;
+    let _anim_set_minor = r.u16()?;
+
+    if flags & FLAG_VOICE_ENABLED != 0 {
+        r.skip(16 + 16 + 4 + 2)?; // engine id, mode id, speed, pitch
+        if r.u8()? != 0 {
+            let _lang_id = r.u16()?;
+            let _dialect = r.string()?;
+            let _gender = r.u16()?;
+            let _age = r.u16()?;
+            let _style = r.string()?;
+        }
+    }
+
+    if flags & FLAG_BALLOON_ENABLED != 0 {
+        r.skip(1 + 1 + 4 * 3)?; // lines, chars per line, fg/bg/border colours
+        let _font_name = r.string()?;
+        r.skip(4 + 4 + 1 + 1)?; // font height, weight, italic, unknown
+    }
+
+    let palette_len = r.u32()? as usize;
+    let mut palette = Vec::with_capacity(r.capacity(palette_len, 4));
+    for _ in 0..palette_len {
+        let quad = r.bytes(4)?;
+        // stored as blue, green, red, reserved
+        palette.push([quad[2], quad[1], quad[0]]);
+    }
+
+    // the name lives in the localised info, the first entry will do
+    let (mut name, mut description) = (String::new(), String::new());
+    if localized.size > 0 {
+        let mut r = Reader::at(data, localized.offset)?;
+        if r.u16()? > 0 {
+            let _lang_id = r.u16()?;
+            name = r.string()?;
+            description = r.string()?;
+        }
+    }
+
+    Ok(AcsCharacterInfo {
+        name,
+        description,
+        width,
+        height,
+        transparent_index,
+        palette,
+    })
+}
+
+fn read_animation(data: &[u8], locator: Locator) -> Result<AcsAnimation, AnimationError> {
+    let mut r = Reader::at(data, locator.offset)?;
+    let name = r.string()?;
+    let _transition_type = r.u8()?;
+    let return_animation = r.string()?;
+
+    let frame_count = r.u16()?;
+    // image count, audio, duration, exit branch, branch and overlay counts
+    let mut frames = Vec::with_capacity(r.capacity(frame_count as usize, 10));
+    for _ in 0..frame_count {
+        let image_count = r.u16()?;
+        let mut images = Vec::with_capacity(r.capacity(image_count as usize, 8));
+        for _ in 0..image_count {
+            images.push(AcsFrameImage {
+                image_index: r.u32()?,
+                x: r.i16()?,
+                y: r.i16()?,
+            });
+        }
+
+        let audio_index = r.u16()?;
+        let duration = r.u16()?;
+        let exit_branch = r.i16()?;
+
+        let branch_count = r.u8()?;
+        let mut branches = Vec::with_capacity(r.capacity(branch_count as usize, 4));
+        for _ in 0..branch_count {
+            branches.push((r.u16()?, r.u16()?));
+        }
+
+        // mouth overlays for lip sync, not used
+        let overlay_count = r.u8()?;
+        for _ in 0..overlay_count {
+            r.skip(1 + 1 + 2 + 1)?; // type, replace flag, image index, unknown
+            let has_region = r.u8()?;
+            r.skip(2 * 4)?; // x, y, width, height
+            if has_region != 0 {
+                r.data_block()?;
+            }
+        }
+
+        frames.push(AcsFrame {
+            images,
+            audio_index: if audio_index == 0xFFFF {
+                None
+            } else {
+                Some(audio_index)
+            },
+            duration,
+            exit_branch: if exit_branch < 0 {
+                None
+            } else {
+                Some(exit_branch as u16)
+            },
+            branches,
+        });
+    }
+
+    Ok(AcsAnimation {
+        name,
+        return_animation,
+        frames,
+    })
+}
+
+fn read_image(data: &[u8], locator: Locator) -> Result<AcsImage, AnimationError> {
+    let mut r = Reader::at(data, locator.offset)?;
+    let _unknown = r.u8()?;
+    let width = r.u16()?;
+    let height = r.u16()?;
+    let compressed = r.u8()? != 0;
+    let raw = r.data_block()?;
+    if width > MAX_SIDE || height > MAX_SIDE {
+        return Err(format_error(format!(
+            "image is {}x{}, bigger than {} pixels across",
+            width, height, MAX_SIDE
+        )));
+    }
+
+    if width == 0 || height == 0 {
+        return Ok(AcsImage {
+            width,
+            height,
+            pixels: Vec::new(),
+        });
+    }
+
+    // rows are padded to 4 bytes and stored bottom up
+    let stride = (width as usize + 3) & !3;
+    let len = stride * height as usize;
+    let decoded = if compressed {
+        decompress(raw, len)?
+    } else if raw.len() >= len {
+        raw[..len].to_vec()
+    } else {
+        return Err(format_error("image data is smaller than its dimensions"));
+    };
+
+    let mut pixels = Vec::with_capacity(width as usize * height as usize);
+    for row in decoded.chunks_exact(stride).rev() {
+        pixels.extend_from_slice(&row[..width as usize]);
+    }
+
+    Ok(AcsImage {
+        width,
+        height,
+        pixels,
+    })
+}
+
+/// parse an entire `.acs` file
+pub fn parse(data: &[u8]) -> Result<AcsFile, AnimationError> {
+    let mut header = Reader::at(data, 0)?;
+    if header.u32()? != ACS_SIGNATURE {
+        return Err(format_error("not an acs file, bad signature"));
+    }
+    let character_locator = header.locator()?;
+    let animations_locator = header.locator()?;
+    let images_locator = header.locator()?;
+    let audio_locator = header.locator()?;
+
+    let character = read_character_info(data, character_locator)?;
+
+    let mut r = Reader::at(data, animations_locator.offset)?;
+    let count = r.u32()?;
+    // name length and locator
+    let mut animations = Vec::with_capacity(r.capacity(count as usize, 12));
+    for _ in 0..count {
+        let _name = r.string()?;
+        animations.push(read_animation(data, r.locator()?)?);
+    }
+
+    let mut r = Reader::at(data, images_locator.offset)?;
+    let count = r.u32()?;
+    // locator and checksum
+    let mut images = Vec::with_capacity(r.capacity(count as usize, 12));
+    for _ in 0..count {
+        let locator = r.locator()?;
+        let _checksum = r.u32()?;
+        images.push(read_image(data, locator)?);
+    }
+
+    let mut r = Reader::at(data, audio_locator.offset)?;
+    let count = r.u32()?;
+    // locator and checksum, the sound is a whole wave file
+    let mut sounds = Vec::with_capacity(r.capacity(count as usize, 12));
+    for _ in 0..count {
+        let locator = r.locator()?;
+        let _checksum = r.u32()?;
+        sounds.push(
+            Reader::at(data, locator.offset)?
+                .bytes(locator.size as usize)?
+                .to_vec(),
+        );
+    }
+
+    Ok(AcsFile {
+        character,
+        animations,
+        images,
+        sounds,
+    })
+}
+
+impl AcsFile {
+    /// draw a frame's images into a character sized cell
+    fn composite(&self, frame: &AcsFrame) -> Result<RgbaImage, AnimationError> {
+        let info = &self.character;
+        let mut cell = RgbaImage::new(info.width as u32, info.height as u32);
+
+        // the first image is the top most, so paint from the back
+        for placement in frame.images.iter().rev() {
+            let image = self
+                .images
+                .get(placement.image_index as usize)
+                .ok_or_else(|| {
+                    format_error(format!(
+                        "frame refers to missing image {}",
+                        placement.image_index
+                    ))
+                })?;
+
+            for y in 0..image.height as i32 {
+                for x in 0..image.width as i32 {
+                    let (cx, cy) = (x + placement.x as i32, y + placement.y as i32);
+                    if cx < 0 || cy < 0 || cx >= cell.width() as i32 || cy >= cell.height() as i32 {
+                        continue;
+                    }
+                    let index = image.pixels[(y * image.width as i32 + x) as usize];
+                    if index == info.transparent_index {
+                        continue;
+                    }
+                    let [r, g, b] = info
+                        .palette
+                        .get(index as usize)
+                        .copied()
+                        .unwrap_or_default();
+                    cell.put_pixel(cx as u32, cy as u32, Rgba([r, g, b, 255]));
+                }
+            }
+        }
+        Ok(cell)
+    }
+
+    /// composite every frame into a sprite sheet and build the matching `AnimationConfig`,
+    /// frames made of the same images share a cell
+    pub fn to_character(&self) -> Result<ImportedCharacter, AnimationError> {
+        let info = &self.character;
+        if info.width == 0 || info.height == 0 {
+            return Err(format_error("character has no size"));
+        }
+        if info.width > MAX_SIDE || info.height > MAX_SIDE {
+            return Err(format_error(format!(
+                "character is {}x{}, bigger than {} pixels across",
+                info.width, info.height, MAX_SIDE
+            )));
+        }
+        let cell_pixels = info.width as usize * info.height as usize;
+
+        let mut cells: Vec<RgbaImage> = Vec::new();
+        let mut cell_lookup: HashMap<Vec<(u32, i16, i16)>, usize> = HashMap::new();
+        let mut animations = Vec::with_capacity(self.animations.len());
+
+        for animation in &self.animations {
+            let mut frames = Vec::with_capacity(animation.frames.len());
+            for frame in &animation.frames {
+                let key: Vec<_> = frame
+                    .images
+                    .iter()
+                    .map(|i| (i.image_index, i.x, i.y))
+                    .collect();
+                let cell = match cell_lookup.get(&key) {
+                    Some(cell) => *cell,
+                    None => {
+                        if (cells.len() + 1) * cell_pixels > MAX_SHEET_PIXELS {
+                            return Err(format_error(
+                                "too many distinct frames to fit in a sprite sheet",
+                            ));
+                        }
+                        cells.push(self.composite(frame)?);
+                        cell_lookup.insert(key, cells.len() - 1);
+                        cells.len() - 1
+                    }
+                };
+                frames.push((cell, frame));
+            }
+            animations.push((animation, frames));
+        }
+
+        let columns = (cells.len() as f64).sqrt().ceil().max(1.0) as usize;
+        let rows = ((cells.len() + columns - 1) / columns).max(1);
+        let (width, height) = (info.width as u32, info.height as u32);
+        let mut sheet = RgbaImage::new(columns as u32 * width, rows as u32 * height);
+        for (i, cell) in cells.iter().enumerate() {
+            let (column, row) = ((i % columns) as i64, (i / columns) as i64);
+            image::imageops::replace(&mut sheet, cell, column * width as i64, row * height as i64);
+        }
+
+        let animations = animations
+            .into_iter()
+            .map(|(animation, frames)| AnimationInfo {
+                name: animation.name.to_owned(),
+                frames: frames
+                    .into_iter()
+                    .map(|(cell, frame)| AnimationFrame {
+                        duration: frame.duration as usize * 10,
+                        info: FrameInfo {
+                            column: cell % columns,
+                            row: cell / columns,
+                            ..Default::default()
+                        },
+                        branching: frame
+                            .branches
+                            .iter()
+                            .map(|(frame_index, weight)| FrameBranch {
+                                frame_index: *frame_index as usize,
+                                weight: *weight as u32,
+                            })
+                            .collect(),
+                        exit_branch: frame.exit_branch.map(|b| b as usize),
+                        sound: frame.audio_index.map(|i| i.to_string()),
+                        ..Default::default()
+                    })
+                    .collect(),
+                ..Default::default()
+            })
+            .collect();
+
+        Ok(ImportedCharacter {
+            name: if info.name.is_empty() {
+                "Agent".to_owned()
+            } else {
+                info.name.to_owned()
+            },
+            config: AnimationConfig {
+                animations: categorise(animations, &CategoryRules::default()),
+                sprite_sheet_info: SpriteSheetInfo {
+                    columns,
+                    rows,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            image_data: encode_png(&sheet)?,
+            sounds: self
+                .sounds
+                .iter()
+                .enumerate()
+                .map(|(i, sound)| (i.to_string(), sound.clone()))
+                .collect(),
+        })
+    }
+}
+
+/// parse an `.acs` file and convert it for use with `AnimationService`
+pub fn import(data: &[u8]) -> Result<ImportedCharacter, AnimationError> {
+    parse(data)?.to_character()
+}
@@ -0,0 +1,205 @@
+//! Converts ClippyJS agents (`agent.js` + `map.png`) into an `AnimationConfig`.
+//!
+//! `agent.js` is a call to `clippy.ready('Name', {...})` where the object is plain json,
+//! `map.png` is already a uniform grid of `framesize` cells so it's used as the sprite sheet.
+
+use std::collections::BTreeMap;
+
+use image::RgbaImage;
+
+use super::categorise;
+use super::encode_png;
+use super::CategoryRules;
+use super::ImportedCharacter;
+use crate::animation::error::AnimationError;
+use crate::animation::models::AnimationConfig;
+use crate::animation::models::AnimationFrame;
+use crate::animation::models::AnimationInfo;
+use crate::animation::models::FrameBranch;
+use crate::animation::models::FrameInfo;
+use crate::animation::models::FrameLayer;
+use crate::animation::models::SpriteSheetInfo;
+
+#[derive(serde::Deserialize, Debug)]
+struct AgentData {
+    framesize: [u32; 2],
+    animations: BTreeMap<String, AgentAnimation>,
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct AgentAnimation {
+    frames: Vec<AgentFrame>,
+}
+
+#[derive(serde::Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct AgentFrame {
+    duration: usize,
+    /// pixel positions on `map.png`, one per overlaid image
+    #[serde(default)]
+    images: Vec<[u32; 2]>,
+    exit_branch: Option<usize>,
+    branching: Option<AgentBranching>,
+    /// id into the agent's sounds file
+    sound: Option<String>,
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct AgentBranching {
+    branches: Vec<AgentBranch>,
+}
+
+#[derive(serde::Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct AgentBranch {
+    frame_index: usize,
+    weight: u32,
+}
+
+/// the name passed to `clippy.ready`, falls back to "Agent"
+pub fn agent_name(agent_js: &str) -> String {
+    agent_js
+        .split_once("clippy.ready(")
+        .and_then(|(_, rest)| {
+            let mut chars = rest.trim_start().chars();
+            let quote = chars.next()?;
+            chars.as_str().split(quote).next()
+        })
+        .filter(|name| !name.is_empty())
+        .unwrap_or("Agent")
+        .to_owned()
+}
+
+fn agent_json(agent_js: &str) -> Result<&str, AnimationError> {
+    let start = agent_js.find('{');
+    let end = agent_js.rfind('}');
+    match (start, end) {
+        (Some(start), Some(end)) if start < end => Ok(&agent_js[start..=end]),
+        _ => Err(AnimationError::Format(
+            "agent.js doesn't contain an agent definition".to_owned(),
+        )),
+    }
+}
+
+fn is_transparent_cell(sheet: &RgbaImage, column: u32, row: u32, width: u32, height: u32) -> bool {
+    (0..height)
+        .all(|y| (0..width).all(|x| sheet.get_pixel(column * width + x, row * height + y)[3] == 0))
+}
+
+/// frames without images are blank, point them at an empty cell,
+/// growing the sheet by a row if there isn't one
+fn blank_cell(sheet: &mut RgbaImage, width: u32, height: u32) -> FrameInfo {
+    let columns = sheet.width() / width;
+    let rows = sheet.height() / height;
+
+    for row in 0..rows {
+        for column in 0..columns {
+            if is_transparent_cell(sheet, column, row, width, height) {
+                return FrameInfo {
+                    column: column as _,
+                    row: row as _,
+                    ..Default::default()
+                };
+            }
+        }
+    }
+
+    let mut grown = RgbaImage::new(sheet.width(), (rows + 1) * height);
+    image::imageops::replace(&mut grown, sheet, 0, 0);
+    *sheet = grown;
+    FrameInfo {
+        column: 0,
+        row: rows as _,
+        ..Default::default()
+    }
+}
+
+/// convert an `agent.js` and its `map.png` into an `ImportedCharacter`
+pub fn import(agent_js: &str, map_png: &[u8]) -> Result<ImportedCharacter, AnimationError> {
+    let agent: AgentData = serde_json::from_str(agent_json(agent_js)?)?;
+    let [width, height] = agent.framesize;
+    if width == 0 || height == 0 {
+        return Err(AnimationError::Format(
+            "framesize must not be zero".to_owned(),
+        ));
+    }
+
+    let mut sheet = image::load_from_memory(map_png)?.to_rgba8();
+    let original_height = sheet.height();
+    let mut blank: Option<FrameInfo> = None;
+
+    let mut animations = Vec::new();
+    for (name, animation) in agent.animations {
+        let mut frames = Vec::new();
+        for frame in animation.frames {
+            // the first image is the frame's own, the rest are overlays on top of it
+            let cell_size = [width as usize, height as usize];
+            let mut info = match frame.images.first() {
+                Some([x, y]) => {
+                    // off the grid it keeps its exact pixels as a region
+                    let image = FrameLayer::at_pixel(*x as usize, *y as usize, cell_size);
+                    FrameInfo {
+                        column: image.column,
+                        row: image.row,
+                        region: image.region,
+                        ..Default::default()
+                    }
+                }
+                None => blank
+                    .get_or_insert_with(|| blank_cell(&mut sheet, width, height))
+                    .clone(),
+            };
+            info.layers = frame
+                .images
+                .iter()
+                .skip(1)
+                .map(|[x, y]| FrameLayer::at_pixel(*x as usize, *y as usize, cell_size))
+                .collect();
+
+            frames.push(AnimationFrame {
+                duration: frame.duration,
+                info,
+                branching: frame
+                    .branching
+                    .map(|b| b.branches)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|b| FrameBranch {
+                        frame_index: b.frame_index,
+                        weight: b.weight,
+                    })
+                    .collect(),
+                exit_branch: frame.exit_branch,
+                sound: frame.sound,
+                ..Default::default()
+            });
+        }
+        animations.push(AnimationInfo {
+            name,
+            frames,
+            ..Default::default()
+        });
+    }
+
+    let image_data = if sheet.height() == original_height {
+        map_png.to_vec()
+    } else {
+        encode_png(&sheet)?
+    };
+
+    Ok(ImportedCharacter {
+        name: agent_name(agent_js),
+        config: AnimationConfig {
+            animations: categorise(animations, &CategoryRules::default()),
+            sprite_sheet_info: SpriteSheetInfo {
+                columns: (sheet.width() / width) as _,
+                rows: (sheet.height() / height) as _,
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        image_data,
+        // the sounds live in separate sounds-*.js files
+        sounds: Vec::new(),
+    })
+}
@@ -0,0 +1,135 @@
+//! Converts the `animations.json` format (`Name`, `Frames` with `dur`/`col`/`row`)
+//! into an `AnimationConfig`, this used to be `assets/remap_animations.py`.
+//! `ImagesOffsets` are sprite sheet pixel positions of extra images stacked on the frame,
+//! they become layers.
+
+use std::collections::BTreeMap;
+
+use super::categorise;
+use super::CategoryRules;
+use crate::animation::error::AnimationError;
+use crate::animation::models::AnimationConfig;
+use crate::animation::models::AnimationFrame;
+use crate::animation::models::AnimationInfo;
+use crate::animation::models::FrameInfo;
+use crate::animation::models::FrameLayer;
+use crate::animation::models::SpriteSheetInfo;
+
+#[derive(serde::Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct JsonAnimation {
+    name: String,
+    frames: Vec<JsonFrame>,
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct JsonFrame {
+    #[serde(default)]
+    dur: usize,
+    #[serde(default)]
+    col: usize,
+    #[serde(default)]
+    row: usize,
+    #[serde(rename = "ImagesOffsets", default)]
+    images_offsets: Option<Vec<[i32; 2]>>,
+    /// anything else, reported so nothing gets dropped silently
+    #[serde(flatten)]
+    other: BTreeMap<String, serde_json::Value>,
+}
+
+/// work out the sprite sheet grid from the image and frame sizes
+pub fn infer_grid(
+    sheet_size: [u32; 2],
+    frame_size: [u32; 2],
+) -> Result<SpriteSheetInfo, AnimationError> {
+    let [sheet_width, sheet_height] = sheet_size;
+    let [width, height] = frame_size;
+    if width == 0 || height == 0 {
+        return Err(AnimationError::Format(
+            "frame size must not be zero".to_owned(),
+        ));
+    }
+    if sheet_width % width != 0 || sheet_height % height != 0 {
+        return Err(AnimationError::Format(format!(
+            "a {}x{} sprite sheet isn't a whole number of {}x{} frames",
+            sheet_width, sheet_height, width, height
+        )));
+    }
+
+    Ok(SpriteSheetInfo {
+        columns: (sheet_width / width) as _,
+        rows: (sheet_height / height) as _,
+        ..Default::default()
+    })
+}
+
+/// convert `animations.json` for a sheet of `sheet_size` pixels split into `frame_size` cells
+pub fn remap(
+    json: &str,
+    sheet_size: [u32; 2],
+    frame_size: [u32; 2],
+    rules: &CategoryRules,
+) -> Result<AnimationConfig, AnimationError> {
+    let animations: Vec<JsonAnimation> = serde_json::from_str(json)?;
+
+    let animations = animations
+        .into_iter()
+        .map(|animation| {
+            let frames = animation
+                .frames
+                .into_iter()
+                .map(|frame| {
+                    for key in frame.other.keys() {
+                        log::warn!(
+                            "{}: frame field {:?} isn't supported, dropping it",
+                            animation.name,
+                            key
+                        );
+                    }
+                    let cell_size = [frame_size[0] as usize, frame_size[1] as usize];
+                    let base = [frame.col * cell_size[0], frame.row * cell_size[1]];
+                    let layers = frame
+                        .images_offsets
+                        .unwrap_or_default()
+                        .into_iter()
+                        .filter_map(|[x, y]| {
+                            if x < 0 || y < 0 {
+                                log::warn!(
+                                    "{}: image at {},{} is off the sprite sheet, dropping it",
+                                    animation.name,
+                                    x,
+                                    y
+                                );
+                                return None;
+                            }
+                            // the frame's own cell is already drawn
+                            let at = [x as usize, y as usize];
+                            (at != base).then(|| FrameLayer::at_pixel(at[0], at[1], cell_size))
+                        })
+                        .collect();
+                    AnimationFrame {
+                        duration: frame.dur,
+                        info: FrameInfo {
+                            column: frame.col,
+                            row: frame.row,
+                            layers,
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    }
+                })
+                .collect();
+            AnimationInfo {
+                name: animation.name,
+                frames,
+                ..Default::default()
+            }
+        })
+        .collect();
+
+    Ok(AnimationConfig {
+        animations: categorise(animations, rules),
+        sprite_sheet_info: infer_grid(sheet_size, frame_size)?,
+        ..Default::default()
+    })
+}
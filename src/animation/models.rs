@@ -1,37 +1,652 @@
+use std::collections::BTreeMap;
+
+use crate::actions::Interaction;
+use crate::actions::LifecycleEvent;
+
+fn is_default<T: Default + PartialEq>(v: &T) -> bool {
+    *v == T::default()
+}
+
+/// a rectangle on a packed atlas, `offset_x`/`offset_y` place it inside the frame
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct AtlasRegion{
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+    #[serde(default)]
+    pub offset_x: usize,
+    #[serde(default)]
+    pub offset_y: usize
+}
+
+/// another image drawn over a frame, e.g. an accessory or an overlay from agent data
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, Default, PartialEq)]
+pub struct FrameLayer{
+    pub column: usize,
+    pub row: usize,
+    /// pixel offset (in sprite sheet pixels) from where the frame is drawn
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub offset_x: i32,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub offset_y: i32,
+    /// exact pixels on the sheet, used instead of `column`/`row` when set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<AtlasRegion>,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub page: usize
+}
+
+impl FrameLayer {
+    /// the layer at pixel `x`,`y` on the sheet, using the grid when it lines up
+    pub fn at_pixel(x: usize, y: usize, cell_size: [usize; 2]) -> Self {
+        let [width, height] = cell_size;
+        if width > 0 && height > 0 && x % width == 0 && y % height == 0 {
+            return FrameLayer { column: x / width, row: y / height, ..Default::default() };
+        }
+        FrameLayer {
+            region: Some(AtlasRegion { x, y, width, height, offset_x: 0, offset_y: 0 }),
+            ..Default::default()
+        }
+    }
+
+    /// the pixels this layer is drawn from
+    pub fn source_region(&self, cell_size: [usize; 2]) -> AtlasRegion {
+        FrameInfo { column: self.column, row: self.row, region: self.region.clone(), ..Default::default() }
+            .source_region(cell_size)
+    }
+}
+
 #[derive(serde::Deserialize, serde::Serialize, Clone, Debug, Default)]
 pub struct FrameInfo{
     pub column: usize,
-    pub row: usize
+    pub row: usize,
+    /// pixel offset (in sprite sheet pixels) applied when drawing this frame
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub offset_x: i32,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub offset_y: i32,
+    /// drawn over the frame's own cell, bottom to top
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub layers: Vec<FrameLayer>,
+    /// where the pixels are on a packed atlas, used instead of `column`/`row` when set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<AtlasRegion>,
+    /// which texture the frame is on when the sheet is split into pages, see `atlas::pack`
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub page: usize
+}
+
+impl FrameInfo {
+    /// the pixels this frame is drawn from, a whole grid cell unless it has an atlas region
+    pub fn source_region(&self, cell_size: [usize; 2]) -> AtlasRegion {
+        match &self.region {
+            Some(region) => region.clone(),
+            None => AtlasRegion {
+                x: self.column * cell_size[0],
+                y: self.row * cell_size[1],
+                width: cell_size[0],
+                height: cell_size[1],
+                offset_x: 0,
+                offset_y: 0
+            }
+        }
+    }
+
+    /// the frame's own image then its layers, bottom to top, each as a frame without layers
+    pub fn images(&self) -> impl Iterator<Item = FrameInfo> + '_ {
+        let base = FrameInfo { layers: Vec::new(), ..self.clone() };
+        let layers = self.layers.iter().map(|layer| FrameInfo {
+            column: layer.column,
+            row: layer.row,
+            offset_x: self.offset_x + layer.offset_x,
+            offset_y: self.offset_y + layer.offset_y,
+            layers: Vec::new(),
+            region: layer.region.clone(),
+            page: layer.page
+        });
+        std::iter::once(base).chain(layers)
+    }
+}
+
+/// a jump to another frame of the same animation, taken with `weight` percent probability
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, Default, PartialEq)]
+pub struct FrameBranch{
+    pub frame_index: usize,
+    pub weight: u32
 }
 
 #[derive(serde::Deserialize, serde::Serialize, Clone, Debug, Default)]
 pub struct AnimationFrame{
     pub duration: usize,
-    pub info: FrameInfo
+    pub info: FrameInfo,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub branching: Vec<FrameBranch>,
+    /// frame to go to when the animation is interrupted, lets it wind down gracefully
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_branch: Option<usize>,
+    /// sound played when the frame is shown, see `audio::AudioSink`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sound: Option<String>,
+    /// sent as `DispatchActions::AnimationEvent` when the frame is shown
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event: Option<String>
+}
+
+/// drawn around the sprite with the egui painter, see `effects`
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, Default, PartialEq)]
+pub struct AnimationEffects{
+    /// twinkling stars around the character
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub sparkles: bool,
+    /// a pulsing glow behind the character
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub glow: bool,
+    /// a shadow on the ground under the character
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub shadow: bool,
+    /// rgba multiplied into the sprite's colours
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tint: Option<[u8; 4]>
+}
+
+impl AnimationEffects {
+    /// these effects with `other`'s added on top
+    pub fn merged(&self, other: &AnimationEffects) -> AnimationEffects {
+        AnimationEffects {
+            sparkles: self.sparkles || other.sparkles,
+            glow: self.glow || other.glow,
+            shadow: self.shadow || other.shadow,
+            tint: other.tint.or(self.tint)
+        }
+    }
+}
+
+/// app events a state machine transition can wait for
+#[derive(serde::Deserialize, serde::Serialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Trigger{
+    /// a question went to the assistant
+    QuestionAsked,
+    /// the assistant answered, answers arrive in one piece so it fires once per answer
+    #[serde(alias = "answer_chunk")]
+    Answer,
+    /// the answer finished typing out on screen
+    Revealed,
+    /// the assistant reported an error
+    Error,
+    /// the state's `timeout_secs` passed without any input
+    Timeout,
+    /// an animation played in the state has played to the end
+    Done,
+    Click,
+    DoubleClick,
+    Drag
+}
+
+/// a state of the `StateMachine`: what plays in it and where triggers take it
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, Default, PartialEq)]
+pub struct MachineState{
+    /// plays from this category, `idle` brings the rest pose, boredom and looking around
+    #[serde(default)]
+    pub category: AnimationCategory,
+    /// play only these, by weight, instead of the whole category. The category is the fallback
+    /// for characters that have none of them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub animations: Vec<String>,
+    /// the state each trigger moves to, triggers that aren't here are ignored
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub on: BTreeMap<Trigger, String>,
+    /// seconds without input before the `timeout` trigger fires
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u64>
+}
+
+impl MachineState {
+    /// the mode the app sees while the machine is in this state
+    pub fn mode(&self) -> AnimationServiceMode {
+        match self.category {
+            AnimationCategory::Idle => AnimationServiceMode::Idle,
+            AnimationCategory::Action => AnimationServiceMode::Active
+        }
+    }
+}
+
+/// states the character moves between on app events. The default idles until a question is
+/// asked, plays actions until it's answered, talks while the answer types out and settles
+/// back into its rest pose
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, PartialEq)]
+pub struct StateMachine{
+    pub initial: String,
+    pub states: BTreeMap<String, MachineState>
+}
+
+impl Default for StateMachine {
+    fn default() -> Self {
+        let idle = MachineState {
+            category: AnimationCategory::Idle,
+            on: BTreeMap::from([(Trigger::QuestionAsked, "active".to_owned())]),
+            ..Default::default()
+        };
+        let active = MachineState {
+            category: AnimationCategory::Action,
+            on: BTreeMap::from([(Trigger::Answer, "talking".to_owned())]),
+            ..Default::default()
+        };
+        let talking = MachineState {
+            category: AnimationCategory::Action,
+            animations: ["Explain", "Writing", "GestureLeft", "GestureRight", "GestureUp", "GestureDown"]
+                .map(str::to_owned)
+                .to_vec(),
+            on: BTreeMap::from([
+                (Trigger::Revealed, "closing".to_owned()),
+                (Trigger::QuestionAsked, "active".to_owned())
+            ]),
+            ..Default::default()
+        };
+        let closing = MachineState {
+            category: AnimationCategory::Idle,
+            animations: vec!["RestPose".to_owned()],
+            on: BTreeMap::from([
+                (Trigger::Done, "idle".to_owned()),
+                (Trigger::QuestionAsked, "active".to_owned())
+            ]),
+            ..Default::default()
+        };
+        StateMachine {
+            initial: "idle".to_owned(),
+            states: BTreeMap::from([
+                ("idle".to_owned(), idle),
+                ("active".to_owned(), active),
+                ("talking".to_owned(), talking),
+                ("closing".to_owned(), closing)
+            ])
+        }
+    }
+}
+
+impl StateMachine {
+    pub fn state(&self, name: &str) -> Option<&MachineState> {
+        self.states.get(name)
+    }
+
+    /// where `trigger` takes the machine from `from`, `None` when it stays put
+    pub fn transition(&self, from: &str, trigger: Trigger) -> Option<&str> {
+        self.state(from)?.on.get(&trigger).map(String::as_str)
+    }
+}
+
+/// how `preprocess` upscales the sprite sheet
+#[derive(serde::Deserialize, serde::Serialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum UpscaleFilter{
+    /// repeat every pixel
+    #[default]
+    Nearest,
+    /// smooth diagonal edges the way pixel art upscalers (Scale2x/xBR) do, in steps of 2x
+    Scale2x
+}
+
+/// makes a sprite sheet pixels bigger, the display scale is divided by `factor` so the character keeps its size
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, PartialEq)]
+pub struct Upscale{
+    pub factor: u32,
+    #[serde(default)]
+    pub filter: UpscaleFilter
+}
+
+/// one colour of the palette swapped for another
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, PartialEq)]
+pub struct PaletteSwap{
+    pub from: [u8; 3],
+    /// rgba, the alpha is multiplied with the pixel's own
+    pub to: [u8; 4]
+}
+
+/// changes made to the sprite sheet when it's loaded, in this order
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, Default, PartialEq)]
+pub struct Preprocess{
+    /// pixels of this colour become transparent, legacy sheets use magenta ([255, 0, 255])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color_key: Option<[u8; 3]>,
+    /// how far off (per channel) a pixel can be and still count as the colour key
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub color_key_tolerance: u8,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub palette: Vec<PaletteSwap>,
+    /// degrees around the colour wheel
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub hue_shift: f32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upscale: Option<Upscale>
+}
+
+impl Preprocess {
+    /// whether the sheet is used as it is
+    pub fn is_empty(&self) -> bool {
+        *self == Preprocess::default()
+    }
+
+    /// how many times bigger the sheet gets
+    pub fn scale_factor(&self) -> u32 {
+        self.upscale.as_ref().map_or(1, |u| u.factor.max(1))
+    }
+}
+
+/// effects for the whole character, on top of the ones each animation has
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, PartialEq)]
+pub struct CharacterEffects{
+    /// added to every animation's effects
+    #[serde(default)]
+    pub all: AnimationEffects,
+    /// added while a question is out and the assistant hasn't answered
+    #[serde(default)]
+    pub waiting: AnimationEffects,
+    /// tint while the assistant is reporting an error, `null` for none
+    #[serde(default)]
+    pub error_tint: Option<[u8; 4]>
+}
+
+impl Default for CharacterEffects {
+    fn default() -> Self {
+        CharacterEffects {
+            all: AnimationEffects { shadow: true, ..Default::default() },
+            waiting: AnimationEffects { glow: true, ..Default::default() },
+            error_tint: Some([255, 110, 110, 255])
+        }
+    }
+}
+
+fn default_weight() -> f32 {
+    1.0
+}
+
+fn is_default_weight(weight: &f32) -> bool {
+    *weight == default_weight()
 }
 
 #[derive(serde::Deserialize, serde::Serialize, Clone, Debug)]
 pub struct AnimationInfo{
     pub name: String,
-    pub frames: Vec<AnimationFrame>
+    pub frames: Vec<AnimationFrame>,
+    /// how likely this animation is to be picked compared to the others, 1 by default
+    #[serde(default = "default_weight", skip_serializing_if = "is_default_weight")]
+    pub weight: f32,
+    /// minimum time (ms) before the animation is picked again
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub cooldown: usize,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub effects: AnimationEffects
 }
 
-#[derive(serde::Deserialize, serde::Serialize, Clone, Debug)]
+impl Default for AnimationInfo {
+    fn default() -> Self {
+        AnimationInfo {
+            name: String::new(),
+            frames: Vec::new(),
+            weight: default_weight(),
+            cooldown: 0,
+            effects: AnimationEffects::default()
+        }
+    }
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, Default)]
 pub struct AnimationSets{
     pub idle: Vec<AnimationInfo>,
     pub action: Vec<AnimationInfo>
 }
 
+/// the lists in `AnimationSets`
+#[derive(serde::Deserialize, serde::Serialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AnimationCategory{
+    Idle,
+    #[default]
+    Action
+}
+
+fn default_scale() -> f32 {
+    1.0
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum SpriteFilter{
+    /// crisp, blocky pixels - what you want for pixel art
+    #[default]
+    Nearest,
+    Linear
+}
+
 #[derive(serde::Deserialize, serde::Serialize, Clone, Debug)]
 pub struct SpriteSheetInfo{
     pub columns: usize,
-    pub rows: usize
+    pub rows: usize,
+    #[serde(default = "default_scale")]
+    pub scale: f32,
+    #[serde(default)]
+    pub filter: SpriteFilter,
+    /// draw the character facing the other way
+    #[serde(default)]
+    pub mirror: bool,
+    /// size of a frame in pixels, needed for atlases where it can't be worked out from the grid
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frame_size: Option<[usize; 2]>
 }
 
-#[derive(serde::Deserialize, serde::Serialize, Clone, Debug)]
+impl Default for SpriteSheetInfo {
+    fn default() -> Self {
+        SpriteSheetInfo {
+            columns: 1,
+            rows: 1,
+            scale: default_scale(),
+            filter: SpriteFilter::default(),
+            mirror: false,
+            frame_size: None
+        }
+    }
+}
+
+impl SpriteSheetInfo {
+    /// size of a frame for a sheet of `image_size` pixels
+    pub fn cell_size(&self, image_size: [usize; 2]) -> [usize; 2] {
+        match self.frame_size {
+            Some(size) => size,
+            None => [image_size[0] / self.columns.max(1), image_size[1] / self.rows.max(1)]
+        }
+    }
+}
+
+/// idle animations that take over after a stretch of inactivity
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, PartialEq)]
+pub struct BoredomLevel{
+    /// seconds without any input before this level starts
+    pub after_secs: u64,
+    /// animations played at this level, matched by part of their name in either category.
+    /// Empty means every idle animation no other level claims
+    #[serde(default)]
+    pub animations: Vec<String>
+}
+
+impl BoredomLevel {
+    pub fn matches(&self, name: &str) -> bool {
+        self.animations.iter().any(|pattern| name.contains(pattern.as_str()))
+    }
+}
+
+/// how the character behaves while nobody is using it
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, PartialEq)]
+pub struct IdleBehaviour{
+    /// played between idle animations, skipped when the character doesn't have it
+    #[serde(default)]
+    pub rest_pose: Option<String>,
+    /// ordered by `after_secs`, fidgeting first then looking around then dozing off
+    #[serde(default)]
+    pub levels: Vec<BoredomLevel>
+}
+
+impl Default for IdleBehaviour {
+    fn default() -> Self {
+        IdleBehaviour {
+            rest_pose: Some("RestPose".to_owned()),
+            levels: vec![
+                BoredomLevel { after_secs: 0, animations: Vec::new() },
+                BoredomLevel { after_secs: 45, animations: vec!["Look".to_owned()] },
+                BoredomLevel { after_secs: 180, animations: vec!["Snooze".to_owned(), "Sleep".to_owned()] }
+            ]
+        }
+    }
+}
+
+/// animations played when the app starts, quits, hides or shows, `null` to skip one
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, PartialEq)]
+pub struct LifecycleAnimations{
+    pub greeting: Option<String>,
+    pub goodbye: Option<String>,
+    pub hide: Option<String>,
+    pub show: Option<String>
+}
+
+impl Default for LifecycleAnimations {
+    fn default() -> Self {
+        LifecycleAnimations {
+            greeting: Some("Greeting".to_owned()),
+            goodbye: Some("GoodBye".to_owned()),
+            hide: Some("Hide".to_owned()),
+            show: Some("Show".to_owned())
+        }
+    }
+}
+
+impl LifecycleAnimations {
+    pub fn animation(&self, event: LifecycleEvent) -> Option<&str> {
+        match event {
+            LifecycleEvent::Started => self.greeting.as_deref(),
+            LifecycleEvent::QuitRequested => self.goodbye.as_deref(),
+            LifecycleEvent::Hidden => self.hide.as_deref(),
+            LifecycleEvent::Shown => self.show.as_deref()
+        }
+    }
+}
+
+/// animations played when the character is clicked or dragged, `null` to skip one
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, PartialEq)]
+pub struct InteractionAnimations{
+    pub clicked: Option<String>,
+    pub dragged: Option<String>
+}
+
+impl Default for InteractionAnimations {
+    fn default() -> Self {
+        InteractionAnimations {
+            clicked: Some("GetAttention".to_owned()),
+            dragged: Some("Alert".to_owned())
+        }
+    }
+}
+
+impl InteractionAnimations {
+    pub fn animation(&self, interaction: Interaction) -> Option<&str> {
+        match interaction {
+            Interaction::Clicked => self.clicked.as_deref(),
+            Interaction::Dragged => self.dragged.as_deref(),
+            Interaction::DoubleClicked => None
+        }
+    }
+}
+
+/// which way the window is moving across the screen
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MoveDirection {
+    Left,
+    Right,
+    Up,
+    Down
+}
+
+impl MoveDirection {
+    /// the direction of a move by `x`, `y` on screen, whichever axis moves most. `None` for no move
+    pub fn from_offset(x: f32, y: f32) -> Option<Self> {
+        if x == 0.0 && y == 0.0 {
+            return None;
+        }
+        Some(match x.abs() >= y.abs() {
+            true if x < 0.0 => MoveDirection::Left,
+            true => MoveDirection::Right,
+            false if y < 0.0 => MoveDirection::Up,
+            false => MoveDirection::Down
+        })
+    }
+
+    /// the same direction for a character drawn flipped
+    pub fn mirrored(self) -> Self {
+        match self {
+            MoveDirection::Left => MoveDirection::Right,
+            MoveDirection::Right => MoveDirection::Left,
+            other => other
+        }
+    }
+}
+
+/// walking (or flying) animations looped while the character moves, `null` to skip one
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, PartialEq)]
+pub struct MovementAnimations{
+    pub left: Option<String>,
+    pub right: Option<String>,
+    pub up: Option<String>,
+    pub down: Option<String>
+}
+
+impl Default for MovementAnimations {
+    fn default() -> Self {
+        // the names Microsoft Agent characters use
+        MovementAnimations {
+            left: Some("MoveLeft".to_owned()),
+            right: Some("MoveRight".to_owned()),
+            up: Some("MoveUp".to_owned()),
+            down: Some("MoveDown".to_owned())
+        }
+    }
+}
+
+impl MovementAnimations {
+    pub fn animation(&self, direction: MoveDirection) -> Option<&str> {
+        match direction {
+            MoveDirection::Left => self.left.as_deref(),
+            MoveDirection::Right => self.right.as_deref(),
+            MoveDirection::Up => self.up.as_deref(),
+            MoveDirection::Down => self.down.as_deref()
+        }
+    }
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, Default)]
 pub struct AnimationConfig{
     pub animations: AnimationSets,
-    pub sprite_sheet_info: SpriteSheetInfo
+    pub sprite_sheet_info: SpriteSheetInfo,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub idle_behaviour: IdleBehaviour,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub lifecycle: LifecycleAnimations,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub interactions: InteractionAnimations,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub movement: MovementAnimations,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub effects: CharacterEffects,
+    /// run over the sprite sheet when it's loaded, see `preprocess`
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub preprocess: Preprocess,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub state_machine: StateMachine
+}
+
+impl AnimationConfig {
+    /// look an animation up by name in either category
+    pub fn find_animation(&self, name: &str) -> Option<&AnimationInfo> {
+        self.animations.idle.iter()
+            .chain(self.animations.action.iter())
+            .find(|a| a.name == name)
+    }
 }
 
 #[derive(serde::Deserialize, serde::Serialize, Clone, Debug, Default, PartialEq)]
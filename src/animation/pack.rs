@@ -0,0 +1,256 @@
+use std::fs;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::path::PathBuf;
+
+use super::error::AnimationError;
+use super::import::clippyjs;
+use super::import::ImportedCharacter;
+use super::models::AnimationConfig;
+
+/// name of the metadata file at the root of every character pack
+pub const PACK_METADATA_FILE: &str = "pack.yaml";
+
+/// folder in a pack with a `<name>.wav` for each frame `sound`
+pub const PACK_SOUNDS_DIR: &str = "sounds";
+
+/// the app id eframe stores its data under, see `main.rs`
+const APP_ID: &str = "Clippit Gpt";
+
+fn default_sprite_sheet() -> String {
+    "sprite_sheet.png".to_owned()
+}
+
+fn default_animations() -> String {
+    "animations.yaml".to_owned()
+}
+
+/// contents of `pack.yaml`
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug)]
+pub struct PackMetadata {
+    pub name: String,
+    #[serde(default)]
+    pub author: String,
+    #[serde(default)]
+    pub description: String,
+    /// path of the sprite sheet, relative to the pack root
+    #[serde(default = "default_sprite_sheet")]
+    pub sprite_sheet: String,
+    /// path of the animation yaml, relative to the pack root
+    #[serde(default = "default_animations")]
+    pub animations: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PackSource {
+    /// compiled into the binary
+    BuiltIn,
+    Directory(PathBuf),
+    Zip(PathBuf),
+    /// an unconverted ClippyJS agent directory (`agent.js` + `map.png`)
+    ClippyJs(PathBuf),
+}
+
+/// an installed pack, only the metadata has been read
+#[derive(Clone, Debug)]
+pub struct CharacterPackInfo {
+    pub metadata: PackMetadata,
+    pub source: PackSource,
+}
+
+/// a fully loaded pack, ready to hand to an `AnimationService`
+#[derive(Clone, Debug)]
+pub struct CharacterPack {
+    pub info: CharacterPackInfo,
+    pub animation_data: String,
+    pub image_data: Vec<u8>,
+}
+
+/// where the user installs character packs, created on first use
+pub fn user_packs_dir() -> Option<PathBuf> {
+    let dir = eframe::storage_dir(APP_ID)?.join("characters");
+    fs::create_dir_all(&dir).ok()?;
+    Some(dir)
+}
+
+fn read_zip_file(
+    archive: &mut zip::ZipArchive<File>,
+    name: &str,
+) -> Result<Vec<u8>, AnimationError> {
+    // packs are often zipped up with their folder, so allow a single top level directory
+    let full_name = archive
+        .file_names()
+        .find(|n| *n == name || n.split_once('/').is_some_and(|(_, rest)| rest == name))
+        .map(|n| n.to_owned())
+        .ok_or_else(|| AnimationError::MissingFile(name.to_owned()))?;
+
+    let mut buff = Vec::new();
+    archive.by_name(&full_name)?.read_to_end(&mut buff)?;
+    Ok(buff)
+}
+
+impl CharacterPackInfo {
+    pub fn builtin() -> Self {
+        CharacterPackInfo {
+            metadata: PackMetadata {
+                name: "Clippit".to_owned(),
+                author: "Microsoft".to_owned(),
+                description: "Everyone's favourite paperclip".to_owned(),
+                sprite_sheet: "clippy.png".to_owned(),
+                animations: "animations.yaml".to_owned(),
+            },
+            source: PackSource::BuiltIn,
+        }
+    }
+
+    /// read the metadata of a pack directory, `.zip` or ClippyJS agent directory
+    pub fn from_path(path: &Path) -> Result<Self, AnimationError> {
+        if path.is_dir()
+            && !path.join(PACK_METADATA_FILE).exists()
+            && path.join("agent.js").exists()
+        {
+            let agent_js = fs::read_to_string(path.join("agent.js"))?;
+            return Ok(CharacterPackInfo {
+                metadata: PackMetadata {
+                    name: clippyjs::agent_name(&agent_js),
+                    author: String::new(),
+                    description: "ClippyJS agent".to_owned(),
+                    sprite_sheet: "map.png".to_owned(),
+                    animations: "agent.js".to_owned(),
+                },
+                source: PackSource::ClippyJs(path.to_path_buf()),
+            });
+        }
+
+        if path.is_dir() {
+            let metadata = fs::read_to_string(path.join(PACK_METADATA_FILE))?;
+            return Ok(CharacterPackInfo {
+                metadata: serde_yaml::from_str(&metadata)?,
+                source: PackSource::Directory(path.to_path_buf()),
+            });
+        }
+
+        let mut archive = zip::ZipArchive::new(File::open(path)?)?;
+        let metadata = read_zip_file(&mut archive, PACK_METADATA_FILE)?;
+        Ok(CharacterPackInfo {
+            metadata: serde_yaml::from_slice(&metadata)?,
+            source: PackSource::Zip(path.to_path_buf()),
+        })
+    }
+
+    fn read_file(&self, name: &str) -> Result<Vec<u8>, AnimationError> {
+        match &self.source {
+            PackSource::BuiltIn => match name {
+                "animations.yaml" => Ok(include_str!("../../assets/animations.yaml")
+                    .as_bytes()
+                    .to_vec()),
+                "clippy.png" => Ok(include_bytes!("../../assets/clippy.png").to_vec()),
+                _ => Err(AnimationError::MissingFile(name.to_owned())),
+            },
+            PackSource::Directory(dir) | PackSource::ClippyJs(dir) => {
+                let path = dir.join(name);
+                if !path.is_file() {
+                    return Err(AnimationError::MissingFile(name.to_owned()));
+                }
+                Ok(fs::read(path)?)
+            }
+            PackSource::Zip(path) => {
+                let mut archive = zip::ZipArchive::new(File::open(path)?)?;
+                read_zip_file(&mut archive, name)
+            }
+        }
+    }
+
+    /// read the sprite sheet and animations, checking the animations parse and the sheet decodes
+    pub fn load(&self) -> Result<CharacterPack, AnimationError> {
+        if let PackSource::ClippyJs(_) = self.source {
+            let agent_js = self.read_file(&self.metadata.animations)?;
+            let imported = clippyjs::import(
+                &String::from_utf8_lossy(&agent_js),
+                &self.read_file(&self.metadata.sprite_sheet)?,
+            )?;
+            return Ok(CharacterPack {
+                info: self.clone(),
+                animation_data: serde_yaml::to_string(&imported.config)?,
+                image_data: imported.image_data,
+            });
+        }
+
+        let animation_data = self.read_file(&self.metadata.animations)?;
+        let animation_data = String::from_utf8_lossy(&animation_data).into_owned();
+        let _: AnimationConfig = serde_yaml::from_str(&animation_data)?;
+
+        let image_data = self.read_file(&self.metadata.sprite_sheet)?;
+        // the built in sheet is known to be good and big, don't decode it twice
+        if self.source != PackSource::BuiltIn {
+            image::load_from_memory(&image_data)?;
+        }
+
+        Ok(CharacterPack {
+            info: self.clone(),
+            animation_data,
+            image_data,
+        })
+    }
+}
+
+/// the built in pack followed by everything installed in `dir`,
+/// packs that can't be read are logged and skipped
+pub fn list_packs(dir: Option<&Path>) -> Vec<CharacterPackInfo> {
+    let mut packs = vec![CharacterPackInfo::builtin()];
+
+    let entries = match dir.map(fs::read_dir) {
+        Some(Ok(entries)) => entries,
+        _ => return packs,
+    };
+
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.is_dir() || p.extension().is_some_and(|e| e.eq_ignore_ascii_case("zip")))
+        .collect();
+    paths.sort();
+
+    for path in paths {
+        match CharacterPackInfo::from_path(&path) {
+            Ok(info) => packs.push(info),
+            Err(e) => log::warn!("skipping character pack {:?}: {}", path, e),
+        }
+    }
+    packs
+}
+
+/// write a pack directory for an imported character, returns the pack's directory
+pub fn write_pack(
+    dir: &Path,
+    character: &ImportedCharacter,
+    author: &str,
+) -> Result<PathBuf, AnimationError> {
+    let metadata = PackMetadata {
+        name: character.name.to_owned(),
+        author: author.to_owned(),
+        description: String::new(),
+        sprite_sheet: default_sprite_sheet(),
+        animations: default_animations(),
+    };
+
+    fs::create_dir_all(dir)?;
+    fs::write(
+        dir.join(PACK_METADATA_FILE),
+        serde_yaml::to_string(&metadata)?,
+    )?;
+    fs::write(
+        dir.join(&metadata.animations),
+        serde_yaml::to_string(&character.config)?,
+    )?;
+    fs::write(dir.join(&metadata.sprite_sheet), &character.image_data)?;
+    if !character.sounds.is_empty() {
+        let sounds = dir.join(PACK_SOUNDS_DIR);
+        fs::create_dir_all(&sounds)?;
+        for (name, data) in &character.sounds {
+            fs::write(sounds.join(format!("{}.wav", name)), data)?;
+        }
+    }
+    Ok(dir.to_path_buf())
+}
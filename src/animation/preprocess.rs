@@ -0,0 +1,262 @@
+//! Changes made to a sprite sheet when it's loaded: colour key to alpha, palette swaps,
+//! a hue shift and pixel art upscaling. Themed variants of a character (a dark mode clippit)
+//! are packs that reuse a sheet with a different `preprocess`.
+//! The latest result for each sheet is kept in memory, switching back to a character doesn't
+//! redo the work.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::hash::Hasher;
+use std::sync::Mutex;
+use std::sync::OnceLock;
+
+use image::imageops;
+use image::imageops::FilterType;
+use image::Rgba;
+use image::RgbaImage;
+
+use super::models::AnimationConfig;
+use super::models::AtlasRegion;
+use super::models::Preprocess;
+use super::models::UpscaleFilter;
+
+/// largest `upscale.factor`, sheets grow with its square
+pub const MAX_UPSCALE: u32 = 8;
+
+/// most pixels an upscaled sheet can have, 256MB as rgba
+pub const MAX_UPSCALED_PIXELS: u64 = 1 << 26;
+
+/// pixels in a `width`x`height` sheet upscaled by `factor`
+pub fn upscaled_pixels(width: usize, height: usize, factor: u32) -> u64 {
+    width as u64 * height as u64 * factor as u64 * factor as u64
+}
+
+/// the latest processed sheet for each image, by a hash of the encoded image,
+/// with a hash of the settings it was made with
+fn cache() -> &'static Mutex<HashMap<u64, (u64, RgbaImage)>> {
+    static CACHE: OnceLock<Mutex<HashMap<u64, (u64, RgbaImage)>>> = OnceLock::new();
+    CACHE.get_or_init(Default::default)
+}
+
+fn hash(value: impl Hash) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// `apply` to the sheet decoded from `image_data`, reusing the last result when it was made with the
+/// same settings. Themes of one sheet replace each other, so the cache doesn't grow with every switch
+pub fn apply_cached(image_data: &[u8], sheet: &RgbaImage, preprocess: &Preprocess) -> RgbaImage {
+    let key = hash(image_data);
+    // f32 isn't Hash, the yaml is as good a key
+    let settings = hash(serde_yaml::to_string(preprocess).unwrap_or_default());
+    if let Some((_, image)) = cache()
+        .lock()
+        .unwrap()
+        .get(&key)
+        .filter(|(s, _)| *s == settings)
+    {
+        return image.clone();
+    }
+    let image = apply(sheet, preprocess);
+    cache()
+        .lock()
+        .unwrap()
+        .insert(key, (settings, image.clone()));
+    image
+}
+
+/// how many sheets `apply_cached` is holding on to
+pub fn cached_sheets() -> usize {
+    cache().lock().unwrap().len()
+}
+
+/// run the pipeline over a sprite sheet, the config has to be run through `scale_config` to match it
+pub fn apply(sheet: &RgbaImage, preprocess: &Preprocess) -> RgbaImage {
+    let mut image = sheet.clone();
+    if let Some(key) = preprocess.color_key {
+        color_key(&mut image, key, preprocess.color_key_tolerance);
+    }
+    if !preprocess.palette.is_empty() {
+        let mut swaps: HashMap<[u8; 3], [u8; 4]> = HashMap::new();
+        for swap in &preprocess.palette {
+            // the first swap for a colour wins
+            swaps.entry(swap.from).or_insert(swap.to);
+        }
+        for pixel in image.pixels_mut() {
+            let Rgba([r, g, b, a]) = *pixel;
+            if let Some(&[r, g, b, to_a]) = swaps.get(&[r, g, b]) {
+                *pixel = Rgba([r, g, b, (a as u16 * to_a as u16 / 255) as u8]);
+            }
+        }
+    }
+    if preprocess.hue_shift % 360.0 != 0.0 {
+        for pixel in image.pixels_mut() {
+            *pixel = shift_hue(*pixel, preprocess.hue_shift);
+        }
+    }
+    match &preprocess.upscale {
+        Some(upscale) if upscale.factor > 1 => {
+            upscale_image(&image, upscale.factor.min(MAX_UPSCALE), upscale.filter)
+        }
+        _ => image,
+    }
+}
+
+/// make pixels close to `key` transparent
+pub fn color_key(image: &mut RgbaImage, key: [u8; 3], tolerance: u8) {
+    for pixel in image.pixels_mut() {
+        let matches = pixel.0[..3]
+            .iter()
+            .zip(key)
+            .all(|(&c, k)| c.abs_diff(k) <= tolerance);
+        if matches {
+            // black so filtering doesn't bleed the key colour into the edges
+            *pixel = Rgba([0, 0, 0, 0]);
+        }
+    }
+}
+
+/// rotate a pixel's hue by `degrees`, keeping its saturation, brightness and alpha
+pub fn shift_hue(pixel: Rgba<u8>, degrees: f32) -> Rgba<u8> {
+    let Rgba([r, g, b, a]) = pixel;
+    if a == 0 {
+        return pixel;
+    }
+    let [r, g, b] = [r, g, b].map(|c| c as f32 / 255.0);
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let chroma = max - min;
+    if chroma == 0.0 {
+        // greys have no hue
+        return pixel;
+    }
+
+    let hue = if max == r {
+        60.0 * ((g - b) / chroma)
+    } else if max == g {
+        60.0 * ((b - r) / chroma + 2.0)
+    } else {
+        60.0 * ((r - g) / chroma + 4.0)
+    };
+    let hue = (hue + degrees).rem_euclid(360.0) / 60.0;
+    let x = chroma * (1.0 - (hue % 2.0 - 1.0).abs());
+    let (r, g, b) = match hue as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let to_u8 = |c: f32| ((c + min) * 255.0).round().clamp(0.0, 255.0) as u8;
+    Rgba([to_u8(r), to_u8(g), to_u8(b), a])
+}
+
+/// make the image `factor` times bigger, `Scale2x` doubles while it can and repeats pixels for what's left
+pub fn upscale_image(image: &RgbaImage, factor: u32, filter: UpscaleFilter) -> RgbaImage {
+    let mut image = image.clone();
+    let mut factor = factor.max(1);
+    if filter == UpscaleFilter::Scale2x {
+        while factor % 2 == 0 {
+            image = scale2x(&image);
+            factor /= 2;
+        }
+    }
+    if factor > 1 {
+        image = imageops::resize(
+            &image,
+            image.width() * factor,
+            image.height() * factor,
+            FilterType::Nearest,
+        );
+    }
+    image
+}
+
+/// the Scale2x (EPX) pixel art upscaler, each pixel becomes four that follow the edges around it
+pub fn scale2x(image: &RgbaImage) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    let mut out = RgbaImage::new(width * 2, height * 2);
+    let at = |x: i64, y: i64| {
+        let x = x.clamp(0, width as i64 - 1) as u32;
+        let y = y.clamp(0, height as i64 - 1) as u32;
+        *image.get_pixel(x, y)
+    };
+
+    for y in 0..height {
+        for x in 0..width {
+            let (xi, yi) = (x as i64, y as i64);
+            let e = at(xi, yi);
+            let b = at(xi, yi - 1);
+            let d = at(xi - 1, yi);
+            let f = at(xi + 1, yi);
+            let h = at(xi, yi + 1);
+
+            let (e0, e1, e2, e3) = if b != h && d != f {
+                (
+                    if d == b { d } else { e },
+                    if b == f { f } else { e },
+                    if d == h { d } else { e },
+                    if h == f { f } else { e },
+                )
+            } else {
+                (e, e, e, e)
+            };
+            out.put_pixel(x * 2, y * 2, e0);
+            out.put_pixel(x * 2 + 1, y * 2, e1);
+            out.put_pixel(x * 2, y * 2 + 1, e2);
+            out.put_pixel(x * 2 + 1, y * 2 + 1, e3);
+        }
+    }
+    out
+}
+
+fn scale_region(region: &mut AtlasRegion, factor: usize) {
+    region.x *= factor;
+    region.y *= factor;
+    region.width *= factor;
+    region.height *= factor;
+    region.offset_x *= factor;
+    region.offset_y *= factor;
+}
+
+/// move every pixel position in the config to match a sheet `apply` upscaled,
+/// the display scale shrinks by the same factor so the character stays the same size on screen
+pub fn scale_config(config: &mut AnimationConfig) {
+    let factor = config.preprocess.scale_factor().min(MAX_UPSCALE);
+    if factor == 1 {
+        return;
+    }
+    let sheet = &mut config.sprite_sheet_info;
+    sheet.scale /= factor as f32;
+    if let Some(size) = &mut sheet.frame_size {
+        *size = size.map(|s| s * factor as usize);
+    }
+
+    let factor_i = factor as i32;
+    let factor = factor as usize;
+    let animations = config
+        .animations
+        .idle
+        .iter_mut()
+        .chain(config.animations.action.iter_mut());
+    for frame in animations.flat_map(|a| a.frames.iter_mut()) {
+        let info = &mut frame.info;
+        info.offset_x *= factor_i;
+        info.offset_y *= factor_i;
+        if let Some(region) = &mut info.region {
+            scale_region(region, factor);
+        }
+        for layer in &mut info.layers {
+            layer.offset_x *= factor_i;
+            layer.offset_y *= factor_i;
+            if let Some(region) = &mut layer.region {
+                scale_region(region, factor);
+            }
+        }
+    }
+    // done, loading the config again must not scale it twice
+    config.preprocess.upscale = None;
+}
@@ -0,0 +1,831 @@
+//! Decides which frame to show next. Kept free of timers and global randomness so the
+//! sequencing can be driven step by step, the `AnimationService` loop just feeds it.
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+use rand::Rng;
+
+use super::models::AnimationConfig;
+use super::models::AnimationFrame;
+use super::models::AnimationInfo;
+use super::models::AnimationServiceMode;
+use super::models::MachineState;
+use super::models::MoveDirection;
+use super::models::StateMachine;
+use super::models::Trigger;
+use crate::actions::DispatchActions;
+use crate::actions::Interaction;
+use crate::actions::LifecycleEvent;
+
+/// where the animation loop gets the time from
+pub trait Clock: Send + Sync {
+    /// time since the clock was created
+    fn now(&self) -> Duration;
+    fn sleep(&self, duration: Duration) -> Pin<Box<dyn Future<Output = ()> + Send>>;
+}
+
+/// real time, sleeping with tokio
+pub struct TokioClock {
+    start: Instant,
+}
+
+impl Default for TokioClock {
+    fn default() -> Self {
+        TokioClock {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Clock for TokioClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    fn sleep(&self, duration: Duration) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        Box::pin(tokio::time::sleep(duration))
+    }
+}
+
+/// a clock that only moves when it's slept on or advanced, sleeping doesn't wait
+#[derive(Default)]
+pub struct ManualClock {
+    now: Mutex<Duration>,
+}
+
+impl ManualClock {
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap() += duration;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        *self.now.lock().unwrap()
+    }
+
+    fn sleep(&self, duration: Duration) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        self.advance(duration);
+        // still give other tasks a go, a loop sleeping on this clock would hog the runtime
+        Box::pin(tokio::task::yield_now())
+    }
+}
+
+/// a frame the scheduler picked to show
+#[derive(Clone, Debug)]
+pub struct FrameStep {
+    /// name of the animation when this frame starts a new one
+    pub started_animation: Option<String>,
+    pub frame_index: usize,
+    pub frame: AnimationFrame,
+    /// playback speed the frame is shown at, 1 is as authored
+    pub speed: f32,
+}
+
+impl FrameStep {
+    /// how long the frame stays on screen
+    pub fn duration(&self) -> Duration {
+        Duration::from_secs_f64(self.frame.duration as f64 / 1000.0 / self.speed as f64)
+    }
+}
+
+/// slowest and fastest playback speed
+pub const MIN_SPEED: f32 = 0.25;
+pub const MAX_SPEED: f32 = 4.0;
+
+/// how long a key pose is held with reduced motion on, in milliseconds
+const KEY_POSE_DURATION: usize = 1500;
+
+/// how animations are played, the user's choice and saved with the app's settings
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct PlaybackSettings {
+    /// multiplies every frame's speed, 2 plays twice as fast
+    pub speed: f32,
+    /// speeds for single animations by name, used instead of `speed`
+    pub animation_speeds: BTreeMap<String, f32>,
+    /// only the rest pose and a still key pose of everything else, and no moving effects
+    pub reduced_motion: bool,
+}
+
+impl Default for PlaybackSettings {
+    fn default() -> Self {
+        PlaybackSettings {
+            speed: 1.0,
+            animation_speeds: BTreeMap::new(),
+            reduced_motion: false,
+        }
+    }
+}
+
+impl PlaybackSettings {
+    /// the speed `animation` plays at, between `MIN_SPEED` and `MAX_SPEED`
+    pub fn speed_for(&self, animation: &str) -> f32 {
+        let speed = self
+            .animation_speeds
+            .get(animation)
+            .copied()
+            .unwrap_or(self.speed);
+        if speed.is_nan() {
+            return 1.0;
+        }
+        speed.clamp(MIN_SPEED, MAX_SPEED)
+    }
+}
+
+/// a still version of `animation`: the frame held longest, shown briefly.
+/// Animators hold the poses that matter, so that's the one that says the most
+fn key_pose(animation: &AnimationInfo) -> AnimationInfo {
+    let key = animation.frames.iter().filter(|f| f.duration > 0).fold(
+        None,
+        |key: Option<&AnimationFrame>, f| match key {
+            Some(key) if key.duration >= f.duration => Some(key),
+            _ => Some(f),
+        },
+    );
+    let frames = key
+        .map(|f| AnimationFrame {
+            duration: KEY_POSE_DURATION,
+            info: f.info.clone(),
+            sound: f.sound.clone(),
+            event: f.event.clone(),
+            ..Default::default()
+        })
+        .into_iter()
+        .collect();
+    AnimationInfo {
+        frames,
+        ..animation.clone()
+    }
+}
+
+/// least time between two look animations, so following the pointer doesn't thrash
+const LOOK_INTERVAL: Duration = Duration::from_secs(2);
+
+/// how long the character looks upset after the assistant reports an error
+const ERROR_SHOWN_FOR: Duration = Duration::from_secs(4);
+
+/// which way the character looks, on screen
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LookDirection {
+    Up,
+    Down,
+    Left,
+    Right,
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight,
+}
+
+impl LookDirection {
+    /// the direction of an offset from the character, +y is down. `None` for no offset
+    pub fn from_offset(x: f32, y: f32) -> Option<Self> {
+        if x == 0.0 && y == 0.0 {
+            return None;
+        }
+        // eight 45° slices, the first centred on the right
+        let slice = ((y.atan2(x).to_degrees() + 360.0 + 22.5) / 45.0) as usize % 8;
+        Some(
+            [
+                LookDirection::Right,
+                LookDirection::DownRight,
+                LookDirection::Down,
+                LookDirection::DownLeft,
+                LookDirection::Left,
+                LookDirection::UpLeft,
+                LookDirection::Up,
+                LookDirection::UpRight,
+            ][slice],
+        )
+    }
+
+    /// the same direction for a character drawn flipped
+    pub fn mirrored(self) -> Self {
+        match self {
+            LookDirection::Left => LookDirection::Right,
+            LookDirection::Right => LookDirection::Left,
+            LookDirection::UpLeft => LookDirection::UpRight,
+            LookDirection::UpRight => LookDirection::UpLeft,
+            LookDirection::DownLeft => LookDirection::DownRight,
+            LookDirection::DownRight => LookDirection::DownLeft,
+            other => other,
+        }
+    }
+
+    /// animations to play, best first, diagonals fall back to looking sideways then up or down
+    pub fn animation_names(self) -> &'static [&'static str] {
+        match self {
+            LookDirection::Up => &["LookUp"],
+            LookDirection::Down => &["LookDown"],
+            LookDirection::Left => &["LookLeft"],
+            LookDirection::Right => &["LookRight"],
+            LookDirection::UpLeft => &["LookUpLeft", "LookLeft", "LookUp"],
+            LookDirection::UpRight => &["LookUpRight", "LookRight", "LookUp"],
+            LookDirection::DownLeft => &["LookDownLeft", "LookLeft", "LookDown"],
+            LookDirection::DownRight => &["LookDownRight", "LookRight", "LookDown"],
+        }
+    }
+}
+
+/// follows the frame's branches, `roll` is a percentage in 0..100
+pub fn next_frame_index(frame: &AnimationFrame, frame_index: usize, roll: u32) -> usize {
+    let mut total = 0;
+    for branch in &frame.branching {
+        total += branch.weight;
+        if roll < total {
+            return branch.frame_index;
+        }
+    }
+    frame_index + 1
+}
+
+/// idle animations for how bored the character is, falling back to calmer levels
+/// when a level has nothing to play
+fn idle_candidates(config: &AnimationConfig, inactive: Duration) -> Vec<&AnimationInfo> {
+    let levels = &config.idle_behaviour.levels;
+    let all = || {
+        config
+            .animations
+            .idle
+            .iter()
+            .chain(config.animations.action.iter())
+    };
+    // imported characters don't always have both categories
+    let idle = if config.animations.idle.is_empty() {
+        &config.animations.action
+    } else {
+        &config.animations.idle
+    };
+    let current = levels
+        .iter()
+        .rposition(|l| Duration::from_secs(l.after_secs) <= inactive);
+
+    for level in levels[..current.map_or(0, |i| i + 1)].iter().rev() {
+        let candidates: Vec<&AnimationInfo> = if level.animations.is_empty() {
+            // the catch all level only gets the idle animations no other level wants
+            idle.iter()
+                .filter(|a| !levels.iter().any(|l| l.matches(&a.name)))
+                .collect()
+        } else {
+            all().filter(|a| level.matches(&a.name)).collect()
+        };
+        if !candidates.is_empty() {
+            return candidates;
+        }
+    }
+    idle.iter().collect()
+}
+
+/// weighted pick, leaving out the animation that just played and ones that are cooling down
+/// unless that leaves nothing
+fn pick_weighted<'a, R: Rng + ?Sized>(
+    candidates: Vec<&'a AnimationInfo>,
+    last: Option<&str>,
+    played_at: &HashMap<String, Duration>,
+    now: Duration,
+    rng: &mut R,
+) -> Option<&'a AnimationInfo> {
+    let is_fresh = |a: &&AnimationInfo| {
+        let cooling_down = played_at
+            .get(&a.name)
+            .is_some_and(|t| now.saturating_sub(*t) < Duration::from_millis(a.cooldown as u64));
+        Some(a.name.as_str()) != last && !cooling_down
+    };
+    let fresh: Vec<&AnimationInfo> = candidates.iter().copied().filter(is_fresh).collect();
+    let pool = if fresh.is_empty() { candidates } else { fresh };
+
+    let total: f32 = pool.iter().map(|a| a.weight.max(0.0)).sum();
+    // huge weights can add up to infinity, pick evenly then
+    if total <= 0.0 || !total.is_finite() {
+        return pool.get(rng.gen_range(0..pool.len().max(1))).copied();
+    }
+    let mut roll = rng.gen_range(0.0..total);
+    for animation in &pool {
+        roll -= animation.weight.max(0.0);
+        if roll < 0.0 {
+            return Some(animation);
+        }
+    }
+    pool.last().copied()
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct AnimationState {
+    current_animation: Option<AnimationInfo>,
+    current_frame_index: usize,
+    current_frame_info: Option<AnimationFrame>,
+    mode: AnimationServiceMode,
+    /// winding the current animation down through its exit branches
+    exiting: bool,
+    /// frames shown since exiting started, a cycle of exit branches is cut off after one lap
+    exit_steps: usize,
+    /// clock time of the last user input, the character gets bored from there
+    last_input: Duration,
+    /// clock time each animation last started, for cooldowns
+    played_at: HashMap<String, Duration>,
+    /// last animation other than the rest pose, so it isn't picked twice in a row
+    last_animation: Option<String>,
+    /// the rest pose is the current animation
+    resting: bool,
+    /// the current animation is from a boredom level past fidgeting
+    bored: bool,
+    /// lifecycle animation to start as soon as the current one is out of the way
+    pending_lifecycle: Option<LifecycleEvent>,
+    /// the current animation is for this lifecycle event, it can't be interrupted
+    lifecycle: Option<LifecycleEvent>,
+    /// lifecycle animations that played to the end since the last `take_finished_lifecycle`
+    finished_lifecycle: Vec<LifecycleEvent>,
+    /// stay on the last frame, the character has said goodbye or is hidden
+    holding: bool,
+    /// the user clicked or dragged the character, react once the current animation is out of the way
+    pending_interaction: Option<Interaction>,
+    /// animation asked for by name with `play`, starts once the current one is out of the way
+    pending_play: Option<String>,
+    /// look this way when the current animation is out of the way
+    pending_look: Option<LookDirection>,
+    /// the window is moving this way, the walk animation loops until it stops
+    moving: Option<MoveDirection>,
+    /// the current animation looks this way
+    looking: Option<LookDirection>,
+    /// clock time the last look animation started
+    looked_at: Option<Duration>,
+    /// clock time the assistant last reported an error, cleared by an answer
+    error_at: Option<Duration>,
+    /// a question is out, cleared by the answer or an error
+    waiting: bool,
+    playback: PlaybackSettings,
+    machine: StateMachine,
+    /// state of `machine` the character is in, the initial one until something happens
+    machine_state: Option<String>,
+    /// clock time the current machine state was entered
+    entered_at: Duration,
+}
+
+impl AnimationState {
+    pub fn mode(&self) -> &AnimationServiceMode {
+        &self.mode
+    }
+
+    pub fn current_animation(&self) -> Option<&AnimationInfo> {
+        self.current_animation.as_ref()
+    }
+
+    /// the frame on screen
+    pub fn current_frame(&self) -> Option<&AnimationFrame> {
+        self.current_frame_info.as_ref()
+    }
+
+    /// index of the frame `next_frame` will try to show
+    pub fn next_frame_index(&self) -> usize {
+        self.current_frame_index
+    }
+
+    pub fn is_exiting(&self) -> bool {
+        self.exiting
+    }
+
+    /// playing something because nobody has been around for a while
+    pub fn is_bored(&self) -> bool {
+        self.bored
+    }
+
+    /// waiting on the assistant to answer
+    pub fn is_waiting(&self) -> bool {
+        self.waiting
+    }
+
+    /// the assistant had trouble recently
+    pub fn showing_error(&self, now: Duration) -> bool {
+        self.error_at
+            .is_some_and(|t| now.saturating_sub(t) < ERROR_SHOWN_FOR)
+    }
+
+    /// the states to move between, starts over in its initial state
+    pub fn set_state_machine(&mut self, machine: StateMachine) {
+        self.mode = machine
+            .state(&machine.initial)
+            .map(MachineState::mode)
+            .unwrap_or_default();
+        self.machine = machine;
+        self.machine_state = None;
+    }
+
+    /// name of the state machine's current state
+    pub fn machine_state(&self) -> &str {
+        self.machine_state
+            .as_deref()
+            .unwrap_or(&self.machine.initial)
+    }
+
+    fn current_machine_state(&self) -> Option<&MachineState> {
+        self.machine.state(self.machine_state())
+    }
+
+    /// move to the state `name` at clock time `now`, the current animation winds down
+    fn enter(&mut self, name: String, now: Duration) {
+        if let Some(state) = self.machine.state(&name) {
+            self.mode = state.mode();
+        }
+        self.machine_state = Some(name);
+        self.entered_at = now;
+        self.interrupt();
+    }
+
+    /// follow the transition for `trigger` if the current state has one, returns true when it did
+    pub fn fire(&mut self, trigger: Trigger, now: Duration) -> bool {
+        let Some(to) = self.machine.transition(self.machine_state(), trigger) else {
+            return false;
+        };
+        self.enter(to.to_owned(), now);
+        true
+    }
+
+    /// the state's `timeout` trigger, when nothing happened for long enough
+    fn timed_out(&self, now: Duration) -> bool {
+        let Some(secs) = self.current_machine_state().and_then(|s| s.timeout_secs) else {
+            return false;
+        };
+        let since = self.last_input.max(self.entered_at);
+        now.saturating_sub(since) >= Duration::from_secs(secs)
+    }
+
+    pub fn playback(&self) -> &PlaybackSettings {
+        &self.playback
+    }
+
+    /// speed and reduced motion, from the next frame on
+    pub fn set_playback(&mut self, playback: PlaybackSettings) {
+        // turning reduced motion on stops whatever is moving
+        let reduce = playback.reduced_motion && !self.playback.reduced_motion;
+        self.playback = playback;
+        if reduce {
+            self.interrupt();
+        }
+    }
+
+    /// how long nobody has touched anything
+    pub fn inactive_for(&self, now: Duration) -> Duration {
+        now.saturating_sub(self.last_input)
+    }
+
+    /// switch mode, the current animation winds down through its exit branch if it has one,
+    /// otherwise it's cut short. The machine moves to a state playing that mode if it has one,
+    /// the initial state first
+    pub fn set_mode(&mut self, mode: AnimationServiceMode) {
+        let initial = self.machine.initial.to_owned();
+        let state = std::iter::once(&initial)
+            .chain(self.machine.states.keys())
+            .find(|name| self.machine.state(name).is_some_and(|s| s.mode() == mode))
+            .cloned();
+        self.mode = mode;
+        match state {
+            Some(state) => {
+                self.machine_state = Some(state);
+                self.interrupt();
+            }
+            None => self.interrupt(),
+        }
+    }
+
+    /// lifecycle events whose animation has finished, oldest first
+    pub fn take_finished_lifecycle(&mut self) -> Vec<LifecycleEvent> {
+        std::mem::take(&mut self.finished_lifecycle)
+    }
+
+    fn finish_lifecycle(&mut self, event: LifecycleEvent) {
+        self.finished_lifecycle.push(event);
+        self.holding = matches!(
+            event,
+            LifecycleEvent::QuitRequested | LifecycleEvent::Hidden
+        );
+    }
+
+    fn interrupt(&mut self) {
+        if self.lifecycle.is_some() {
+            return;
+        }
+        let exit_branch = self.current_frame_info.as_ref().and_then(|f| f.exit_branch);
+        match exit_branch {
+            Some(exit_branch) if self.current_animation.is_some() => {
+                self.exiting = true;
+                self.exit_steps = 0;
+                self.current_frame_index = exit_branch;
+            }
+            _ => self.current_animation = None,
+        }
+    }
+
+    /// walk `direction` until it's set back to `None`, the walk animation takes over straight away
+    pub fn set_moving(&mut self, direction: Option<MoveDirection>) {
+        if self.moving == direction {
+            return;
+        }
+        self.moving = direction;
+        self.bored = false;
+        self.interrupt();
+    }
+
+    pub fn moving(&self) -> Option<MoveDirection> {
+        self.moving
+    }
+
+    /// play the animation `name` once, then carry on as before. The current animation winds down first
+    pub fn play(&mut self, name: &str) {
+        self.pending_play = Some(name.to_owned());
+        self.bored = false;
+        self.interrupt();
+    }
+
+    /// pay attention to something in `direction` at clock time `now`, e.g. the pointer.
+    /// Only while idle, and at most every `LOOK_INTERVAL`, a look asked for sooner plays
+    /// once the current animation is done. Returns true when the current animation was interrupted
+    pub fn look_toward(&mut self, direction: LookDirection, now: Duration) -> bool {
+        self.last_input = now;
+        if self.mode != AnimationServiceMode::Idle || self.lifecycle.is_some() || self.holding {
+            return false;
+        }
+        if self.looking == Some(direction) {
+            self.pending_look = None;
+            return false;
+        }
+        self.pending_look = Some(direction);
+        if self
+            .looked_at
+            .is_some_and(|t| now.saturating_sub(t) < LOOK_INTERVAL)
+        {
+            return false;
+        }
+        self.bored = false;
+        self.interrupt();
+        true
+    }
+
+    /// react to an app event at clock time `now`, returns true when the current animation
+    /// was interrupted and the next frame should be picked straight away
+    pub fn handle_action(&mut self, action: &DispatchActions, now: Duration) -> bool {
+        let trigger = match action {
+            DispatchActions::AskQuestion(..) => {
+                self.waiting = true;
+                Trigger::QuestionAsked
+            }
+            DispatchActions::RespondToQuestion(..) => {
+                self.error_at = None;
+                self.waiting = false;
+                Trigger::Answer
+            }
+            DispatchActions::AssistantError(..) => {
+                self.error_at = Some(now);
+                self.waiting = false;
+                return self.fire(Trigger::Error, now);
+            }
+            DispatchActions::AnswerRevealed(_) => Trigger::Revealed,
+            DispatchActions::QuestionTextChanged(_) => {
+                // someone's there, wake up from anything past fidgeting
+                self.last_input = now;
+                if self.bored && self.current_animation.is_some() {
+                    self.bored = false;
+                    self.interrupt();
+                    return true;
+                }
+                return false;
+            }
+            DispatchActions::Interaction(_, interaction) => {
+                self.last_input = now;
+                let trigger = match interaction {
+                    Interaction::Clicked => Trigger::Click,
+                    Interaction::DoubleClicked => Trigger::DoubleClick,
+                    Interaction::Dragged => Trigger::Drag,
+                };
+                let moved = self.fire(trigger, now);
+                // a double click is for the app, it opens the chat input
+                if self.lifecycle.is_some()
+                    || self.holding
+                    || *interaction == Interaction::DoubleClicked
+                {
+                    return moved;
+                }
+                self.pending_interaction = Some(*interaction);
+                self.bored = false;
+                if !moved {
+                    self.interrupt();
+                }
+                return true;
+            }
+            DispatchActions::Lifecycle(_, event) => {
+                self.last_input = now;
+                self.holding = false;
+                self.pending_lifecycle = Some(*event);
+                self.interrupt();
+                return true;
+            }
+            _ => return false,
+        };
+        self.last_input = now;
+        self.fire(trigger, now)
+    }
+
+    /// the animation to start next at clock time `now`
+    fn pick_animation<R: Rng + ?Sized>(
+        &mut self,
+        config: &AnimationConfig,
+        now: Duration,
+        rng: &mut R,
+    ) -> Option<AnimationInfo> {
+        if let Some(event) = self.pending_lifecycle.take() {
+            let animation = config
+                .lifecycle
+                .animation(event)
+                .and_then(|name| config.find_animation(name));
+            match animation {
+                Some(animation) => {
+                    self.lifecycle = Some(event);
+                    return Some(animation.clone());
+                }
+                // the character doesn't have one, carry on as if it played
+                None => self.finish_lifecycle(event),
+            }
+        }
+        if self.holding {
+            return None;
+        }
+        if self.timed_out(now) {
+            self.fire(Trigger::Timeout, now);
+        }
+
+        let walk = self
+            .moving
+            .and_then(|d| config.movement.animation(d))
+            .and_then(|name| config.find_animation(name));
+        if let Some(animation) = walk {
+            self.resting = false;
+            return Some(animation.clone());
+        }
+
+        if let Some(interaction) = self.pending_interaction.take() {
+            let animation = config
+                .interactions
+                .animation(interaction)
+                .and_then(|name| config.find_animation(name));
+            if let Some(animation) = animation {
+                self.resting = false;
+                return Some(animation.clone());
+            }
+        }
+
+        if let Some(animation) = self
+            .pending_play
+            .take()
+            .and_then(|name| config.find_animation(&name))
+        {
+            self.resting = false;
+            return Some(animation.clone());
+        }
+
+        self.looking = None;
+        if let Some(direction) = self
+            .pending_look
+            .take()
+            .filter(|_| self.mode == AnimationServiceMode::Idle)
+        {
+            let animation = direction
+                .animation_names()
+                .iter()
+                .find_map(|name| config.find_animation(name));
+            if let Some(animation) = animation {
+                self.looking = Some(direction);
+                self.looked_at = Some(now);
+                self.resting = false;
+                return Some(animation.clone());
+            }
+        }
+
+        let last = self.last_animation.as_deref();
+        let was_resting = std::mem::take(&mut self.resting);
+        self.bored = false;
+
+        // the state plays its own list, the character might not have any of it
+        let listed: Vec<&AnimationInfo> = self
+            .current_machine_state()
+            .map(|s| {
+                s.animations
+                    .iter()
+                    .filter_map(|name| config.find_animation(name))
+                    .collect()
+            })
+            .unwrap_or_default();
+        if !listed.is_empty() {
+            return pick_weighted(listed, last, &self.played_at, now, rng).cloned();
+        }
+
+        if self.mode == AnimationServiceMode::Idle {
+            let rest_pose = config.idle_behaviour.rest_pose.as_deref();
+            if self.playback.reduced_motion {
+                if let Some(animation) = rest_pose.and_then(|name| config.find_animation(name)) {
+                    self.resting = true;
+                    // stay on the rest pose until something happens
+                    return (!was_resting).then(|| animation.clone());
+                }
+            }
+            // settle back into the rest pose between idles
+            if let Some(rest_pose) = rest_pose.filter(|_| !was_resting && last.is_some()) {
+                if let Some(animation) = config.find_animation(rest_pose) {
+                    self.resting = true;
+                    return Some(animation.clone());
+                }
+            }
+
+            let inactive = self.inactive_for(now);
+            let candidates: Vec<&AnimationInfo> = idle_candidates(config, inactive)
+                .into_iter()
+                .filter(|a| Some(a.name.as_str()) != rest_pose)
+                .collect();
+            let animation = pick_weighted(candidates, last, &self.played_at, now, rng)?;
+            // anything the catch all level wouldn't play means the character got bored
+            let levels = &config.idle_behaviour.levels;
+            self.bored = levels.iter().any(|l| {
+                !l.animations.is_empty() && l.after_secs > 0 && l.matches(&animation.name)
+            });
+            return Some(animation.clone());
+        }
+
+        // imported characters don't always have both categories
+        let animations = if config.animations.action.is_empty() {
+            &config.animations.idle
+        } else {
+            &config.animations.action
+        };
+        pick_weighted(animations.iter().collect(), last, &self.played_at, now, rng).cloned()
+    }
+
+    /// move on to the next frame at clock time `now`, starting a new animation when the
+    /// current one is done. `None` when there is nothing to play
+    pub fn next_frame<R: Rng + ?Sized>(
+        &mut self,
+        config: &AnimationConfig,
+        now: Duration,
+        rng: &mut R,
+    ) -> Option<FrameStep> {
+        let mut started_animation = None;
+
+        // the second go is with a freshly picked animation, if that has no frames give up
+        for _ in 0..2 {
+            if self.current_animation.is_none() {
+                let mut animation = self.pick_animation(config, now, rng)?;
+                if self.playback.reduced_motion {
+                    animation = key_pose(&animation);
+                }
+                started_animation = Some(animation.name.to_owned());
+                self.played_at.insert(animation.name.to_owned(), now);
+                if !self.resting {
+                    self.last_animation = Some(animation.name.to_owned());
+                }
+                self.current_animation = Some(animation);
+                self.current_frame_index = 0;
+                self.exiting = false;
+            }
+            let animation = self.current_animation.as_ref()?;
+
+            // finished, or branched somewhere that doesn't exist
+            if self.current_frame_index >= animation.frames.len() {
+                self.current_animation = None;
+                match self.lifecycle.take() {
+                    Some(event) => self.finish_lifecycle(event),
+                    None => {
+                        self.fire(Trigger::Done, now);
+                    }
+                }
+                continue;
+            }
+
+            let frame_index = self.current_frame_index;
+            let frame = animation.frames[frame_index].clone();
+            self.current_frame_index = if self.exiting {
+                self.exit_steps += 1;
+                if self.exit_steps >= animation.frames.len() {
+                    animation.frames.len()
+                } else {
+                    frame.exit_branch.unwrap_or(frame_index + 1)
+                }
+            } else {
+                next_frame_index(&frame, frame_index, rng.gen_range(0..100))
+            };
+            self.current_frame_info = Some(frame.clone());
+
+            let speed = self.playback.speed_for(&animation.name);
+            return Some(FrameStep {
+                started_animation,
+                frame_index,
+                frame,
+                speed,
+            });
+        }
+        None
+    }
+}
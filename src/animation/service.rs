@@ -1,58 +1,189 @@
 use std::io::Cursor;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::sync::Mutex;
 
-use std::time::Duration;
 
-use rand::Rng;
+use rand::RngCore;
 
+use super::atlas;
+use super::audio::AudioSettings;
+use super::audio::AudioSink;
+use super::audio::NullSink;
+use super::error::AnimationError;
 use super::models::AnimationConfig;
-use super::models::AnimationFrame;
-use super::models::AnimationInfo;
-use super::models::AnimationServiceMode;
+use super::models::FrameInfo;
+use super::models::MoveDirection;
+use super::preprocess;
+use super::scheduler::AnimationState;
+use super::scheduler::Clock;
+use super::scheduler::PlaybackSettings;
+use super::scheduler::TokioClock;
+use super::sprite_sheet::SpriteSheet;
+use super::validate::parse_and_validate;
+use super::widget::SpriteAnimation;
+use super::widget::NOTHING_TO_PLAY_DELAY;
+use crate::actions::AgentId;
 use crate::actions::DispatchActions;
 
 use egui::ColorImage;
 use egui::Context;
-use egui::TextureHandle;
+use egui::Pos2;
+use egui::Rect;
+use egui::Response;
 use egui::Ui;
+use egui::Vec2;
+use image::codecs::gif::GifEncoder;
+use image::codecs::gif::Repeat;
+use image::Delay;
+use image::Frame;
+use image::RgbaImage;
+use tokio::sync::broadcast::error::TryRecvError;
 use tokio::sync::broadcast::Sender;
 
-use egui::scroll_area::ScrollAreaOutput;
 
+/// the agent a service plays for unless it's given another one
+pub const DEFAULT_AGENT: &str = "clippit";
 
-#[derive(Clone)]
-struct AnimationState {
-    current_animation: Option<AnimationInfo>,
-    current_frame_index: usize,
-    current_frame_info: Option<AnimationFrame>,
-    mode: AnimationServiceMode
-    
-}
-
+/// Loads a character pack into a `SpriteAnimation` and plays it for an agent on the app's
+/// event bus: actions go to the scheduler, frames are timed on a tokio task and what they
+/// do (animations starting, sounds, events) goes back out on the bus.
 #[derive(Clone)]
 pub struct AnimationService {
-    animation_config: AnimationConfig,
+    agent: AgentId,
     sndr: Sender<DispatchActions>,
-    state: Arc<Mutex<AnimationState>>,
-    sprite_height: usize,
-    sprite_width: usize,
-    texture_handle: TextureHandle,
-    should_run: bool
+    animation: Arc<Mutex<SpriteAnimation>>,
+    should_run: Arc<AtomicBool>,
+    clock: Arc<dyn Clock>,
+    audio: Arc<dyn AudioSink>,
+    audio_settings: Arc<Mutex<AudioSettings>>,
+    /// false for agents without an assistant, nobody would answer so questions are ignored
+    answers_questions: Arc<AtomicBool>
 }
 
-fn load_image_as_color_image(bytes: &Vec<u8> ) -> ColorImage {
+fn load_image(bytes: &[u8]) -> Result<RgbaImage, AnimationError> {
     let mut c = Cursor::new(bytes);
     c.set_position(0);
 
     let im_buff = image::io::Reader::new(
             &mut c
-        ).with_guessed_format()
-        .expect("can't guess format")
-        .decode()
-        .expect("can't decode image");
+        ).with_guessed_format()?
+        .decode()?;
+
+    Ok(im_buff.to_rgba8())
+}
+
+pub fn load_image_as_color_image(bytes: &[u8]) -> Result<ColorImage, AnimationError> {
+    Ok(to_color_image(&load_image(bytes)?))
+}
+
+/// what `export_animation` writes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExportFormat {
+    Gif,
+    Apng
+}
+
+/// draw a frame the way `SpriteSheet::paint_frame` does, into an image the size of one cell
+pub fn render_frame_image(sheet: &RgbaImage, config: &AnimationConfig, info: &FrameInfo) -> Result<RgbaImage, AnimationError> {
+    let [width, height] = config.sprite_sheet_info.cell_size([sheet.width() as usize, sheet.height() as usize]);
+    let mut image = RgbaImage::new(width as u32, height as u32);
 
-    let im_buff = im_buff.to_rgba8();
+    for layer in info.images() {
+        if layer.page != 0 {
+            return Err(AnimationError::Format("exporting from split sprite sheets isn't supported".to_owned()));
+        }
+        let region = layer.source_region([width, height]);
+        if region.x + region.width > sheet.width() as usize || region.y + region.height > sheet.height() as usize {
+            return Err(AnimationError::Format(format!(
+                "frame at {},{} is outside the {}x{} sprite sheet",
+                region.x, region.y, sheet.width(), sheet.height()
+            )));
+        }
+
+        let pixels = image::imageops::crop_imm(sheet, region.x as u32, region.y as u32, region.width as u32, region.height as u32);
+        image::imageops::overlay(
+            &mut image,
+            &*pixels,
+            region.offset_x as i64 + layer.offset_x as i64,
+            region.offset_y as i64 + layer.offset_y as i64
+        );
+    }
+    if config.sprite_sheet_info.mirror {
+        image::imageops::flip_horizontal_in_place(&mut image);
+    }
+    Ok(image)
+}
+
+/// the visible frames of an animation in order with how long they're shown (ms),
+/// branches aren't followed and zero length frames are left out
+pub fn render_animation_frames(sheet: &RgbaImage, config: &AnimationConfig, name: &str) -> Result<Vec<(RgbaImage, usize)>, AnimationError> {
+    let animation = config
+        .find_animation(name)
+        .ok_or_else(|| AnimationError::Format(format!("there is no animation called {}", name)))?;
+
+    animation.frames
+        .iter()
+        .filter(|f| f.duration > 0)
+        .map(|f| Ok((render_frame_image(sheet, config, &f.info)?, f.duration)))
+        .collect()
+}
+
+/// encode an animation as a looping gif or apng, works without a window or gpu
+pub fn export_animation(sheet: &RgbaImage, config: &AnimationConfig, name: &str, format: ExportFormat) -> Result<Vec<u8>, AnimationError> {
+    let frames = render_animation_frames(sheet, config, name)?;
+    let Some((first, _)) = frames.first() else {
+        return Err(AnimationError::Format(format!("{} has no visible frames", name)));
+    };
+    let (width, height) = first.dimensions();
+    let mut buff = Vec::new();
+
+    match format {
+        ExportFormat::Gif => {
+            let mut encoder = GifEncoder::new(&mut buff);
+            encoder.set_repeat(Repeat::Infinite)?;
+            encoder.encode_frames(frames.into_iter().map(|(image, duration)| {
+                Frame::from_parts(image, 0, 0, Delay::from_numer_denom_ms(duration as u32, 1))
+            }))?;
+        },
+        ExportFormat::Apng => {
+            let png_error = |e: png::EncodingError| AnimationError::Format(format!("png encoding failed: {}", e));
+            let mut encoder = png::Encoder::new(&mut buff, width, height);
+            encoder.set_color(png::ColorType::Rgba);
+            encoder.set_depth(png::BitDepth::Eight);
+            encoder.set_animated(frames.len() as u32, 0).map_err(png_error)?;
+            let mut writer = encoder.write_header().map_err(png_error)?;
+            for (image, duration) in &frames {
+                // the delay is a u16 fraction, 65 seconds is plenty for one frame
+                writer.set_frame_delay((*duration).min(u16::MAX as usize) as u16, 1000).map_err(png_error)?;
+                writer.write_image_data(image.as_raw()).map_err(png_error)?;
+            }
+            writer.finish().map_err(png_error)?;
+        }
+    }
+    Ok(buff)
+}
+
+/// the sheet as textures no bigger than `max_texture_side`, repacked into pages when it's over
+/// the limit. The config comes back pointing into the pages
+pub fn split_into_pages(config: AnimationConfig, sheet: RgbaImage, max_texture_side: usize) -> Result<(AnimationConfig, Vec<ColorImage>), AnimationError> {
+    let (width, height) = (sheet.width() as usize, sheet.height() as usize);
+    if width <= max_texture_side && height <= max_texture_side {
+        return Ok((config, vec![to_color_image(&sheet)]));
+    }
+    let packed = atlas::pack(&config, &[sheet], [max_texture_side as u32; 2])?;
+    log::info!(
+        "{}x{} sprite sheet is over the {}px texture limit, split into {} pages",
+        width,
+        height,
+        max_texture_side,
+        packed.pages.len()
+    );
+    Ok((packed.config, packed.pages.iter().map(to_color_image).collect()))
+}
+
+fn to_color_image(im_buff: &RgbaImage) -> ColorImage {
     let pix = im_buff.as_flat_samples();
     ColorImage::from_rgba_unmultiplied(
         [im_buff.width() as _, 
@@ -63,160 +194,204 @@ fn load_image_as_color_image(bytes: &Vec<u8> ) -> ColorImage {
 
 impl AnimationService {
     /// Called once before the first frame.
+    /// The config is validated against the sprite sheet, warnings are logged and errors returned.
+    /// `max_texture_side` is the renderer's limit, egui only knows it once the first frame has started
     pub fn new(
         ctx: Context,
         config_data :  String,
         image_data: Vec<u8>,
-        sndr : Sender<DispatchActions>
-    ) -> Self {
-        let config: AnimationConfig = serde_yaml::from_str(&config_data)
-            .expect("trouble reading config file");
+        sndr : Sender<DispatchActions>,
+        max_texture_side: usize
+    ) -> Result<Self, AnimationError> {
+        let sheet = load_image(&image_data)?;
+        let image = to_color_image(&sheet);
 
-        let texture = ctx.load_texture(
+        let (config, diagnostics) = parse_and_validate(&config_data, Some(&image));
+        let mut config = match config {
+            Some(config) => config,
+            None => return Err(AnimationError::Invalid(diagnostics))
+        };
+        for diagnostic in diagnostics {
+            log::warn!("{}", diagnostic);
+        }
+
+        // validation is against the sheet as drawn, the pipeline runs after it
+        let sheet = if config.preprocess.is_empty() {
+            sheet
+        } else {
+            let sheet = preprocess::apply_cached(&image_data, &sheet, &config.preprocess);
+            preprocess::scale_config(&mut config);
+            sheet
+        };
+        let (config, pages) = split_into_pages(config, sheet, max_texture_side)?;
+
+        let sprite_sheet = SpriteSheet::new(
+            &ctx,
             "clippit_sprite_sheet", 
-            load_image_as_color_image(&image_data), 
-            Default::default()
+            pages, 
+            &config.sprite_sheet_info
         );
 
-        let im_size = texture.size_vec2();
-        let width = (im_size.x as usize) / config.sprite_sheet_info.columns;
-        let height = (im_size.y as usize) / config.sprite_sheet_info.rows;
-
-        AnimationService {
-            animation_config : config,
-            sndr: sndr,
-            sprite_width: width,
-            sprite_height: height,
-            texture_handle: texture,
-            should_run: false,
-            state: Arc::from(
-                Mutex::from(
-                    AnimationState{
-                        current_animation: None,
-                        mode: AnimationServiceMode::Idle,
-                        current_frame_index: 0,
-                        current_frame_info: None,
-                    }
-                )
-            )
-        }
+        Ok(AnimationService {
+            agent: DEFAULT_AGENT.to_owned(),
+            sndr,
+            animation: Arc::new(Mutex::new(SpriteAnimation::new(config, sprite_sheet))),
+            should_run: Arc::new(AtomicBool::new(false)),
+            clock: Arc::new(TokioClock::default()),
+            audio: Arc::new(NullSink),
+            audio_settings: Arc::new(Mutex::new(AudioSettings::default())),
+            answers_questions: Arc::new(AtomicBool::new(true))
+        })
     }
 
     pub fn start(&mut self){
 
-        if self.should_run == true {return}
-        self.should_run = true;
+        if self.should_run.swap(true, Ordering::SeqCst) {return}
         
-        let state = self.state.clone();
-        let config = self.animation_config.clone();
+        let should_run = self.should_run.clone();
+        let animation = self.animation.clone();
+        let agent = self.agent.clone();
         let sndr = self.sndr.clone();
+        let clock = self.clock.clone();
+        let audio = self.audio.clone();
+        let audio_settings = self.audio_settings.clone();
+        let answers_questions = self.answers_questions.clone();
 
-        tokio::spawn(async move {
-            let mut receiver = sndr.subscribe();
-
-            loop{
-                let duration;
-                let mode = match receiver.try_recv() {
-                    Ok(DispatchActions::AskQuestion(_question)) => {
-                        Some(AnimationServiceMode::Active)
-                    },
-                    Ok(DispatchActions::RespondToQuestion(_answer)) => {
-                        Some(AnimationServiceMode::Idle)
-                    },
-                    _ => None
-                };
+        // subscribe before returning so events sent right after `start` aren't missed
+        let mut receiver = sndr.subscribe();
 
-                {
-                    // internal state
-                    let mut i_s = state.lock().unwrap();
+        tokio::spawn(async move {
 
-                    match &mode {
-                        Some(x) => {
-                            i_s.mode = x.clone();
-                            // short circuit the animation
-                            i_s.current_animation = None;
-                            continue 
+            while should_run.load(Ordering::SeqCst) {
+                // everything sent since the last frame, one action per frame falls behind a busy bus
+                let mut mode_changed = false;
+                loop {
+                    let action = match receiver.try_recv() {
+                        Ok(action) => action,
+                        Err(TryRecvError::Lagged(missed)) => {
+                            log::warn!("{} missed {} actions", agent, missed);
+                            continue;
                         },
-                        _ => ()
-                    }
-            
-                    // if no animation selected, pick one at random (depending on the current mode)
-                    if i_s.current_animation.is_none() {
-                        let animation: Option<AnimationInfo>;
-                        let rand = {
-                            let mut rng = rand::thread_rng();
-                            let r = rng.gen::<u32>() as usize;
-                            drop(rng);
-                            r
-                        };
-                        let ai;
-                        let animation = 
-                            match i_s.mode {
-                                AnimationServiceMode::Idle => {
-                                    ai = rand % config.animations.idle.len();
-                                    config.animations.idle.get(ai).cloned()
-                                },
-                                _ => {
-                                    ai = rand % config.animations.action.len();
-                                    config.animations.action.get(ai).cloned()
-                                }
-                            };
-
-                        let _ = sndr.send(
-                            DispatchActions::NewAnimationStarted(
-                                animation.clone().unwrap().name
-                            )
-                        ).unwrap_or_default(); // needs to be expect
-
-                        i_s.current_animation = animation;
-                        i_s.current_frame_index = 0;
+                        Err(_) => break
+                    };
+                    let unanswered = matches!(action, DispatchActions::AskQuestion(..)) && !answers_questions.load(Ordering::SeqCst);
+                    if action.is_for(&agent) && !unanswered {
+                        mode_changed |= animation.lock().unwrap().state_mut().handle_action(&action, clock.now());
                     }
-            
-                    let animation = i_s.current_animation.clone().unwrap();
-            
-                    // guard against reading non-existent frame
-                    if i_s.current_frame_index > animation.frames.len() - 1 {
-                        i_s.current_animation = None;
-                        continue;
-                    }
-                    
-                    let frame_info = &animation.frames[i_s.current_frame_index].clone();
-                    i_s.current_frame_info = Some(frame_info.clone());
-                    i_s.current_frame_index += 1;
-                    drop(i_s); // free up mutex then wait for the duration
-                    duration = frame_info.duration.clone();
+                }
+                // pick the next frame straight away for the new mode
+                if mode_changed {
+                    continue;
+                }
+
+                let (step, finished) = {
+                    let mut animation = animation.lock().unwrap();
+                    let step = animation.step(clock.now());
+                    (step, animation.state_mut().take_finished_lifecycle())
+                };
+                for event in finished {
+                    let _ = sndr.send(DispatchActions::LifecycleAnimationDone(agent.clone(), event));
+                }
+                let Some(step) = step else {
+                    clock.sleep(NOTHING_TO_PLAY_DELAY).await;
+                    continue;
+                };
+
+                if let Some(name) = &step.started_animation {
+                    let _ = sndr.send(DispatchActions::NewAnimationStarted(agent.clone(), name.to_owned()));
+                }
+                let volume = audio_settings.lock().unwrap().effective_volume();
+                if let (Some(sound), Some(volume)) = (&step.frame.sound, volume) {
+                    audio.play(sound, volume);
+                }
+                if let Some(event) = &step.frame.event {
+                    let _ = sndr.send(DispatchActions::AnimationEvent(agent.clone(), event.to_owned()));
                 }
                 let _ = sndr.send(DispatchActions::NewFrameToRender);
-                tokio::time::sleep(Duration::from_millis(duration as u64)).await;
+                clock.sleep(step.duration()).await;
             }
         });
         
     }
 
-    pub fn render_animation(&self, ui: &mut Ui) -> ScrollAreaOutput<()>{
-        let frame;
-        {
-        let state_ = self.state.lock().unwrap();
-        frame = state_.current_frame_info.clone().unwrap_or_default();
-        }
+    /// stops the animation loop, e.g. when swapping characters
+    pub fn stop(&self){
+        self.should_run.store(false, Ordering::SeqCst);
+    }
+
+    /// the agent this plays for, actions addressed to other agents are ignored. Call before `start`
+    pub fn set_agent(&mut self, agent: AgentId) {
+        self.agent = agent;
+    }
+
+    pub fn agent(&self) -> &str {
+        &self.agent
+    }
+
+    /// time source for the animation loop, call before `start`
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    /// random source for picking animations and branches, call before `start`
+    pub fn set_rng(&mut self, rng: impl RngCore + Send + 'static) {
+        self.animation.lock().unwrap().set_rng(rng);
+    }
+
+    /// where frame sounds are played, nowhere by default. Call before `start`
+    pub fn set_audio_sink(&mut self, audio: Arc<dyn AudioSink>) {
+        self.audio = audio;
+    }
+
+    /// volume and mute for frame sounds, takes effect straight away
+    pub fn set_audio_settings(&self, settings: AudioSettings) {
+        *self.audio_settings.lock().unwrap() = settings;
+    }
 
-        let vert_scroll_off = 1.0 + (frame.info.row * self.sprite_height) as f32;
-        let hoz_scroll_off = 1.0 + (frame.info.column * self.sprite_width) as f32;
+    /// whether questions put this agent in its thinking state, off for agents without an assistant.
+    /// Takes effect straight away
+    pub fn set_answers_questions(&self, answers: bool) {
+        self.answers_questions.store(answers, Ordering::SeqCst);
+    }
 
-        egui::ScrollArea::both()
-            .max_height((self.sprite_height - 5) as f32 )
-            .max_width((self.sprite_width - 5) as f32 )
-            .scroll_bar_visibility(egui::scroll_area::ScrollBarVisibility::AlwaysHidden)
-            .enable_scrolling(false)
-            .auto_shrink(false)
-            .vertical_scroll_offset(vert_scroll_off)
-            .horizontal_scroll_offset(hoz_scroll_off)
-            .show(ui, |ui|{
-            ui.add(
-                egui::Image::from_texture(&self.texture_handle)
-            );
-        })     
+    /// playback speed and reduced motion, takes effect from the next frame
+    pub fn set_playback(&self, playback: PlaybackSettings) {
+        self.animation.lock().unwrap().set_playback(playback);
     }
 
+    /// loop the walk animation for `direction` while the window moves, `None` when it stops
+    pub fn set_moving(&self, direction: Option<MoveDirection>) {
+        self.animation.lock().unwrap().set_moving(direction);
+    }
 
-}
\ No newline at end of file
+    /// a copy of the scheduler state
+    pub fn state(&self) -> AnimationState {
+        self.animation.lock().unwrap().state().clone()
+    }
+
+    /// draw scale, overrides the one from the sprite sheet info
+    pub fn set_scale(&mut self, scale: f32) {
+        self.animation.lock().unwrap().set_scale(scale);
+    }
+
+    /// flip the character horizontally
+    pub fn set_mirror(&mut self, mirror: bool) {
+        self.animation.lock().unwrap().set_mirror(mirror);
+    }
+
+    /// look toward `target` if it's outside `sprite_rect`, where the character was drawn.
+    /// The scheduler throttles it, so it can be called on every pointer move
+    pub fn look_toward(&self, target: Pos2, sprite_rect: Rect) {
+        self.animation.lock().unwrap().look_toward(target, sprite_rect, self.clock.now());
+    }
+
+    /// size the animation takes up on screen
+    pub fn frame_size(&self) -> Vec2 {
+        self.animation.lock().unwrap().frame_size()
+    }
+
+    /// draw the current frame, the response senses clicks and drags on the character
+    pub fn render_animation(&self, ui: &mut Ui) -> Response {
+        self.animation.lock().unwrap().paint(ui, self.clock.now())
+    }
+}
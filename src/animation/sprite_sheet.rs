@@ -0,0 +1,137 @@
+use egui::Color32;
+use egui::ColorImage;
+use egui::Context;
+use egui::Painter;
+use egui::Pos2;
+use egui::Rect;
+use egui::TextureHandle;
+use egui::TextureOptions;
+use egui::Vec2;
+
+use super::models::FrameInfo;
+use super::models::SpriteFilter;
+use super::models::SpriteSheetInfo;
+
+/// A sprite sheet uploaded to the gpu, knows how to cut a single cell out of
+/// the texture and paint it into a rect.
+/// Sheets too big for one texture are split into pages, one texture each.
+#[derive(Clone)]
+pub struct SpriteSheet {
+    pages: Vec<TextureHandle>,
+    cell_width: usize,
+    cell_height: usize,
+}
+
+impl SpriteSheet {
+    /// `pages` must not be empty, the cell size is worked out from the first one
+    pub fn new(ctx: &Context, name: &str, pages: Vec<ColorImage>, info: &SpriteSheetInfo) -> Self {
+        let options = match info.filter {
+            SpriteFilter::Nearest => TextureOptions::NEAREST,
+            SpriteFilter::Linear => TextureOptions::LINEAR,
+        };
+
+        let [cell_width, cell_height] = info.cell_size(pages[0].size);
+
+        let pages = pages
+            .into_iter()
+            .enumerate()
+            .map(|(i, image)| match i {
+                0 => ctx.load_texture(name, image, options),
+                _ => ctx.load_texture(format!("{}_{}", name, i), image, options),
+            })
+            .collect();
+
+        SpriteSheet {
+            pages,
+            cell_width,
+            cell_height,
+        }
+    }
+
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// size of a single cell in sprite sheet pixels
+    pub fn cell_size(&self) -> Vec2 {
+        Vec2::new(self.cell_width as f32, self.cell_height as f32)
+    }
+
+    /// texture of the page a frame is on
+    pub fn texture(&self, info: &FrameInfo) -> Option<&TextureHandle> {
+        self.pages.get(info.page)
+    }
+
+    /// uv coordinates (0..1) of a frame's pixels on its page
+    pub fn frame_uv(&self, info: &FrameInfo) -> Rect {
+        let size = self
+            .texture(info)
+            .map_or(Vec2::splat(1.0), |t| t.size_vec2());
+        let region = info.source_region([self.cell_width, self.cell_height]);
+        let min = Pos2::new(region.x as f32 / size.x, region.y as f32 / size.y);
+        let max = Pos2::new(
+            (region.x + region.width) as f32 / size.x,
+            (region.y + region.height) as f32 / size.y,
+        );
+        Rect::from_min_max(min, max)
+    }
+
+    /// paint a frame and its layers into `rect`, `rect` is the whole (scaled) cell
+    pub fn paint_frame(&self, painter: &Painter, rect: Rect, info: &FrameInfo, mirror: bool) {
+        self.paint_frame_tinted(painter, rect, info, mirror, Color32::WHITE);
+    }
+
+    /// `paint_frame` with every pixel multiplied by `tint`
+    pub fn paint_frame_tinted(
+        &self,
+        painter: &Painter,
+        rect: Rect,
+        info: &FrameInfo,
+        mirror: bool,
+        tint: Color32,
+    ) {
+        for image in info.images() {
+            self.paint_image(painter, rect, &image, mirror, tint);
+        }
+    }
+
+    /// paint a single image, layers are left to `paint_frame`
+    fn paint_image(
+        &self,
+        painter: &Painter,
+        rect: Rect,
+        info: &FrameInfo,
+        mirror: bool,
+        tint: Color32,
+    ) {
+        let scale = rect.width() / self.cell_width as f32;
+        let region = info.source_region([self.cell_width, self.cell_height]);
+        let Some(texture) = self.texture(info) else {
+            return;
+        };
+        if region.width == 0 || region.height == 0 {
+            return;
+        }
+
+        let mut uv = self.frame_uv(info);
+        // atlas regions are trimmed, put them back where they were in the cell
+        let mut region_min = Vec2::new(region.offset_x as f32, region.offset_y as f32);
+        let region_size = Vec2::new(region.width as f32, region.height as f32);
+        let mut offset = Vec2::new(info.offset_x as f32, info.offset_y as f32);
+
+        if mirror {
+            // swapping the u coordinates flips the quad horizontally
+            std::mem::swap(&mut uv.min.x, &mut uv.max.x);
+            region_min.x = self.cell_width as f32 - region_min.x - region_size.x;
+            offset.x = -offset.x;
+        }
+
+        let min = rect.min + (region_min + offset) * scale;
+        painter.image(
+            texture.id(),
+            Rect::from_min_size(min, region_size * scale),
+            uv,
+            tint,
+        );
+    }
+}
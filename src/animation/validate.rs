@@ -0,0 +1,626 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use egui::ColorImage;
+use yaml_rust::parser::Event;
+use yaml_rust::parser::MarkedEventReceiver;
+use yaml_rust::parser::Parser;
+use yaml_rust::scanner::Marker;
+
+use super::models::AnimationConfig;
+use super::models::AnimationFrame;
+use super::models::AnimationInfo;
+use super::models::AtlasRegion;
+use super::models::FrameInfo;
+use super::models::Preprocess;
+use super::models::SpriteSheetInfo;
+use super::models::StateMachine;
+use super::models::Trigger;
+use super::models::UpscaleFilter;
+use super::preprocess::upscaled_pixels;
+use super::preprocess::MAX_UPSCALE;
+use super::preprocess::MAX_UPSCALED_PIXELS;
+
+/// longest frame duration (ms) that doesn't get flagged, anything longer is probably a typo
+const MAX_SENSIBLE_DURATION: usize = 60_000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Severity {
+    /// the animation config can't be used
+    Error,
+    /// usable, but probably not what the author meant
+    Warning,
+}
+
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// where in the config, e.g. `animations.idle[3].frames[2].info`
+    pub path: String,
+    /// 1 based line in the yaml, when it could be found
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{}", severity)?;
+        if let Some(line) = self.line {
+            write!(f, " (line {})", line)?;
+        }
+        if !self.path.is_empty() {
+            write!(f, " {}", self.path)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+pub fn has_errors(diagnostics: &[Diagnostic]) -> bool {
+    diagnostics.iter().any(|d| d.severity == Severity::Error)
+}
+
+/// where we are while walking the yaml events
+enum Container {
+    Mapping { key: Option<String> },
+    Sequence { index: usize },
+}
+
+/// maps config paths (`animations.idle[3].frames[2]`) to the line they start on
+#[derive(Default)]
+struct LineIndex {
+    lines: HashMap<String, usize>,
+    stack: Vec<(String, Container)>,
+}
+
+impl LineIndex {
+    fn build(yaml: &str) -> Self {
+        let mut index = LineIndex::default();
+        // a syntax error is reported by serde_yaml, whatever was indexed before it is still useful
+        let _ = Parser::new(yaml.chars()).load(&mut index, false);
+        index
+    }
+
+    /// path of the node that is about to start, `None` when it's a mapping key
+    fn next_path(&mut self) -> Option<String> {
+        match self.stack.last_mut() {
+            None => Some(String::new()),
+            Some((path, Container::Sequence { index })) => {
+                let p = format!("{}[{}]", path, index);
+                *index += 1;
+                Some(p)
+            }
+            Some((path, Container::Mapping { key })) => {
+                let key = key.take()?;
+                Some(if path.is_empty() {
+                    key
+                } else {
+                    format!("{}.{}", path, key)
+                })
+            }
+        }
+    }
+
+    fn line(&self, path: &str) -> Option<usize> {
+        // fall back to the closest parent that was found
+        let mut path = path;
+        loop {
+            if let Some(line) = self.lines.get(path) {
+                return Some(*line);
+            }
+            path = &path[..path.rfind(['.', '['])?];
+        }
+    }
+}
+
+impl MarkedEventReceiver for LineIndex {
+    fn on_event(&mut self, ev: Event, mark: Marker) {
+        match ev {
+            Event::Scalar(value, ..) => {
+                match self.next_path() {
+                    Some(path) => {
+                        self.lines.entry(path).or_insert(mark.line());
+                    }
+                    // it was a key, remember it for the value
+                    None => {
+                        if let Some((_, Container::Mapping { key })) = self.stack.last_mut() {
+                            *key = Some(value);
+                        }
+                    }
+                }
+            }
+            Event::SequenceStart(_) | Event::MappingStart(_) => {
+                let path = self.next_path().unwrap_or_default();
+                self.lines.entry(path.clone()).or_insert(mark.line());
+                let container = match ev {
+                    Event::SequenceStart(_) => Container::Sequence { index: 0 },
+                    _ => Container::Mapping { key: None },
+                };
+                self.stack.push((path, container));
+            }
+            Event::SequenceEnd | Event::MappingEnd => {
+                self.stack.pop();
+            }
+            _ => (),
+        }
+    }
+}
+
+struct Validator<'a> {
+    index: LineIndex,
+    diagnostics: Vec<Diagnostic>,
+    image: Option<&'a ColorImage>,
+}
+
+impl<'a> Validator<'a> {
+    fn report(&mut self, severity: Severity, path: String, message: String) {
+        let line = self.index.line(&path);
+        self.diagnostics.push(Diagnostic {
+            severity,
+            path,
+            line,
+            message,
+        });
+    }
+
+    fn is_transparent_cell(&self, info: &FrameInfo, config: &AnimationConfig) -> bool {
+        let Some(image) = self.image else {
+            return false;
+        };
+        let region = info.source_region(config.sprite_sheet_info.cell_size(image.size));
+        if region.x + region.width > image.size[0] || region.y + region.height > image.size[1] {
+            // check_cell has reported it already
+            return false;
+        }
+        (0..region.height)
+            .all(|y| (0..region.width).all(|x| image[(region.x + x, region.y + y)].a() == 0))
+    }
+
+    fn check_region(&mut self, path: &str, info: &FrameInfo) {
+        let Some(region) = &info.region else { return };
+        if region.width == 0 || region.height == 0 {
+            self.report(
+                Severity::Warning,
+                format!("{}.info.region", path),
+                "region is empty, the frame won't be shown".to_owned(),
+            );
+        }
+        self.check_fits(format!("{}.info.region", path), region);
+    }
+
+    /// errors when the pixels are not all on the sprite sheet, true when they are or there's no sheet to check
+    fn check_fits(&mut self, path: String, region: &AtlasRegion) -> bool {
+        let Some(image) = self.image else {
+            return true;
+        };
+        let [width, height] = image.size;
+        if region.x + region.width <= width && region.y + region.height <= height {
+            return true;
+        }
+        self.report(
+            Severity::Error,
+            path,
+            format!(
+                "region {}x{} at {},{} is outside the {}x{} sprite sheet",
+                region.width, region.height, region.x, region.y, width, height
+            ),
+        );
+        false
+    }
+
+    fn check_animation(
+        &mut self,
+        path: &str,
+        animation: &AnimationInfo,
+        config: &AnimationConfig,
+        transparent: &mut HashMap<(usize, usize), bool>,
+    ) {
+        let sheet = &config.sprite_sheet_info;
+        if animation.frames.is_empty() {
+            self.report(
+                Severity::Error,
+                path.to_owned(),
+                format!("{} has no frames", animation.name),
+            );
+        }
+        if !animation.weight.is_finite() || animation.weight < 0.0 {
+            self.report(
+                Severity::Error,
+                format!("{}.weight", path),
+                format!("weight {} has to be a number, 0 or more", animation.weight),
+            );
+        }
+
+        for (i, frame) in animation.frames.iter().enumerate() {
+            let path = format!("{}.frames[{}]", path, i);
+
+            if frame.duration == 0 {
+                // agent data uses these as jump points, but they're never seen
+                self.report(
+                    Severity::Warning,
+                    format!("{}.duration", path),
+                    "duration is zero, the frame won't be shown".to_owned(),
+                );
+            } else if frame.duration > MAX_SENSIBLE_DURATION {
+                self.report(
+                    Severity::Warning,
+                    format!("{}.duration", path),
+                    format!(
+                        "duration of {}ms is very long, it's in milliseconds",
+                        frame.duration
+                    ),
+                );
+            }
+
+            let info = &frame.info;
+            self.check_layers(&path, info, sheet);
+            if info.page > 0 {
+                self.report_page(&format!("{}.info", path), info.page);
+                continue;
+            }
+            if info.region.is_some() {
+                // atlas frames don't use the grid
+                self.check_region(&path, info);
+                self.check_branches(&path, animation, frame);
+                continue;
+            }
+
+            let in_bounds = info.column < sheet.columns && info.row < sheet.rows;
+            if info.column >= sheet.columns {
+                self.report(
+                    Severity::Error,
+                    format!("{}.info.column", path),
+                    format!(
+                        "column {} is outside the {} column sprite sheet",
+                        info.column, sheet.columns
+                    ),
+                );
+            }
+            if info.row >= sheet.rows {
+                self.report(
+                    Severity::Error,
+                    format!("{}.info.row", path),
+                    format!(
+                        "row {} is outside the {} row sprite sheet",
+                        info.row, sheet.rows
+                    ),
+                );
+            }
+            if in_bounds
+                && sheet.columns > 0
+                && sheet.rows > 0
+                && self.check_cell(&path, info, sheet)
+            {
+                let is_transparent = *transparent
+                    .entry((info.column, info.row))
+                    .or_insert_with(|| self.is_transparent_cell(info, config));
+                if is_transparent {
+                    self.report(
+                        Severity::Warning,
+                        format!("{}.info", path),
+                        format!("cell {},{} is fully transparent", info.column, info.row),
+                    );
+                }
+            }
+
+            self.check_branches(&path, animation, frame);
+        }
+    }
+
+    /// a grid cell can still run off the sheet when `frame_size` is bigger than the sheet allows
+    fn check_cell(&mut self, path: &str, info: &FrameInfo, sheet: &SpriteSheetInfo) -> bool {
+        let Some(image) = self.image else {
+            return true;
+        };
+        let region = info.source_region(sheet.cell_size(image.size));
+        self.check_fits(format!("{}.info", path), &region)
+    }
+
+    /// pages are made by the loader when a sheet is too big for one texture, a pack only has the one sheet
+    fn report_page(&mut self, path: &str, page: usize) {
+        self.report(
+            Severity::Error,
+            format!("{}.page", path),
+            format!(
+                "page {} doesn't exist, a character pack has a single sprite sheet",
+                page
+            ),
+        );
+    }
+
+    fn check_layers(&mut self, path: &str, info: &FrameInfo, sheet: &SpriteSheetInfo) {
+        for (i, layer) in info.layers.iter().enumerate() {
+            let path = format!("{}.info.layers[{}]", path, i);
+            if layer.page > 0 {
+                self.report_page(&path, layer.page);
+                continue;
+            }
+            match &layer.region {
+                Some(region) => {
+                    self.check_fits(format!("{}.region", path), region);
+                }
+                None => {
+                    if layer.column >= sheet.columns || layer.row >= sheet.rows {
+                        self.report(
+                            Severity::Error,
+                            path,
+                            format!(
+                                "cell {},{} is outside the {}x{} sprite sheet",
+                                layer.column, layer.row, sheet.columns, sheet.rows
+                            ),
+                        );
+                    } else if let Some(image) = self.image {
+                        let region = layer.source_region(sheet.cell_size(image.size));
+                        self.check_fits(path, &region);
+                    }
+                }
+            }
+        }
+    }
+
+    fn check_branches(&mut self, path: &str, animation: &AnimationInfo, frame: &AnimationFrame) {
+        let targets = frame
+            .branching
+            .iter()
+            .map(|b| b.frame_index)
+            .chain(frame.exit_branch);
+        for target in targets {
+            if target >= animation.frames.len() {
+                self.report(
+                    Severity::Error,
+                    path.to_owned(),
+                    format!(
+                        "branches to frame {} but {} only has {} frames",
+                        target,
+                        animation.name,
+                        animation.frames.len()
+                    ),
+                );
+            }
+        }
+        let total_weight: u32 = frame.branching.iter().map(|b| b.weight).sum();
+        if total_weight > 100 {
+            self.report(
+                Severity::Warning,
+                format!("{}.branching", path),
+                format!("branch weights add up to {}%", total_weight),
+            );
+        }
+    }
+
+    fn check_preprocess(&mut self, preprocess: &Preprocess) {
+        if !preprocess.hue_shift.is_finite() {
+            self.report(
+                Severity::Error,
+                "preprocess.hue_shift".to_owned(),
+                "hue shift must be a number of degrees".to_owned(),
+            );
+        }
+        for (i, swap) in preprocess.palette.iter().enumerate() {
+            if preprocess.palette[..i]
+                .iter()
+                .any(|earlier| earlier.from == swap.from)
+            {
+                self.report(
+                    Severity::Warning,
+                    format!("preprocess.palette[{}].from", i),
+                    format!("{:?} is already swapped, this one is ignored", swap.from),
+                );
+            }
+        }
+        let Some(upscale) = &preprocess.upscale else {
+            return;
+        };
+        let too_big = self.image.filter(|image| {
+            upscaled_pixels(image.size[0], image.size[1], upscale.factor) > MAX_UPSCALED_PIXELS
+        });
+        if upscale.factor == 0 || upscale.factor > MAX_UPSCALE {
+            self.report(
+                Severity::Error,
+                "preprocess.upscale.factor".to_owned(),
+                format!(
+                    "factor {} has to be between 1 and {}",
+                    upscale.factor, MAX_UPSCALE
+                ),
+            );
+        } else if let Some(image) = too_big {
+            self.report(
+                Severity::Error,
+                "preprocess.upscale.factor".to_owned(),
+                format!(
+                    "a {}x{} sheet upscaled {} times is over the {} pixel limit",
+                    image.size[0], image.size[1], upscale.factor, MAX_UPSCALED_PIXELS
+                ),
+            );
+        } else if upscale.filter == UpscaleFilter::Scale2x && !upscale.factor.is_power_of_two() {
+            self.report(
+                Severity::Warning,
+                "preprocess.upscale.factor".to_owned(),
+                format!(
+                    "Scale2x works in steps of 2, a factor of {} repeats pixels for the rest",
+                    upscale.factor
+                ),
+            );
+        }
+    }
+
+    fn check_state_machine(&mut self, config: &AnimationConfig) {
+        let machine = &config.state_machine;
+        if machine.state(&machine.initial).is_none() {
+            self.report(
+                Severity::Error,
+                "state_machine.initial".to_owned(),
+                format!("there is no state called {}", machine.initial),
+            );
+        }
+        for (name, state) in &machine.states {
+            let path = format!("state_machine.states.{}", name);
+            for (trigger, to) in &state.on {
+                if machine.state(to).is_none() {
+                    self.report(
+                        Severity::Error,
+                        format!("{}.on", path),
+                        format!(
+                            "{:?} goes to {} but there is no state called that",
+                            trigger, to
+                        ),
+                    );
+                }
+            }
+            // the built in machine asks for animations most characters have, it's fine without them
+            let missing = state
+                .animations
+                .iter()
+                .filter(|name| config.find_animation(name).is_none());
+            for animation in missing.filter(|_| *machine != StateMachine::default()) {
+                self.report(
+                    Severity::Warning,
+                    format!("{}.animations", path),
+                    format!(
+                        "there is no animation called {}, it plays from the category instead",
+                        animation
+                    ),
+                );
+            }
+            match (state.timeout_secs, state.on.contains_key(&Trigger::Timeout)) {
+                (Some(_), false) => self.report(
+                    Severity::Warning,
+                    format!("{}.timeout_secs", path),
+                    "there's no timeout transition, it does nothing".to_owned(),
+                ),
+                (None, true) => self.report(
+                    Severity::Warning,
+                    format!("{}.on", path),
+                    "the timeout transition needs timeout_secs, it never fires".to_owned(),
+                ),
+                _ => (),
+            }
+        }
+    }
+
+    fn check(&mut self, config: &AnimationConfig) {
+        let sheet = &config.sprite_sheet_info;
+        if sheet.columns == 0 || sheet.rows == 0 {
+            self.report(
+                Severity::Error,
+                "sprite_sheet_info".to_owned(),
+                "columns and rows must be at least 1".to_owned(),
+            );
+        } else if let Some(image) = self.image {
+            let [width, height] = image.size;
+            if width < sheet.columns || height < sheet.rows {
+                self.report(
+                    Severity::Error,
+                    "sprite_sheet_info".to_owned(),
+                    format!(
+                        "a {}x{} image can't hold {}x{} cells",
+                        width, height, sheet.columns, sheet.rows
+                    ),
+                );
+            } else if width % sheet.columns != 0 || height % sheet.rows != 0 {
+                self.report(
+                    Severity::Warning,
+                    "sprite_sheet_info".to_owned(),
+                    format!(
+                        "a {}x{} image doesn't divide evenly into {}x{} cells",
+                        width, height, sheet.columns, sheet.rows
+                    ),
+                );
+            }
+        }
+        if sheet.scale <= 0.0 {
+            self.report(
+                Severity::Error,
+                "sprite_sheet_info.scale".to_owned(),
+                "scale must be positive".to_owned(),
+            );
+        }
+        self.check_preprocess(&config.preprocess);
+        self.check_state_machine(config);
+
+        let categories = [
+            ("animations.idle", &config.animations.idle),
+            ("animations.action", &config.animations.action),
+        ];
+        if categories
+            .iter()
+            .all(|(_, animations)| animations.is_empty())
+        {
+            self.report(
+                Severity::Error,
+                "animations".to_owned(),
+                "there are no animations".to_owned(),
+            );
+        }
+
+        let mut seen: HashMap<&str, String> = HashMap::new();
+        let mut transparent = HashMap::new();
+        for (category, animations) in categories {
+            if animations.is_empty() {
+                self.report(
+                    Severity::Warning,
+                    category.to_owned(),
+                    "category is empty".to_owned(),
+                );
+            }
+
+            for (i, animation) in animations.iter().enumerate() {
+                let path = format!("{}[{}]", category, i);
+                if let Some(first) = seen.get(animation.name.as_str()) {
+                    self.report(
+                        Severity::Error,
+                        format!("{}.name", path),
+                        format!("{} is already used by {}", animation.name, first),
+                    );
+                } else {
+                    seen.insert(&animation.name, path.to_owned());
+                }
+                self.check_animation(&path, animation, config, &mut transparent);
+            }
+        }
+    }
+}
+
+/// check a parsed config, `image` is the decoded sprite sheet if it's available
+pub fn validate(
+    yaml: &str,
+    config: &AnimationConfig,
+    image: Option<&ColorImage>,
+) -> Vec<Diagnostic> {
+    let mut validator = Validator {
+        index: LineIndex::build(yaml),
+        diagnostics: Vec::new(),
+        image,
+    };
+    validator.check(config);
+    validator.diagnostics
+}
+
+/// parse and check animation yaml, returns the config only if there are no errors
+pub fn parse_and_validate(
+    yaml: &str,
+    image: Option<&ColorImage>,
+) -> (Option<AnimationConfig>, Vec<Diagnostic>) {
+    let config: AnimationConfig = match serde_yaml::from_str(yaml) {
+        Ok(config) => config,
+        Err(e) => {
+            let diagnostic = Diagnostic {
+                severity: Severity::Error,
+                path: String::new(),
+                line: e.location().map(|l| l.line()),
+                message: e.to_string(),
+            };
+            return (None, vec![diagnostic]);
+        }
+    };
+
+    let diagnostics = validate(yaml, &config, image);
+    if has_errors(&diagnostics) {
+        (None, diagnostics)
+    } else {
+        (Some(config), diagnostics)
+    }
+}
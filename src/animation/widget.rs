@@ -0,0 +1,242 @@
+//! `SpriteAnimation`, an animated character for any egui app. It only needs an
+//! `AnimationConfig` and a sprite sheet texture: no tokio, no event bus, no app actions.
+//! Add it with `ui.add(&mut animation)` and steer it with `play` and `set_mode`.
+//! `AnimationService` wraps one to hook it up to the app.
+
+use std::time::Duration;
+
+use egui::ColorImage;
+use egui::Context;
+use egui::Pos2;
+use egui::Rect;
+use egui::Response;
+use egui::Sense;
+use egui::Ui;
+use egui::Vec2;
+use egui::Widget;
+use rand::rngs::StdRng;
+use rand::RngCore;
+use rand::SeedableRng;
+
+use super::effects;
+use super::models::AnimationConfig;
+use super::models::AnimationServiceMode;
+use super::models::MoveDirection;
+use super::scheduler::AnimationState;
+use super::scheduler::FrameStep;
+use super::scheduler::LookDirection;
+use super::scheduler::PlaybackSettings;
+use super::sprite_sheet::SpriteSheet;
+
+/// how long to wait before trying again when there is nothing to play
+pub const NOTHING_TO_PLAY_DELAY: Duration = Duration::from_millis(500);
+
+/// frames further behind than this are skipped rather than caught up, e.g. after the window was hidden
+const CATCH_UP_LIMIT: Duration = Duration::from_secs(1);
+
+/// most frames `advance` steps through in one go, zero length frames can loop
+const MAX_STEPS: usize = 64;
+
+pub struct SpriteAnimation {
+    config: AnimationConfig,
+    sheet: SpriteSheet,
+    state: AnimationState,
+    rng: Box<dyn RngCore + Send>,
+    scale: f32,
+    mirror: bool,
+    /// clock time the frame on screen is over, `None` to pick one straight away
+    frame_ends_at: Option<Duration>,
+}
+
+impl SpriteAnimation {
+    /// `config` should be validated against the sheet, see `validate::parse_and_validate`
+    pub fn new(config: AnimationConfig, sheet: SpriteSheet) -> Self {
+        let mut state = AnimationState::default();
+        state.set_state_machine(config.state_machine.clone());
+        SpriteAnimation {
+            scale: config.sprite_sheet_info.scale,
+            mirror: config.sprite_sheet_info.mirror,
+            config,
+            sheet,
+            state,
+            rng: Box::new(StdRng::from_entropy()),
+            frame_ends_at: None,
+        }
+    }
+
+    /// upload `image` as the sprite sheet texture
+    pub fn from_image(
+        ctx: &Context,
+        name: &str,
+        config: AnimationConfig,
+        image: ColorImage,
+    ) -> Self {
+        let sheet = SpriteSheet::new(ctx, name, vec![image], &config.sprite_sheet_info);
+        SpriteAnimation::new(config, sheet)
+    }
+
+    pub fn config(&self) -> &AnimationConfig {
+        &self.config
+    }
+
+    pub fn state(&self) -> &AnimationState {
+        &self.state
+    }
+
+    /// for driving the scheduler directly, e.g. with app events
+    pub fn state_mut(&mut self) -> &mut AnimationState {
+        &mut self.state
+    }
+
+    /// random source for picking animations and branches
+    pub fn set_rng(&mut self, rng: impl RngCore + Send + 'static) {
+        self.rng = Box::new(rng);
+    }
+
+    /// switch between idling and the action animations
+    pub fn set_mode(&mut self, mode: AnimationServiceMode) {
+        self.state.set_mode(mode);
+        self.frame_ends_at = None;
+    }
+
+    /// play the animation `name` once, then go back to what the mode plays
+    pub fn play(&mut self, name: &str) {
+        self.state.play(name);
+        self.frame_ends_at = None;
+    }
+
+    /// loop the walk animation for `direction` until it's `None` again
+    pub fn set_moving(&mut self, direction: Option<MoveDirection>) {
+        let direction = direction.map(|d| if self.mirror { d.mirrored() } else { d });
+        if direction != self.state.moving() {
+            self.state.set_moving(direction);
+            self.frame_ends_at = None;
+        }
+    }
+
+    /// speed and reduced motion
+    pub fn set_playback(&mut self, playback: PlaybackSettings) {
+        self.state.set_playback(playback);
+    }
+
+    /// draw scale, overrides the one from the sprite sheet info
+    pub fn set_scale(&mut self, scale: f32) {
+        self.scale = scale;
+    }
+
+    /// flip the character horizontally
+    pub fn set_mirror(&mut self, mirror: bool) {
+        self.mirror = mirror;
+    }
+
+    pub fn mirror(&self) -> bool {
+        self.mirror
+    }
+
+    /// size the animation takes up on screen
+    pub fn frame_size(&self) -> Vec2 {
+        self.sheet.cell_size() * self.scale
+    }
+
+    /// look toward `target` at clock time `now` if it's outside `sprite_rect`, where the
+    /// character was drawn. The scheduler throttles it, so it can be called on every pointer move
+    pub fn look_toward(&mut self, target: Pos2, sprite_rect: Rect, now: Duration) {
+        if sprite_rect.contains(target) {
+            return;
+        }
+        let offset = target - sprite_rect.center();
+        let Some(direction) = LookDirection::from_offset(offset.x, offset.y) else {
+            return;
+        };
+        let direction = if self.mirror {
+            direction.mirrored()
+        } else {
+            direction
+        };
+        if self.state.look_toward(direction, now) {
+            self.frame_ends_at = None;
+        }
+    }
+
+    /// move on to the next frame now, for callers that keep their own time
+    pub fn step(&mut self, now: Duration) -> Option<FrameStep> {
+        self.state.next_frame(&self.config, now, &mut *self.rng)
+    }
+
+    /// play every frame that is due by clock time `now`, oldest first.
+    /// Callers can play the frames' sounds or act on their events
+    pub fn advance(&mut self, now: Duration) -> Vec<FrameStep> {
+        let mut steps = Vec::new();
+        while steps.len() < MAX_STEPS {
+            let start = match self.frame_ends_at {
+                Some(t) if t > now => break,
+                Some(t) if now - t < CATCH_UP_LIMIT => t,
+                _ => now,
+            };
+            match self.step(start) {
+                Some(step) => {
+                    self.frame_ends_at = Some(start + step.duration());
+                    steps.push(step);
+                }
+                None => {
+                    self.frame_ends_at = Some(now + NOTHING_TO_PLAY_DELAY);
+                    break;
+                }
+            }
+        }
+        steps
+    }
+
+    /// how long until `advance` has something to do
+    pub fn time_to_next_frame(&self, now: Duration) -> Duration {
+        self.frame_ends_at
+            .map_or(Duration::ZERO, |t| t.saturating_sub(now))
+    }
+
+    /// draw the current frame with its effects, `now` is the clock time for the error tint.
+    /// The response senses clicks and drags on the character
+    pub fn paint(&self, ui: &mut Ui, now: Duration) -> Response {
+        let frame = self.state.current_frame().cloned().unwrap_or_default();
+        let mut effects = self.config.effects.all.clone();
+        if let Some(animation) = self.state.current_animation() {
+            effects = effects.merged(&animation.effects);
+        }
+        if self.state.is_waiting() {
+            effects = effects.merged(&self.config.effects.waiting);
+        }
+        if self.state.showing_error(now) {
+            effects.tint = self.config.effects.error_tint.or(effects.tint);
+        }
+        let reduced_motion = self.state.playback().reduced_motion;
+
+        let (rect, response) = ui.allocate_exact_size(self.frame_size(), Sense::click_and_drag());
+        if ui.is_rect_visible(rect) {
+            let time = ui.input(|i| i.time);
+            let painter = ui.painter();
+            effects::paint_behind(painter, rect, &effects, time, reduced_motion);
+            self.sheet.paint_frame_tinted(
+                painter,
+                rect,
+                &frame.info,
+                self.mirror,
+                effects::tint(&effects),
+            );
+            effects::paint_front(painter, rect, &effects, time, reduced_motion);
+            if effects::is_moving(&effects) && !reduced_motion {
+                ui.ctx().request_repaint();
+            }
+        }
+        response
+    }
+}
+
+/// advances on egui's clock (`ui.input().time`) and asks for a repaint when the next frame is due
+impl Widget for &mut SpriteAnimation {
+    fn ui(self, ui: &mut Ui) -> Response {
+        let now = Duration::from_secs_f64(ui.input(|i| i.time));
+        self.advance(now);
+        let response = self.paint(ui, now);
+        ui.ctx().request_repaint_after(self.time_to_next_frame(now));
+        response
+    }
+}
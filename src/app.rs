@@ -1,17 +1,35 @@
 use std::collections::HashMap;
+use std::collections::HashSet;
 
 use std::env::current_exe;
 use std::fs;
-use std::path::PathBuf;
 use std::time::Duration;
 use std::sync::Arc;
 use std::sync::Mutex;
+use crate::actions::AgentId;
 use crate::actions::DispatchActions;
+use crate::actions::Interaction;
+use crate::actions::LifecycleEvent;
+use crate::animation::audio::AudioSettings;
+use crate::animation::editor::AnimationEditor;
 use crate::animation::models::AnimationServiceMode;
+use crate::animation::models::MoveDirection;
+use crate::animation::pack::list_packs;
+use crate::animation::pack::user_packs_dir;
+use crate::animation::pack::CharacterPackInfo;
+use crate::animation::pack::PackSource;
+use crate::animation::scheduler::PlaybackSettings;
+use crate::animation::scheduler::MAX_SPEED;
+use crate::animation::scheduler::MIN_SPEED;
 use crate::animation::service::AnimationService;
 use crate::assistant::AssistantService;
+use crate::models::AgentConfig;
 use crate::models::AppConfig;
 use crate::models::QuestionResponse;
+use crate::movement;
+use crate::movement::Movement;
+use crate::movement::WALK_SPEED;
+use crate::movement::WANDER_AFTER_SECS;
 use crate::state_updater::StateUpdater;
 
 
@@ -19,42 +37,134 @@ use egui::Color32;
 use egui::Id;
 use egui::Layout;
 use egui::Margin;
+use egui::Pos2;
+use egui::Rect;
+use egui::Response;
 use egui::Rounding;
 use egui::Sense;
 use egui::Separator;
 use egui::Stroke;
 use egui::Ui;
 use egui::ViewportCommand;
+use egui::Vec2;
+use egui::vec2;
 use egui_extras::Size;
 
 use egui_extras::StripBuilder;
 use tokio::sync::broadcast;
 use tokio::sync::broadcast::Sender;
 
+/// how long to wait for the goodbye animation before closing anyway
+const QUIT_TIMEOUT_SECS: f64 = 10.0;
+
+/// egui's double click window, a click only counts once it's over
+const DOUBLE_CLICK_SECS: f64 = 0.3;
+
 #[derive(Clone)]
 pub struct ClippitGptAppShared{
     pub question_field: String,
     pub mode: AnimationServiceMode,
-    pub answers: Vec<QuestionResponse>,
-    pub current_animation: String 
+    pub answers: Vec<(AgentId, QuestionResponse)>,
+    /// what each agent is playing
+    pub current_animation: HashMap<AgentId, String>,
+    /// every agent on screen, the main one first
+    pub agents: Vec<AgentId>,
+    /// agents with an assistant, they answer questions
+    pub assistants: Vec<AgentId>,
+    /// agents still working on an answer
+    pub waiting_for: HashSet<AgentId>,
+    pub said_goodbye: HashSet<AgentId>,
+    pub hidden: HashSet<AgentId>,
+    /// the last thing that went wrong talking to an assistant, cleared by an answer
+    pub assistant_error: Option<String>,
+    /// every agent said goodbye, the window can close
+    pub quit_ready: bool,
+    /// give the question field the keyboard on the next frame
+    pub focus_question_field: bool,
+    /// character pack picked from an agent's menu
+    pub switch_to_pack: Option<(AgentId, usize)>,
+    /// walk the window here, see `DispatchActions::MoveTo`
+    pub move_to: Option<Pos2>
+}
+
+/// how fast answers type out, at playback speed 1
+const REVEAL_CHARS_PER_SEC: f64 = 40.0;
+
+/// eframe storage key for `Settings`
+const SETTINGS_KEY: &str = "settings";
+
+/// the user's choices, saved between runs
+#[derive(serde::Deserialize, serde::Serialize, Default)]
+#[serde(default)]
+struct Settings {
+    audio: AudioSettings,
+    playback: PlaybackSettings,
+    /// no wandering around the screen when idle
+    stay_put: bool
+}
+
+/// how far an answer has typed out
+struct Reveal {
+    /// `ctx.input().time` the answer showed up
+    started_at: f64,
+    /// `AnswerRevealed` has been sent
+    done: bool
+}
+
+/// a character on screen, its animation is in `animations` under the same id
+struct Agent {
+    id: AgentId,
+    /// index into `packs`
+    pack: usize,
+    /// `ctx.input().time` of a click that could still become a double click
+    pending_click: Option<f64>
 }
 
 pub struct ClippitGptApp {
     state: Arc<Mutex<ClippitGptAppShared>>,
-    animations: HashMap<String, AnimationService>,
-    mpmc_channel: Sender<DispatchActions>
+    animations: HashMap<AgentId, AnimationService>,
+    mpmc_channel: Sender<DispatchActions>,
+    packs: Vec<CharacterPackInfo>,
+    /// the main agent first, the picker and the editor work on it
+    agents: Vec<Agent>,
+    /// who questions go to, everyone when `None`
+    ask_to: Option<AgentId>,
+    /// volume and mute for every agent's sounds
+    audio: AudioSettings,
+    /// speed and reduced motion for every agent
+    playback: PlaybackSettings,
+    /// typewriter progress of each answer, by index into `answers`
+    reveals: Vec<Reveal>,
+    stay_put: bool,
+    /// where the window is walking to
+    movement: Option<Movement>,
+    /// the walk animation the agents are playing
+    walking: Option<MoveDirection>,
+    /// `ctx.input().time` to wander off at, pushed back by any input
+    wander_at: f64,
+    pack_error: Option<String>,
+    editor: Option<AnimationEditor>,
+    /// the renderer's texture size limit, sprite sheets bigger than this are split into pages
+    max_texture_side: usize,
+    /// `ctx.input().time` quitting started at
+    quit_requested_at: Option<f64>,
+    was_minimized: bool
 }
 
 impl ClippitGptApp {
     /// Called once before the first frame.
     pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
-        let current_exe_path = PathBuf::from(current_exe().unwrap());
+        let current_exe_path = current_exe().unwrap();
         let exe_folder = current_exe_path.as_path().parent().unwrap();
         let config_path = exe_folder.join("config.yaml");
         let config = fs::read_to_string(config_path).expect("config.yaml file not found!");
         let config: AppConfig = serde_yaml::from_str(&config).expect("unable to parse config file!");
 
         let (sndr, _) = broadcast::channel::<DispatchActions>(50);
+        let agent_configs = config.agents();
+        let settings: Settings = cc.storage
+            .and_then(|storage| eframe::get_value(storage, SETTINGS_KEY))
+            .unwrap_or_default();
         
         let shared = Arc::new(
             Mutex::new(
@@ -62,35 +172,49 @@ impl ClippitGptApp {
                     question_field: "".to_owned(),
                     mode: AnimationServiceMode::Idle,
                     answers: Vec::new(),
-                    current_animation: "".to_owned()
+                    current_animation: HashMap::new(),
+                    agents: agent_configs.iter().map(|a| a.id.to_owned()).collect(),
+                    assistants: agent_configs.iter().filter(|a| !a.silent).map(|a| a.id.to_owned()).collect(),
+                    waiting_for: HashSet::new(),
+                    said_goodbye: HashSet::new(),
+                    hidden: HashSet::new(),
+                    assistant_error: None,
+                    quit_ready: false,
+                    focus_question_field: false,
+                    switch_to_pack: None,
+                    move_to: None
                 }
             )
         );
 
-        let config_data = include_str!("../assets/animations.yaml");
-        let image_data = include_bytes!("../assets/clippy.png");
-        let image_data = image_data.to_vec();
-        
-        let mut clippit_animation = AnimationService::new(
-            cc.egui_ctx.clone(),
-            config_data.to_owned(),
-            image_data,
-            sndr.clone()
-        );
-        clippit_animation.start();
-        let mut ani : HashMap<String, AnimationService> = HashMap::new();
-        ani.insert(
-            "clippit".to_string(),
-            clippit_animation
-        );
+        let packs = list_packs(user_packs_dir().as_deref());
+        let max_texture_side = max_texture_side(cc);
+        let mut pack_error = None;
+        let mut agents = Vec::new();
+        let mut ani : HashMap<AgentId, AnimationService> = HashMap::new();
 
-        let mut ass_service = AssistantService::new(
-            config.open_ai_api_key.to_owned(),
-            config.assistant_id.to_owned(),
-            sndr.clone()
-        );
+        for agent in &agent_configs {
+            let pack = agent.pack
+                .as_ref()
+                .and_then(|name| packs.iter().position(|p| p.metadata.name == *name))
+                .unwrap_or(0);
+            let (pack, animation) = match start_animation(&cc.egui_ctx, &sndr, &agent.id, &packs[pack], settings.audio, &settings.playback, max_texture_side) {
+                Ok(animation) => (pack, animation),
+                Err(e) => {
+                    pack_error = Some(e);
+                    let animation = start_animation(&cc.egui_ctx, &sndr, &agent.id, &CharacterPackInfo::builtin(), settings.audio, &settings.playback, max_texture_side)
+                        .expect("built in character pack is broken!");
+                    (0, animation)
+                }
+            };
+            animation.set_answers_questions(!agent.silent);
+            ani.insert(agent.id.to_owned(), animation);
+            agents.push(Agent { id: agent.id.to_owned(), pack, pending_click: None });
 
-        ass_service.start();
+            if !agent.silent {
+                start_assistant(&config, agent, &sndr);
+            }
+        }
 
         let ctx_rp = cc.egui_ctx.clone();
         let _shared = shared.clone();
@@ -110,7 +234,22 @@ impl ClippitGptApp {
         let app = ClippitGptApp{
             state: shared.clone(),
             animations: ani,
-            mpmc_channel: sndr.clone()
+            mpmc_channel: sndr.clone(),
+            packs,
+            agents,
+            ask_to: None,
+            audio: settings.audio,
+            playback: settings.playback,
+            reveals: Vec::new(),
+            stay_put: settings.stay_put,
+            movement: None,
+            walking: None,
+            wander_at: WANDER_AFTER_SECS,
+            pack_error,
+            editor: None,
+            max_texture_side,
+            quit_requested_at: None,
+            was_minimized: false
         };
 
         StateUpdater::new(
@@ -121,9 +260,310 @@ impl ClippitGptApp {
 
         app
     }
+
+    /// swap an agent's character for another installed pack, keeps the old one on failure
+    fn switch_character(&mut self, ctx: &egui::Context, agent: usize, index: usize) {
+        let id = self.agents[agent].id.to_owned();
+        let animation = match start_animation(ctx, &self.mpmc_channel, &id, &self.packs[index], self.audio, &self.playback, self.max_texture_side) {
+            Ok(animation) => animation,
+            Err(e) => {
+                self.pack_error = Some(e);
+                return;
+            }
+        };
+
+        animation.set_moving(self.walking);
+        animation.set_answers_questions(self.state.lock().unwrap().assistants.contains(&id));
+        if let Some(old) = self.animations.insert(id, animation) {
+            old.stop();
+        }
+        self.agents[agent].pack = index;
+        self.pack_error = None;
+    }
+
+    /// say goodbye, the window closes once the animation is done
+    fn quit(&mut self, now: f64) {
+        if self.quit_requested_at.is_none() {
+            self.quit_requested_at = Some(now);
+            let _ = self.mpmc_channel.send(DispatchActions::Lifecycle(None, LifecycleEvent::QuitRequested));
+        }
+    }
+
+    /// close once the goodbye is done, turn the os asking to close into a goodbye,
+    /// and show the character again when the window is restored
+    fn handle_lifecycle(&mut self, ctx: &egui::Context, quit_ready: bool) {
+        let (close_requested, minimized, now) = ctx.input(|i| {
+            (i.viewport().close_requested(), i.viewport().minimized.unwrap_or(false), i.time)
+        });
+
+        let timed_out = self.quit_requested_at.is_some_and(|t| now - t > QUIT_TIMEOUT_SECS);
+        if quit_ready || timed_out {
+            ctx.send_viewport_cmd(ViewportCommand::Close);
+        } else if close_requested {
+            ctx.send_viewport_cmd(ViewportCommand::CancelClose);
+            self.quit(now);
+        }
+        if self.quit_requested_at.is_some() {
+            ctx.request_repaint_after(Duration::from_secs_f64(QUIT_TIMEOUT_SECS));
+        }
+
+        if self.was_minimized && !minimized {
+            let _ = self.mpmc_channel.send(DispatchActions::Lifecycle(None, LifecycleEvent::Shown));
+        }
+        self.was_minimized = minimized;
+    }
+
+    /// look for new character packs, keeping the ones in use selected
+    fn refresh_packs(&mut self) {
+        let old = std::mem::replace(&mut self.packs, list_packs(user_packs_dir().as_deref()));
+        for agent in &mut self.agents {
+            agent.pack = self.packs
+                .iter()
+                .position(|p| p.source == old[agent.pack].source)
+                .unwrap_or(0);
+        }
+    }
+
+    /// type answers out a few characters a frame, returns how many characters of each to show.
+    /// Agents are told when their answer is all there so they can stop talking
+    fn reveal_answers(&mut self, ctx: &egui::Context, answers: &[(AgentId, QuestionResponse)]) -> Vec<usize> {
+        let now = ctx.input(|i| i.time);
+        // a new conversation starts the list over
+        self.reveals.truncate(answers.len());
+        while self.reveals.len() < answers.len() {
+            self.reveals.push(Reveal { started_at: now, done: false });
+        }
+
+        let chars_per_sec = REVEAL_CHARS_PER_SEC * self.playback.speed as f64;
+        let revealed = answers
+            .iter()
+            .zip(&mut self.reveals)
+            .map(|((agent, qr), reveal)| {
+                let total = qr.answer.chars().count();
+                // typing is motion too
+                let shown = if self.playback.reduced_motion {
+                    total
+                } else {
+                    ((now - reveal.started_at) * chars_per_sec) as usize
+                };
+                if shown >= total && !reveal.done {
+                    reveal.done = true;
+                    let _ = self.mpmc_channel.send(DispatchActions::AnswerRevealed(agent.to_owned()));
+                }
+                shown.min(total)
+            })
+            .collect();
+        if self.reveals.iter().any(|r| !r.done) {
+            ctx.request_repaint();
+        }
+        revealed
+    }
+
+    /// walk the window to `to`, kept on screen. Reduced motion jumps straight there
+    fn move_window(&mut self, ctx: &egui::Context, to: Pos2) {
+        let (outer, screen, now) = ctx.input(|i| (i.viewport().outer_rect, i.viewport().monitor_size, i.time));
+        let to = match (outer, screen) {
+            (Some(outer), Some(screen)) => movement::clamp_to_screen(to, outer.size(), screen),
+            _ => to
+        };
+        match outer {
+            Some(outer) if !self.playback.reduced_motion => {
+                let speed = WALK_SPEED * self.playback.speed;
+                self.movement = Some(Movement::new(outer.min, vec![to], speed, now));
+            },
+            _ => {
+                self.movement = None;
+                ctx.send_viewport_cmd(ViewportCommand::OuterPosition(to));
+            }
+        }
+        ctx.request_repaint();
+    }
+
+    /// keep the window on its path and the agents walking the way it goes
+    fn update_movement(&mut self, ctx: &egui::Context) {
+        let (pressed, now) = ctx.input(|i| (i.pointer.any_pressed(), i.time));
+        // grabbing the window stops it
+        if pressed {
+            self.movement = None;
+        }
+        // reduced motion was turned on on the way
+        if self.playback.reduced_motion {
+            if let Some(movement) = self.movement.take() {
+                ctx.send_viewport_cmd(ViewportCommand::OuterPosition(movement.target()));
+            }
+        }
+
+        let mut direction = None;
+        if let Some(movement) = &self.movement {
+            ctx.send_viewport_cmd(ViewportCommand::OuterPosition(movement.position(now)));
+            direction = movement.direction(now);
+            if movement.is_done(now) {
+                self.movement = None;
+            } else {
+                ctx.request_repaint();
+            }
+        }
+        if direction != self.walking {
+            self.walking = direction;
+            for animation in self.animations.values() {
+                animation.set_moving(direction);
+            }
+        }
+    }
+
+    /// after a while without input an idle character takes a stroll, unless it should stay put
+    fn wander(&mut self, ctx: &egui::Context, mode: &AnimationServiceMode) {
+        let (input, minimized, outer, screen, now) = ctx.input(|i| {
+            let viewport = i.viewport();
+            (!i.events.is_empty(), viewport.minimized.unwrap_or(false), viewport.outer_rect, viewport.monitor_size, i.time)
+        });
+        if input || self.movement.is_some() {
+            self.wander_at = now + WANDER_AFTER_SECS;
+            return;
+        }
+        let still = self.stay_put || self.playback.reduced_motion || self.quit_requested_at.is_some() || minimized;
+        if still || *mode != AnimationServiceMode::Idle || now < self.wander_at {
+            ctx.request_repaint_after(Duration::from_secs_f64((self.wander_at - now).max(0.0)));
+            return;
+        }
+
+        self.wander_at = now + WANDER_AFTER_SECS;
+        if let (Some(outer), Some(screen)) = (outer, screen) {
+            let to = movement::wander_target(&mut rand::thread_rng(), outer.min, outer.size(), screen);
+            self.move_window(ctx, to);
+        }
+    }
+
+    /// open the animation editor on the main agent's character
+    fn open_editor(&mut self, ctx: &egui::Context) {
+        let pack = &self.packs[self.agents[0].pack];
+        let editor = pack
+            .load()
+            .and_then(|pack| AnimationEditor::new(ctx, &pack));
+        match editor {
+            Ok(editor) => self.editor = Some(editor),
+            Err(e) => self.pack_error = Some(format!("couldn't edit {}: {}", pack.metadata.name, e))
+        }
+    }
+
+    fn show_editor(&mut self, ctx: &egui::Context) {
+        let Some(editor) = &mut self.editor else { return };
+        if editor.show(ctx) {
+            // pick up the edits, saving a built in pack makes a new one
+            let dir = editor.pack_dir();
+            self.refresh_packs();
+            let index = self.packs
+                .iter()
+                .position(|p| dir.as_ref().is_some_and(|d| p.source == PackSource::Directory(d.to_owned())));
+            if let Some(index) = index {
+                self.switch_character(ctx, 0, index);
+            }
+        }
+        if !self.editor.as_ref().is_some_and(|e| e.is_open()) {
+            self.editor = None;
+        }
+    }
+}
+
+/// the biggest texture the renderer takes. egui reports a default of 2048 until the first frame
+/// has started, so ask the gl context directly
+fn max_texture_side(cc: &eframe::CreationContext<'_>) -> usize {
+    use eframe::glow::HasContext;
+    // safety: only reads a value, the context is current while the app is created
+    let from_gl = cc.gl.as_ref().map(|gl| unsafe { gl.get_parameter_i32(eframe::glow::MAX_TEXTURE_SIZE) });
+    match from_gl {
+        Some(side) if side > 0 => side as usize,
+        _ => cc.egui_ctx.input(|i| i.max_texture_side)
+    }
+}
+
+/// load a pack and start animating it for `agent`
+fn start_animation(ctx: &egui::Context, sndr: &Sender<DispatchActions>, agent: &str, info: &CharacterPackInfo, audio: AudioSettings, playback: &PlaybackSettings, max_texture_side: usize) -> Result<AnimationService, String> {
+    let pack = info.load().map_err(|e| format!("couldn't load {}: {}", info.metadata.name, e))?;
+    let mut animation = AnimationService::new(
+        ctx.clone(),
+        pack.animation_data,
+        pack.image_data,
+        sndr.clone(),
+        max_texture_side
+    ).map_err(|e| format!("couldn't load {}: {}", info.metadata.name, e))?;
+    animation.set_agent(agent.to_owned());
+    animation.set_audio_settings(audio);
+    animation.set_playback(playback.clone());
+    animation.start();
+    let _ = sndr.send(DispatchActions::Lifecycle(Some(agent.to_owned()), LifecycleEvent::Started));
+    Ok(animation)
+}
+
+/// answer questions for `agent` with its own assistant and persona
+fn start_assistant(config: &AppConfig, agent: &AgentConfig, sndr: &Sender<DispatchActions>) {
+    let mut ass_service = AssistantService::new(
+        config.open_ai_api_key.to_owned(),
+        agent.assistant_id.to_owned().unwrap_or_else(|| config.assistant_id.to_owned()),
+        sndr.clone()
+    );
+    ass_service.set_agent(agent.id.to_owned());
+    ass_service.set_persona(agent.persona.to_owned());
+    ass_service.start();
+}
+
+/// clicks, drags and the right click menu on an agent's character
+fn character_interactions(ui: &Ui, character: Response, sender: &Sender<DispatchActions>, agent: &mut Agent, packs: &[CharacterPackInfo]) {
+    let id = || agent.id.to_owned();
+    let now = ui.input(|i| i.time);
+    if character.double_clicked() {
+        agent.pending_click = None;
+        sender.send(DispatchActions::Interaction(id(), Interaction::DoubleClicked)).expect("couldn't open the chat!");
+    } else if character.clicked() {
+        agent.pending_click = Some(now);
+    }
+    // the first half of a double click isn't a click of its own
+    if let Some(clicked_at) = agent.pending_click {
+        let left = clicked_at + DOUBLE_CLICK_SECS - now;
+        if character.is_pointer_button_down_on() {
+            ui.ctx().request_repaint();
+        } else if left > 0.0 {
+            ui.ctx().request_repaint_after(Duration::from_secs_f64(left));
+        } else {
+            agent.pending_click = None;
+            sender.send(DispatchActions::Interaction(id(), Interaction::Clicked)).expect("couldn't click!");
+        }
+    }
+    if character.drag_started_by(egui::PointerButton::Primary) {
+        sender.send(DispatchActions::Interaction(id(), Interaction::Dragged)).expect("couldn't drag!");
+        ui.ctx().send_viewport_cmd(ViewportCommand::StartDrag);
+    }
+
+    character.context_menu(|ui|{
+        if ui.button("Hide").clicked() {
+            sender.send(DispatchActions::Lifecycle(None, LifecycleEvent::Hidden)).expect("couldn't hide!");
+            ui.close_menu();
+        }
+        ui.menu_button("Change character", |ui|{
+            for (i, pack) in packs.iter().enumerate() {
+                if ui.selectable_label(i == agent.pack, &pack.metadata.name).clicked() {
+                    sender.send(DispatchActions::SwitchCharacter(id(), i)).expect("couldn't switch character!");
+                    ui.close_menu();
+                }
+            }
+        });
+        if ui.button("New conversation").clicked() {
+            sender.send(DispatchActions::NewConversation).expect("couldn't start over!");
+            ui.close_menu();
+        }
+    });
 }
 
 impl eframe::App for ClippitGptApp {
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        let settings = Settings {
+            audio: self.audio,
+            playback: self.playback.clone(),
+            stay_put: self.stay_put
+        };
+        eframe::set_value(storage, SETTINGS_KEY, &settings);
+    }
+
     fn clear_color(&self, _visuals: &egui::Visuals) -> [f32; 4] {
         egui::Rgba::TRANSPARENT.to_array() // Make sure we don't paint anything behind the rounded corners
     }
@@ -134,13 +574,27 @@ impl eframe::App for ClippitGptApp {
         // For inspiration and more examples, go to https://emilk.github.io/egui
         let mut state;
         {
-            let state_ = self.state.lock().unwrap();
+            let mut state_ = self.state.lock().unwrap();
             state = state_.clone();
+            // one shot requests, handled this frame
+            state_.focus_question_field = false;
+            state_.switch_to_pack = None;
+            state_.move_to = None;
             drop(state_);
         }
         
+        let revealed = self.reveal_answers(ctx, &state.answers);
         //let mut state = self.state.lock().unwrap();
         let sender = &self.mpmc_channel;
+        let mut selected_pack = self.agents[0].pack;
+        let mut ask_to = self.ask_to.clone();
+        let mut audio = self.audio;
+        let mut playback = self.playback.clone();
+        let mut stay_put = self.stay_put;
+        let mut refresh_packs = false;
+        let mut open_editor = false;
+        let mut quit = false;
+        let mut sprite_rects: Vec<(AgentId, Rect)> = Vec::new();
         
         let panel_frame = egui::Frame {
             fill: Color32::from_rgba_premultiplied(0, 0, 0, 180),
@@ -152,34 +606,102 @@ impl eframe::App for ClippitGptApp {
         };
 
         egui::CentralPanel::default().frame(panel_frame).show(ctx, |ui| {
-            if ui.interact(ui.max_rect(), Id::new("window-drag"), Sense::click()).is_pointer_button_down_on() {
-                ui.ctx().send_viewport_cmd(ViewportCommand::StartDrag);
-            }
+            let window_drag = ui.interact(ui.max_rect(), Id::new("window-drag"), Sense::click());
             
-            let clippit_animation = self.animations.get("clippit").unwrap();
+            // the agents stand side by side
+            let sprite_sizes: Vec<Vec2> = self.agents.iter().map(|a| self.animations[&a.id].frame_size()).collect();
+            let spacing = ui.spacing().item_spacing.x * (sprite_sizes.len() - 1) as f32;
+            let sprite_size = vec2(
+                sprite_sizes.iter().map(|s| s.x).sum::<f32>() + spacing,
+                sprite_sizes.iter().map(|s| s.y).fold(0.0, f32::max)
+            );
             ui.horizontal(|ui|{
                 StripBuilder::new(ui)
                     .size(Size::remainder())
-                    .size(Size::exact(100.0))
+                    .size(Size::exact(sprite_size.x))
                     .horizontal(|mut strip|{
                         strip.cell(|ui|{
                             ui.vertical(|ui|{
-                                ui.label(state.current_animation.to_owned());
+                                ui.label(state.current_animation.get(&self.agents[0].id).cloned().unwrap_or_default());
                                 ui.label(
                                     if state.mode == AnimationServiceMode::Idle {
                                         "Clippy Idle".to_owned() 
                                     } else {
                                         "Clippy Active".to_owned() 
                                     });
+                                ui.horizontal(|ui|{
+                                    egui::ComboBox::from_id_source("character-picker")
+                                        .selected_text(self.packs[self.agents[0].pack].metadata.name.to_owned())
+                                        .show_ui(ui, |ui|{
+                                            for (i, pack) in self.packs.iter().enumerate() {
+                                                ui.selectable_value(&mut selected_pack, i, &pack.metadata.name)
+                                                    .on_hover_text(format!("{}\nby {}", pack.metadata.description, pack.metadata.author));
+                                            }
+                                        });
+                                    if ui.small_button("⟳").on_hover_text("look for new character packs").clicked() {
+                                        refresh_packs = true;
+                                    }
+                                    if ui.small_button("🎞").on_hover_text("browse and edit the animations").clicked() {
+                                        open_editor = true;
+                                    }
+                                    ui.menu_button("☰", |ui|{
+                                        if ui.button("Collapse").clicked() {
+                                            sender.send(DispatchActions::Lifecycle(None, LifecycleEvent::Hidden)).expect("couldn't hide!");
+                                            ui.close_menu();
+                                        }
+                                        if ui.button("Quit").clicked() {
+                                            quit = true;
+                                            ui.close_menu();
+                                        }
+                                        ui.separator();
+                                        ui.checkbox(&mut audio.muted, "Mute");
+                                        ui.add_enabled(!audio.muted, egui::Slider::new(&mut audio.volume, 0.0..=1.0).text("Volume"));
+                                        ui.separator();
+                                        ui.add(egui::Slider::new(&mut playback.speed, MIN_SPEED..=MAX_SPEED).logarithmic(true).text("Speed"));
+                                        ui.menu_button("Animation speeds", |ui|{
+                                            let current = state.current_animation.get(&self.agents[0].id);
+                                            if let Some(current) = current.filter(|name| !playback.animation_speeds.contains_key(*name)) {
+                                                if ui.button(format!("Set a speed for {}", current)).clicked() {
+                                                    playback.animation_speeds.insert(current.to_owned(), playback.speed);
+                                                }
+                                            }
+                                            let mut removed = None;
+                                            for (name, speed) in playback.animation_speeds.iter_mut() {
+                                                ui.horizontal(|ui|{
+                                                    ui.add(egui::Slider::new(speed, MIN_SPEED..=MAX_SPEED).logarithmic(true).text(name));
+                                                    if ui.small_button("✖").on_hover_text("back to the overall speed").clicked() {
+                                                        removed = Some(name.to_owned());
+                                                    }
+                                                });
+                                            }
+                                            if let Some(name) = removed {
+                                                playback.animation_speeds.remove(&name);
+                                            }
+                                        });
+                                        ui.checkbox(&mut playback.reduced_motion, "Reduced motion")
+                                            .on_hover_text("only the rest pose and still key poses, no sparkles or glow");
+                                        ui.checkbox(&mut stay_put, "Stay put")
+                                            .on_hover_text("don't wander around the screen when idle");
+                                    });
+                                });
+                                if let Some(err) = &self.pack_error {
+                                    ui.colored_label(Color32::RED, err);
+                                }
                             });
                             
                         });
                         strip.strip(|builder|{
                             builder
-                                .size(Size::exact(100.0))
+                                .size(Size::exact(sprite_size.y))
                                 .vertical(|mut strip|{
                                     strip.cell(|ui|{
-                                        clippit_animation.render_animation(ui);
+                                        ui.horizontal(|ui|{
+                                            for agent in &mut self.agents {
+                                                let character = self.animations[&agent.id].render_animation(ui);
+                                                sprite_rects.push((agent.id.to_owned(), character.rect));
+                                                character_interactions(ui, character, sender, agent, &self.packs);
+                                            }
+                                        });
                                     })
                                 });
                         });
@@ -189,33 +711,53 @@ impl eframe::App for ClippitGptApp {
                       
             ui.label("Ask ClippitGPT Something:");
             ui.horizontal(|ui| {
+                if self.agents.len() > 1 {
+                    egui::ComboBox::from_id_source("ask-to")
+                        .selected_text(ask_to.as_deref().unwrap_or("everyone"))
+                        .show_ui(ui, |ui|{
+                            ui.selectable_value(&mut ask_to, None, "everyone");
+                            for agent in &state.assistants {
+                                ui.selectable_value(&mut ask_to, Some(agent.to_owned()), agent);
+                            }
+                        });
+                }
                 ui.add_enabled(
-                    if state.mode == AnimationServiceMode::Idle {true} else {false}, 
+                    state.mode == AnimationServiceMode::Idle, 
                     |ui: &mut Ui| {
                         let txt = ui.text_edit_singleline(&mut state.question_field  );
+                        if state.focus_question_field {
+                            txt.request_focus();
+                        }
                         txt.ctx.input(|i|{
                             if i.key_pressed(egui::Key::Enter) {
-                                sender.send(DispatchActions::AskQuestion(state.question_field.to_owned())).expect("couldn't ask question!");
+                                sender.send(DispatchActions::AskQuestion(ask_to.clone(), state.question_field.to_owned())).expect("couldn't ask question!");
                             }
                         });
                         if txt.changed(){
                             sender.send(DispatchActions::QuestionTextChanged(state.question_field.clone())).expect("couldn't update text");
+                            for (agent, sprite_rect) in &sprite_rects {
+                                self.animations[agent].look_toward(txt.rect.center(), *sprite_rect);
+                            }
                         }
                         txt
                     }
                 );
                 ui.add_enabled(
-                    if state.mode == AnimationServiceMode::Idle {true} else {false}, 
+                    state.mode == AnimationServiceMode::Idle, 
                     |ui: &mut Ui| {
                         let btn = ui.button("Ask!");
                         if btn.clicked(){
-                            sender.send(DispatchActions::AskQuestion(state.question_field.to_owned())).expect("couldn't ask question!");
+                            sender.send(DispatchActions::AskQuestion(ask_to.clone(), state.question_field.to_owned())).expect("couldn't ask question!");
                         };
                         btn
                     }
                 );
             });
 
+            if let Some(error) = &state.assistant_error {
+                ui.colored_label(Color32::LIGHT_RED, "couldn't reach the assistant, trying again").on_hover_text(error);
+            }
+
             ui.add_space(20.0);
             ui.label("Conversation History:");
             ui.add(Separator::default());
@@ -223,9 +765,14 @@ impl eframe::App for ClippitGptApp {
             egui::ScrollArea::both()
             .hscroll(false)
             .show(ui, |ui|{
-                for qr in state.answers.iter().rev(){
+                for ((agent, qr), shown) in state.answers.iter().zip(&revealed).rev(){
                     ui.label(&qr.question);
-                    ui.colored_label(Color32::RED, &qr.answer);
+                    let answer: String = qr.answer.chars().take(*shown).collect();
+                    if self.agents.len() > 1 {
+                        ui.colored_label(Color32::RED, format!("{}: {}", agent, answer));
+                    } else {
+                        ui.colored_label(Color32::RED, answer);
+                    }
                     ui.add(Separator::default());
                     ui.add_space(10.0);
                 }
@@ -235,8 +782,64 @@ impl eframe::App for ClippitGptApp {
             ui.with_layout(Layout::left_to_right(egui::Align::Max), |ui| {
                 egui::warn_if_debug_build(ui);
             }); 
+
+            // the character handles its own clicks and drags
+            let on_character = window_drag.interact_pointer_pos().is_some_and(|p| sprite_rects.iter().any(|(_, r)| r.contains(p)));
+            if window_drag.is_pointer_button_down_on() && !on_character {
+                ui.ctx().send_viewport_cmd(ViewportCommand::StartDrag);
+            }
             
         });
+
+        // the pointer is only known while it's over the window, i.e. near the character
+        let pointer = ctx.input(|i| i.pointer.hover_pos().filter(|_| i.pointer.is_moving()));
+        if let Some(pointer) = pointer {
+            for (agent, sprite_rect) in &sprite_rects {
+                self.animations[agent].look_toward(pointer, *sprite_rect);
+            }
+        }
+        self.ask_to = ask_to;
+        if playback != self.playback {
+            self.playback = playback;
+            for animation in self.animations.values() {
+                animation.set_playback(self.playback.clone());
+            }
+        }
+        self.stay_put = stay_put;
+        if audio != self.audio {
+            self.audio = audio;
+            for animation in self.animations.values() {
+                animation.set_audio_settings(audio);
+            }
+        }
+
+        if refresh_packs {
+            self.refresh_packs();
+        }
+
+        if selected_pack != self.agents[0].pack {
+            self.switch_character(ctx, 0, selected_pack);
+        }
+        if let Some((agent, index)) = state.switch_to_pack {
+            if let Some(agent) = self.agents.iter().position(|a| a.id == agent) {
+                self.switch_character(ctx, agent, index);
+            }
+        }
+
+        if open_editor {
+            self.open_editor(ctx);
+        }
+        self.show_editor(ctx);
+
+        if quit {
+            self.quit(ctx.input(|i| i.time));
+        }
+        if let Some(position) = state.move_to {
+            self.move_window(ctx, position);
+        }
+        self.wander(ctx, &state.mode);
+        self.update_movement(ctx);
+        self.handle_lifecycle(ctx, state.quit_ready);
         
     }
 }
@@ -2,16 +2,52 @@
 
 use std::{time::Duration};
 
-use openai_dive::v1::{api::Client, resources::{assistant::{message::{CreateMessageParameters, MessageContent, MessageRole}, run::{CreateRunParameters, RunStatus}, thread::CreateThreadParameters}, shared::ListParameters}};
+use openai_dive::v1::{api::Client, resources::{assistant::{message::{CreateMessageParameters, MessageContent, MessageRole}, run::{CreateRunParameters, RunStatus}, thread::{CreateThreadParameters, Thread}}, shared::ListParameters}};
 use tokio::sync::broadcast::Sender;
 
-use crate::{actions::DispatchActions, models::QuestionResponse};
+use crate::{actions::{AgentId, DispatchActions}, animation::service::DEFAULT_AGENT, models::QuestionResponse};
+
+/// log a failed request and let the agent know, the caller keeps trying.
+/// Only the first failure is reported, `reported` stays set until the caller clears it on success
+fn report_error(sender: &Sender<DispatchActions>, agent: &str, error: impl std::fmt::Debug, reported: &mut bool) {
+    if *reported {
+        log::debug!("assistant for {} is still failing: {:?}", agent, error);
+        return;
+    }
+    *reported = true;
+    log::warn!("assistant for {} failed: {:?}", agent, error);
+    let _ = sender.send(DispatchActions::AssistantError(agent.to_owned(), format!("{:?}", error)));
+}
+
+/// a new conversation, keeps trying on network issue
+async fn create_thread(client: &Client, sender: &Sender<DispatchActions>, agent: &str) -> Thread {
+    let mut reported = false;
+    loop {
+        let ret = client
+            .assistants()
+            .threads()
+            .create(
+                CreateThreadParameters{
+                    messages: None, 
+                    metadata:None 
+                }
+            ).await;
+        
+        if let Ok(ret) = ret{
+            return ret;
+        }
+        report_error(sender, agent, ret.unwrap_err(), &mut reported);
+        tokio::time::sleep(Duration::from_secs(3)).await;
+    }
+}
 
 pub struct AssistantService {
     client: Client,
     sender: Sender<DispatchActions>,
     running: bool,
-    assistant_id: String
+    assistant_id: String,
+    agent: AgentId,
+    persona: Option<String>
 }
 
 impl AssistantService {
@@ -24,14 +60,24 @@ impl AssistantService {
 
         let client = Client::new(api_key.to_string());
 
-        let assistant_service = AssistantService{
+        AssistantService{
             client: client.to_owned(),
             sender: sndr.to_owned(),
             running: false,
-            assistant_id: assistant_id.to_owned()
-        };
+            assistant_id: assistant_id.to_owned(),
+            agent: DEFAULT_AGENT.to_owned(),
+            persona: None
+        }
+    }
 
-        assistant_service
+    /// the agent this answers for, questions for other agents are ignored. Call before `start`
+    pub fn set_agent(&mut self, agent: AgentId) {
+        self.agent = agent;
+    }
+
+    /// instructions used instead of the assistant's own for every run. Call before `start`
+    pub fn set_persona(&mut self, persona: Option<String>) {
+        self.persona = persona;
     }
 
     pub fn start(&mut self){
@@ -41,53 +87,44 @@ impl AssistantService {
         let sender = self.sender.clone();
         let assistant_id = self.assistant_id.to_owned();
         let client = self.client.clone();
+        let agent = self.agent.clone();
+        let persona = self.persona.clone();
 
         tokio::spawn(async move {
             let mut receiver = sender.subscribe();
 
             let assistant;
             // keep trying on network issue
+            let mut reported = false;
             loop {
                 let ret = client.assistants().retrieve(&assistant_id).await;
                 if let Ok(ret) = ret{
                     assistant = ret;
                     break;
                 }
-                dbg!(ret.unwrap_err());
+                report_error(&sender, &agent, ret.unwrap_err(), &mut reported);
                 tokio::time::sleep(Duration::from_secs(3)).await;
             }
 
             // don't import as Threads, could cause confusion with Thread
-            let _thread;
-            loop {
-                let ret = client
-                    .assistants()
-                    .threads()
-                    .create(
-                        CreateThreadParameters{
-                            messages: None, 
-                            metadata:None 
-                        }
-                    ).await;
-                
-                if let Ok(ret) = ret{
-                    _thread = ret;
-                    break;
-                }
-                dbg!(ret.unwrap_err());
-                tokio::time::sleep(Duration::from_secs(3)).await;
-            }
+            let mut _thread = create_thread(&client, &sender, &agent).await;
 
             loop{
                 let action = receiver.recv().await;
                 if action.is_err(){ continue; }
                 let action = action.unwrap();
+                if !action.is_for(&agent) { continue; }
                 let question = match action {
-                    DispatchActions::AskQuestion(question) => question,
+                    DispatchActions::AskQuestion(_, question) => question,
+                    DispatchActions::NewConversation => {
+                        _thread = create_thread(&client, &sender, &agent).await;
+                        continue;
+                    },
                     _ => continue
                 };
 
-                let msg;
+                let _msg;
+                let mut reported = false;
                 loop {
                     let ret = client
                         .assistants()
@@ -103,14 +140,15 @@ impl AssistantService {
                         ).await;
                     
                     if let Ok(ret) = ret{
-                        msg = ret;
+                        _msg = ret;
                         break;
                     }
-                    dbg!(ret.unwrap_err());
+                    report_error(&sender, &agent, ret.unwrap_err(), &mut reported);
                     tokio::time::sleep(Duration::from_secs(3)).await;
                 }
 
                 let mut run;
+                let mut reported = false;
                 loop {
                     let ret = client
                         .assistants()
@@ -120,7 +158,7 @@ impl AssistantService {
                             CreateRunParameters { 
                                 assistant_id: assistant.id.to_owned(), 
                                 model: None, 
-                                instructions: None, 
+                                instructions: persona.to_owned(), 
                                 tools: None
                             }
                         ).await;
@@ -129,10 +167,11 @@ impl AssistantService {
                         run = ret;
                         break;
                     }
-                    dbg!(ret.unwrap_err());
+                    report_error(&sender, &agent, ret.unwrap_err(), &mut reported);
                     tokio::time::sleep(Duration::from_secs(3)).await;
                 }
 
+                let mut reported = false;
                 loop {
                     let ret = client
                         .assistants()
@@ -141,15 +180,17 @@ impl AssistantService {
                         .await;
                     
                     if let Ok(ret) = ret{
+                        reported = false;
                         run = ret.clone();
                         if run.status == RunStatus::Completed {break}
                     } else {
-                        dbg!(ret.unwrap_err());
+                        report_error(&sender, &agent, ret.unwrap_err(), &mut reported);
                     }
                     tokio::time::sleep(Duration::from_secs(1)).await;
                 }
 
                 let msgs;
+                let mut reported = false;
                 loop {
                     let ret = 
                         client
@@ -169,20 +210,20 @@ impl AssistantService {
                         msgs = ret;
                         break;
                     } else {
-                        dbg!(ret.unwrap_err());
+                        report_error(&sender, &agent, ret.unwrap_err(), &mut reported);
                     }
                     tokio::time::sleep(Duration::from_secs(3)).await;
                 }
 
-                let message = msgs.data.get(0).expect("Something went wrong, message is none!");
-                let content = message.content.get(0).expect("message has no content");
+                let message = msgs.data.first().expect("Something went wrong, message is none!");
+                let content = message.content.first().expect("message has no content");
                 let text = match content {
                     MessageContent::ImageFile(_) => panic!("Not expecting an image file"),
                     MessageContent::Text(text) => &text.text.value
                 };
 
-                let _ = sender.send(DispatchActions::RespondToQuestion(QuestionResponse{
-                    question: question,
+                let _ = sender.send(DispatchActions::RespondToQuestion(agent.to_owned(), QuestionResponse{
+                    question,
                     answer: text.to_owned()
                 }));
             }
@@ -0,0 +1,270 @@
+#![warn(clippy::all, rust_2018_idioms)]
+
+//! Command line tools for working with character packs.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::ExitCode;
+
+use clippit_gpt::animation::atlas;
+use clippit_gpt::animation::error::AnimationError;
+use clippit_gpt::animation::import::acs;
+use clippit_gpt::animation::import::clippyjs;
+use clippit_gpt::animation::import::encode_png;
+use clippit_gpt::animation::import::remap;
+use clippit_gpt::animation::import::CategoryRules;
+use clippit_gpt::animation::models::AnimationConfig;
+use clippit_gpt::animation::pack::write_pack;
+use clippit_gpt::animation::preprocess;
+use clippit_gpt::animation::service::export_animation;
+use clippit_gpt::animation::service::load_image_as_color_image;
+use clippit_gpt::animation::service::render_animation_frames;
+use clippit_gpt::animation::service::ExportFormat;
+use clippit_gpt::animation::validate::has_errors;
+use clippit_gpt::animation::validate::parse_and_validate;
+
+const USAGE: &str = "usage: clippit_tools <command> [args]
+
+commands:
+    import-acs <character.acs> <out_dir>       convert a Microsoft Agent character into a pack
+    import-clippyjs <agent_dir> <out_dir>      convert a ClippyJS agent (agent.js + map.png) into a pack
+    remap <animations.json> <sheet.png> <out.yaml> <width>x<height> [categories.yaml]
+                                               convert animations.json to animation yaml, the sheet grid
+                                               comes from the png and frame size, categories.yaml holds
+                                               the category rules (default: Idle in the name means idle)
+    validate <animations.yaml> [sheet.png]     check animation yaml, against the sprite sheet if given
+    atlas <animations.yaml> <sheet.png> <out_dir> [max_width]
+                                               trim, deduplicate and pack the frames into a smaller
+                                               sprite sheet (default max width 2048)
+    export <animations.yaml> <sheet.png> <animation> <out.gif|out.png>
+                                               save an animation as a gif or animated png
+    export-frames <animations.yaml> <sheet.png> <animation> <out_dir>
+                                               save every visible frame of an animation as a png,
+                                               zero length frames (branch points) are skipped";
+
+fn import_acs(args: &[String]) -> Result<(), AnimationError> {
+    let [acs_path, out_dir] = args else {
+        return Err(AnimationError::Format(USAGE.to_owned()));
+    };
+    let character = acs::import(&fs::read(acs_path)?)?;
+    let dir = write_pack(Path::new(out_dir), &character, "")?;
+    println!("imported {} into {:?}", character.name, dir);
+    Ok(())
+}
+
+fn import_clippyjs(args: &[String]) -> Result<(), AnimationError> {
+    let [agent_dir, out_dir] = args else {
+        return Err(AnimationError::Format(USAGE.to_owned()));
+    };
+    let agent_dir = Path::new(agent_dir);
+    let character = clippyjs::import(
+        &fs::read_to_string(agent_dir.join("agent.js"))?,
+        &fs::read(agent_dir.join("map.png"))?,
+    )?;
+    let dir = write_pack(Path::new(out_dir), &character, "")?;
+    println!("imported {} into {:?}", character.name, dir);
+    Ok(())
+}
+
+fn parse_size(size: &str) -> Result<[u32; 2], AnimationError> {
+    let bad_size =
+        || AnimationError::Format(format!("expected a size like 124x93, got {:?}", size));
+    let (width, height) = size.split_once('x').ok_or_else(bad_size)?;
+    Ok([
+        width.parse().map_err(|_| bad_size())?,
+        height.parse().map_err(|_| bad_size())?,
+    ])
+}
+
+fn remap_json(args: &[String]) -> Result<(), AnimationError> {
+    let (json_path, sheet_path, out_path, frame_size, rules_path) = match args {
+        [json, sheet, out, size] => (json, sheet, out, size, None),
+        [json, sheet, out, size, rules] => (json, sheet, out, size, Some(rules)),
+        _ => return Err(AnimationError::Format(USAGE.to_owned())),
+    };
+
+    let rules: CategoryRules = match rules_path {
+        Some(path) => serde_yaml::from_str(&fs::read_to_string(path)?)?,
+        None => CategoryRules::default(),
+    };
+    let (width, height) = image::image_dimensions(sheet_path)?;
+
+    let config = remap::remap(
+        &fs::read_to_string(json_path)?,
+        [width, height],
+        parse_size(frame_size)?,
+        &rules,
+    )?;
+    fs::write(out_path, serde_yaml::to_string(&config)?)?;
+    println!(
+        "wrote {} idle and {} action animations to {}",
+        config.animations.idle.len(),
+        config.animations.action.len(),
+        out_path
+    );
+    Ok(())
+}
+
+fn validate(args: &[String]) -> Result<(), AnimationError> {
+    let (yaml_path, sheet_path) = match args {
+        [yaml] => (yaml, None),
+        [yaml, sheet] => (yaml, Some(sheet)),
+        _ => return Err(AnimationError::Format(USAGE.to_owned())),
+    };
+
+    let image = match sheet_path {
+        Some(path) => Some(load_image_as_color_image(&fs::read(path)?)?),
+        None => None,
+    };
+    let (_, diagnostics) = parse_and_validate(&fs::read_to_string(yaml_path)?, image.as_ref());
+
+    if has_errors(&diagnostics) {
+        return Err(AnimationError::Invalid(diagnostics));
+    }
+    for diagnostic in &diagnostics {
+        println!("{}", diagnostic);
+    }
+    println!("{} is valid", yaml_path);
+    Ok(())
+}
+
+fn pack_atlas(args: &[String]) -> Result<(), AnimationError> {
+    let (yaml_path, sheet_path, out_dir, max_width) = match args {
+        [yaml, sheet, out] => (yaml, sheet, out, atlas::DEFAULT_MAX_WIDTH),
+        [yaml, sheet, out, width] => (
+            yaml,
+            sheet,
+            out,
+            width.parse().map_err(|_| {
+                AnimationError::Format(format!("expected a width, got {:?}", width))
+            })?,
+        ),
+        _ => return Err(AnimationError::Format(USAGE.to_owned())),
+    };
+
+    let yaml = fs::read_to_string(yaml_path)?;
+    let sheet = image::load_from_memory(&fs::read(sheet_path)?)?.to_rgba8();
+    let config = serde_yaml::from_str(&yaml)?;
+    // a single page, packs only have one sprite sheet file
+    let packed = atlas::pack(&config, std::slice::from_ref(&sheet), [max_width, u32::MAX])?;
+    let image = &packed.pages[0];
+
+    let out_dir = Path::new(out_dir);
+    fs::create_dir_all(out_dir)?;
+    fs::write(
+        out_dir.join("animations.yaml"),
+        serde_yaml::to_string(&packed.config)?,
+    )?;
+    fs::write(out_dir.join("sprite_sheet.png"), encode_png(image)?)?;
+    println!(
+        "packed {}x{} into {}x{} in {:?}",
+        sheet.width(),
+        sheet.height(),
+        image.width(),
+        image.height(),
+        out_dir
+    );
+    Ok(())
+}
+
+fn load_animations(
+    yaml_path: &str,
+    sheet_path: &str,
+) -> Result<(AnimationConfig, image::RgbaImage), AnimationError> {
+    let sheet_data = fs::read(sheet_path)?;
+    // checked the way the app does, a bad upscale could take all the memory
+    let (config, diagnostics) = parse_and_validate(
+        &fs::read_to_string(yaml_path)?,
+        Some(&load_image_as_color_image(&sheet_data)?),
+    );
+    let Some(mut config) = config else {
+        return Err(AnimationError::Invalid(diagnostics));
+    };
+    let sheet = image::load_from_memory(&sheet_data)?.to_rgba8();
+    // export what the app shows
+    let sheet = preprocess::apply(&sheet, &config.preprocess);
+    preprocess::scale_config(&mut config);
+    Ok((config, sheet))
+}
+
+fn export(args: &[String]) -> Result<(), AnimationError> {
+    let [yaml_path, sheet_path, name, out_path] = args else {
+        return Err(AnimationError::Format(USAGE.to_owned()));
+    };
+    let format = match Path::new(out_path).extension().and_then(|e| e.to_str()) {
+        Some(e) if e.eq_ignore_ascii_case("gif") => ExportFormat::Gif,
+        Some(e) if e.eq_ignore_ascii_case("png") || e.eq_ignore_ascii_case("apng") => {
+            ExportFormat::Apng
+        }
+        _ => {
+            return Err(AnimationError::Format(format!(
+                "can't tell the format of {:?}, use .gif or .png",
+                out_path
+            )))
+        }
+    };
+
+    let (config, sheet) = load_animations(yaml_path, sheet_path)?;
+    fs::write(out_path, export_animation(&sheet, &config, name, format)?)?;
+    println!("exported {} to {}", name, out_path);
+    Ok(())
+}
+
+fn export_frames(args: &[String]) -> Result<(), AnimationError> {
+    let [yaml_path, sheet_path, name, out_dir] = args else {
+        return Err(AnimationError::Format(USAGE.to_owned()));
+    };
+
+    let (config, sheet) = load_animations(yaml_path, sheet_path)?;
+    let frames = render_animation_frames(&sheet, &config, name)?;
+    let out_dir = Path::new(out_dir);
+    fs::create_dir_all(out_dir)?;
+    for (i, (image, _)) in frames.iter().enumerate() {
+        fs::write(
+            out_dir.join(format!("{}_{:03}.png", name, i)),
+            encode_png(image)?,
+        )?;
+    }
+    let skipped = config
+        .find_animation(name)
+        .map_or(0, |a| a.frames.len() - frames.len());
+    println!(
+        "wrote {} frames of {} to {:?}, skipped {} zero length frames",
+        frames.len(),
+        name,
+        out_dir,
+        skipped
+    );
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let Some((command, args)) = args.split_first() else {
+        eprintln!("{}", USAGE);
+        return ExitCode::FAILURE;
+    };
+
+    let result = match command.as_str() {
+        "import-acs" => import_acs(args),
+        "import-clippyjs" => import_clippyjs(args),
+        "remap" => remap_json(args),
+        "validate" => validate(args),
+        "atlas" => pack_atlas(args),
+        "export" => export(args),
+        "export-frames" => export_frames(args),
+        _ => {
+            eprintln!("{}", USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
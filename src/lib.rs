@@ -6,6 +6,7 @@ pub mod actions;
 pub mod gui_state;
 pub mod state_updater;
 pub mod assistant;
+pub mod movement;
 
 mod app;
 pub use app::ClippitGptApp;
\ No newline at end of file
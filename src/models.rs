@@ -1,3 +1,5 @@
+use crate::animation::service::DEFAULT_AGENT;
+
 #[derive(Clone, Debug)]
 pub struct QuestionResponse {
     pub question: String,
@@ -5,8 +7,38 @@ pub struct QuestionResponse {
 }
 
 
+/// a character on screen with its own pack and, optionally, its own assistant
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, Default)]
+pub struct AgentConfig{
+    pub id: String,
+    /// name of the character pack, the built in character when it's left out or not installed
+    #[serde(default)]
+    pub pack: Option<String>,
+    /// assistant answering for this agent, the app wide `assistant_id` when left out
+    #[serde(default)]
+    pub assistant_id: Option<String>,
+    /// instructions replacing the assistant's own, e.g. which side of a debate to argue
+    #[serde(default)]
+    pub persona: Option<String>,
+    /// doesn't answer questions, for agents driven by something else on the bus like a build watcher
+    #[serde(default)]
+    pub silent: bool
+}
+
 #[derive(serde::Deserialize, serde::Serialize, Clone, Debug, Default)]
 pub struct AppConfig{
     pub open_ai_api_key: String,
-    pub assistant_id: String
+    pub assistant_id: String,
+    /// the first agent is the main one, a lone clippit when empty
+    #[serde(default)]
+    pub agents: Vec<AgentConfig>
+}
+
+impl AppConfig {
+    pub fn agents(&self) -> Vec<AgentConfig> {
+        if !self.agents.is_empty() {
+            return self.agents.clone();
+        }
+        vec![AgentConfig { id: DEFAULT_AGENT.to_owned(), ..Default::default() }]
+    }
 }
\ No newline at end of file
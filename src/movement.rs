@@ -0,0 +1,95 @@
+//! Moving the window around the desktop like the classic agents did. A `Movement` is a path
+//! the window's top left corner follows at a steady speed, the app sends its position with
+//! `ViewportCommand::OuterPosition` every frame and plays the walk animation for its direction.
+//! Positions are in points on the monitor the window is on, with the origin at its top left.
+
+use egui::pos2;
+use egui::Pos2;
+use egui::Vec2;
+use rand::Rng;
+
+use crate::animation::models::MoveDirection;
+
+/// how fast the window walks at playback speed 1, in points a second
+pub const WALK_SPEED: f32 = 150.0;
+
+/// how long the character stands still without any input before it wanders off
+pub const WANDER_AFTER_SECS: f64 = 60.0;
+
+/// furthest the character wanders in one go, sideways. It goes up and down a quarter of that
+const WANDER_DISTANCE: f32 = 300.0;
+
+pub struct Movement {
+    /// where it started, then every point it passes through
+    waypoints: Vec<Pos2>,
+    /// points a second
+    speed: f32,
+    /// `ctx.input().time` it set off
+    started_at: f64,
+}
+
+impl Movement {
+    /// go from `from` through each point of `path` in turn, setting off at `now`
+    pub fn new(from: Pos2, path: Vec<Pos2>, speed: f32, now: f64) -> Self {
+        let mut waypoints = vec![from];
+        waypoints.extend(path);
+        Movement {
+            waypoints,
+            speed: speed.max(f32::EPSILON),
+            started_at: now,
+        }
+    }
+
+    /// where the path ends
+    pub fn target(&self) -> Pos2 {
+        *self.waypoints.last().unwrap()
+    }
+
+    /// the segment being walked at `now` and the position on it, `None` once it's over
+    fn locate(&self, now: f64) -> (Option<usize>, Pos2) {
+        let mut left = (now - self.started_at).max(0.0) as f32 * self.speed;
+        for (i, segment) in self.waypoints.windows(2).enumerate() {
+            let length = segment[0].distance(segment[1]);
+            if left < length {
+                return (
+                    Some(i),
+                    segment[0] + (segment[1] - segment[0]) * (left / length),
+                );
+            }
+            left -= length;
+        }
+        (None, self.target())
+    }
+
+    /// where the window should be at `now`
+    pub fn position(&self, now: f64) -> Pos2 {
+        self.locate(now).1
+    }
+
+    /// which way it's heading at `now`, `None` once it has arrived
+    pub fn direction(&self, now: f64) -> Option<MoveDirection> {
+        let (segment, _) = self.locate(now);
+        let offset = segment.map(|i| self.waypoints[i + 1] - self.waypoints[i])?;
+        MoveDirection::from_offset(offset.x, offset.y)
+    }
+
+    pub fn is_done(&self, now: f64) -> bool {
+        self.locate(now).0.is_none()
+    }
+}
+
+/// move `position` so a window of `size` is all on a screen of `screen`.
+/// Windows bigger than the screen stick to its top left
+pub fn clamp_to_screen(position: Pos2, size: Vec2, screen: Vec2) -> Pos2 {
+    let max = (screen - size).max(Vec2::ZERO);
+    pos2(position.x.clamp(0.0, max.x), position.y.clamp(0.0, max.y))
+}
+
+/// somewhere near `from` to wander off to, still on the screen
+pub fn wander_target(rng: &mut impl Rng, from: Pos2, size: Vec2, screen: Vec2) -> Pos2 {
+    let offset = Vec2::new(
+        rng.gen_range(-WANDER_DISTANCE..=WANDER_DISTANCE),
+        rng.gen_range(-WANDER_DISTANCE..=WANDER_DISTANCE) / 4.0,
+    );
+    clamp_to_screen(from + offset, size, screen)
+}
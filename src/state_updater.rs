@@ -1,13 +1,17 @@
 use std::sync::Arc;
 use std::sync::Mutex;
 
+use egui::ViewportCommand;
 use tokio::sync::broadcast::Sender;
 use tokio::sync::broadcast::Receiver;
+use tokio::sync::broadcast::error::RecvError;
 
 
 use crate::animation::models::AnimationServiceMode;
 use crate::app::ClippitGptAppShared;
 use crate::{actions::DispatchActions};
+use crate::actions::Interaction;
+use crate::actions::LifecycleEvent;
 
 pub struct StateUpdater{
     app_state: Arc<Mutex<ClippitGptAppShared>>,
@@ -38,27 +42,88 @@ impl StateUpdater{
 
         tokio::spawn(async move {
             loop {
-                let v = receiver.recv().await.unwrap();
+                let v = match receiver.recv().await {
+                    Ok(v) => v,
+                    Err(RecvError::Lagged(missed)) => {
+                        log::warn!("state updater missed {} actions", missed);
+                        continue;
+                    },
+                    Err(RecvError::Closed) => break
+                };
                 let mut state = app_state.lock().unwrap();
                 
                 match v {
                     DispatchActions::NewFrameToRender => ctx.request_repaint() , 
-                    DispatchActions::AskQuestion(_question) => {
+                    DispatchActions::AskQuestion(to, _question) => {
                         state.question_field = "".to_owned();
-                        state.mode = AnimationServiceMode::Active;
+                        state.waiting_for = state.assistants
+                            .iter()
+                            .filter(|a| to.as_ref().map_or(true, |to| to == *a))
+                            .cloned()
+                            .collect();
+                        if !state.waiting_for.is_empty() {
+                            state.mode = AnimationServiceMode::Active;
+                        }
                         ctx.request_repaint();
                     },
-                    DispatchActions::RespondToQuestion(answer) => {
-                        state.mode = AnimationServiceMode::Idle;
-                        state.answers.push(answer);
+                    DispatchActions::RespondToQuestion(agent, answer) => {
+                        state.waiting_for.remove(&agent);
+                        if state.waiting_for.is_empty() {
+                            state.mode = AnimationServiceMode::Idle;
+                        }
+                        state.answers.push((agent, answer));
+                        state.assistant_error = None;
                         ctx.request_repaint();
                     },
                     DispatchActions::QuestionTextChanged(txt) => {
                         state.question_field = txt;
                         ctx.request_repaint();
                     }
-                    DispatchActions::NewAnimationStarted(ani) => {
-                        state.current_animation = ani;
+                    DispatchActions::NewAnimationStarted(agent, ani) => {
+                        state.current_animation.insert(agent, ani);
+                        ctx.request_repaint();
+                    }
+                    DispatchActions::Lifecycle(..) => ctx.request_repaint(),
+                    DispatchActions::LifecycleAnimationDone(agent, event) => {
+                        // the window waits for every agent
+                        match event {
+                            LifecycleEvent::QuitRequested => {
+                                state.said_goodbye.insert(agent);
+                                state.quit_ready = state.agents.iter().all(|a| state.said_goodbye.contains(a));
+                            },
+                            LifecycleEvent::Hidden => {
+                                state.hidden.insert(agent);
+                                if state.agents.iter().all(|a| state.hidden.contains(a)) {
+                                    state.hidden.clear();
+                                    ctx.send_viewport_cmd(ViewportCommand::Minimized(true));
+                                }
+                            },
+                            _ => ()
+                        }
+                        ctx.request_repaint();
+                    }
+                    DispatchActions::Interaction(_agent, interaction) => {
+                        if interaction == Interaction::DoubleClicked {
+                            state.focus_question_field = true;
+                        }
+                        ctx.request_repaint();
+                    }
+                    DispatchActions::SwitchCharacter(agent, index) => {
+                        state.switch_to_pack = Some((agent, index));
+                        ctx.request_repaint();
+                    }
+                    DispatchActions::AnimationEvent(..) | DispatchActions::AnswerRevealed(_) => (),
+                    DispatchActions::MoveTo(position) => {
+                        state.move_to = Some(position);
+                        ctx.request_repaint();
+                    }
+                    DispatchActions::AssistantError(agent, error) => {
+                        state.assistant_error = Some(format!("{}: {}", agent, error));
+                        ctx.request_repaint();
+                    }
+                    DispatchActions::NewConversation => {
+                        state.answers.clear();
+                        state.question_field = "".to_owned();
                         ctx.request_repaint();
                     }
                 }
@@ -0,0 +1,178 @@
+use clippit_gpt::animation::import::acs;
+use proptest::prelude::*;
+
+// generated by tests/fixtures/make_acs_fixture.py
+const TINY_ACS: &[u8] = include_bytes!("fixtures/tiny.acs");
+
+const RED: [u8; 4] = [255, 0, 0, 255];
+const BLUE: [u8; 4] = [0, 0, 255, 255];
+
+#[test]
+fn parses_character_info() {
+    let file = acs::parse(TINY_ACS).unwrap();
+    assert_eq!(file.character.name, "Tiny");
+    assert_eq!(file.character.description, "test character");
+    assert_eq!((file.character.width, file.character.height), (4, 3));
+    assert_eq!(file.character.transparent_index, 0);
+    assert_eq!(
+        file.character.palette,
+        vec![[255, 0, 255], [255, 0, 0], [0, 0, 255]]
+    );
+}
+
+#[test]
+fn parses_animations_and_branches() {
+    let file = acs::parse(TINY_ACS).unwrap();
+    let names: Vec<_> = file.animations.iter().map(|a| a.name.as_str()).collect();
+    assert_eq!(names, vec!["IdleBlink", "Wave"]);
+
+    let blink = &file.animations[0];
+    assert_eq!(blink.frames.len(), 2);
+    assert_eq!(blink.frames[0].duration, 10);
+    assert_eq!(blink.frames[0].branches, vec![(0, 50)]);
+    assert_eq!(blink.frames[0].exit_branch, None);
+    assert_eq!(blink.frames[0].audio_index, None);
+    assert_eq!(blink.frames[1].exit_branch, Some(0));
+    assert_eq!(blink.frames[1].images.len(), 2);
+    assert_eq!(file.animations[1].frames[0].audio_index, Some(0));
+}
+
+#[test]
+fn extracts_sounds_as_wave_files() {
+    let file = acs::parse(TINY_ACS).unwrap();
+    assert_eq!(file.sounds.len(), 1);
+    assert_eq!(&file.sounds[0][..4], b"RIFF");
+    assert_eq!(&file.sounds[0][8..12], b"WAVE");
+
+    let character = acs::import(TINY_ACS).unwrap();
+    assert_eq!(
+        character.config.animations.action[0].frames[0]
+            .sound
+            .as_deref(),
+        Some("0")
+    );
+    assert_eq!(
+        character.sounds,
+        vec![("0".to_owned(), file.sounds[0].clone())]
+    );
+}
+
+#[test]
+fn decodes_raw_and_compressed_images() {
+    let file = acs::parse(TINY_ACS).unwrap();
+
+    let raw = &file.images[0];
+    assert_eq!((raw.width, raw.height), (2, 2));
+    assert_eq!(raw.pixels, vec![1, 0, 1, 1]);
+
+    // stored bottom up, so the red row ends up at the bottom
+    let compressed = &file.images[1];
+    assert_eq!((compressed.width, compressed.height), (4, 3));
+    assert_eq!(compressed.pixels, vec![2, 2, 2, 2, 2, 2, 2, 2, 1, 1, 1, 1]);
+}
+
+#[test]
+fn builds_sprite_sheet_and_config() {
+    let character = acs::import(TINY_ACS).unwrap();
+    assert_eq!(character.name, "Tiny");
+
+    let config = &character.config;
+    assert_eq!(config.animations.idle.len(), 1);
+    assert_eq!(config.animations.idle[0].name, "Blink");
+    assert_eq!(config.animations.action[0].name, "Wave");
+
+    let blink = &config.animations.idle[0];
+    assert_eq!(blink.frames[0].duration, 100);
+    assert_eq!(blink.frames[0].branching[0].frame_index, 0);
+    assert_eq!(blink.frames[0].branching[0].weight, 50);
+    assert_eq!(blink.frames[1].exit_branch, Some(0));
+
+    // "Wave" uses the same image as the first blink frame so they share a cell
+    let wave = &config.animations.action[0];
+    assert_eq!(wave.frames[0].info.column, blink.frames[0].info.column);
+    assert_eq!(wave.frames[0].info.row, blink.frames[0].info.row);
+
+    let sheet = image::load_from_memory(&character.image_data)
+        .unwrap()
+        .to_rgba8();
+    let columns = config.sprite_sheet_info.columns as u32;
+    assert_eq!(sheet.width(), columns * 4);
+    assert_eq!(sheet.height(), config.sprite_sheet_info.rows as u32 * 3);
+
+    // second blink frame, the red square sits on top of the blue image
+    let cell = &blink.frames[1].info;
+    let pixel = |x: u32, y: u32| {
+        sheet
+            .get_pixel(cell.column as u32 * 4 + x, cell.row as u32 * 3 + y)
+            .0
+    };
+    assert_eq!(pixel(0, 0), BLUE);
+    assert_eq!(pixel(1, 1), RED);
+    assert_eq!(pixel(2, 2), RED);
+    // the red square's transparent pixel shows what's underneath
+    assert_eq!(pixel(2, 1), BLUE);
+    assert_eq!(pixel(3, 0), BLUE);
+}
+
+#[test]
+fn rejects_bad_signature() {
+    let mut data = TINY_ACS.to_vec();
+    data[0] = 0;
+    assert!(acs::parse(&data).is_err());
+    assert!(acs::parse(&data[..10]).is_err());
+}
+
+#[test]
+fn rejects_bad_compressed_data() {
+    assert!(acs::decompress(&[1, 2, 3], 4).is_err());
+    assert!(acs::decompress(&[0], 4).is_err());
+}
+
+#[test]
+fn huge_counts_are_errors_not_allocations() {
+    // the animation list's offset, then its count
+    let mut data = TINY_ACS.to_vec();
+    let offset = u32::from_le_bytes([data[12], data[13], data[14], data[15]]) as usize;
+    data[offset..offset + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert!(acs::parse(&data).is_err());
+
+    assert!(acs::decompress(&[0, 0xFF, 0xFF], usize::MAX).is_err());
+}
+
+#[test]
+fn empty_images_have_no_pixels() {
+    // the first image's width, after its locator in the image list
+    let mut data = TINY_ACS.to_vec();
+    let list = u32::from_le_bytes([data[20], data[21], data[22], data[23]]) as usize;
+    let image = u32::from_le_bytes([
+        data[list + 4],
+        data[list + 5],
+        data[list + 6],
+        data[list + 7],
+    ]) as usize;
+    data[image + 1..image + 3].copy_from_slice(&0u16.to_le_bytes());
+    let file = acs::parse(&data).unwrap();
+    assert_eq!(file.images[0].width, 0);
+    assert!(file.images[0].pixels.is_empty());
+}
+
+proptest! {
+    #[test]
+    fn garbage_never_panics(data in prop::collection::vec(any::<u8>(), 0..512)) {
+        let mut with_signature = vec![0xC3, 0xAB, 0xCD, 0xAB];
+        with_signature.extend(&data);
+        let _ = acs::import(&data);
+        let _ = acs::import(&with_signature);
+        let _ = acs::decompress(&data, data.len() * 4);
+    }
+
+    #[test]
+    fn damaged_files_never_panic(damage in prop::collection::vec((any::<prop::sample::Index>(), any::<u8>()), 1..16)) {
+        let mut data = TINY_ACS.to_vec();
+        for (index, byte) in damage {
+            let i = index.index(data.len());
+            data[i] = byte;
+        }
+        let _ = acs::import(&data);
+    }
+}
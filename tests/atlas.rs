@@ -0,0 +1,119 @@
+use clippit_gpt::animation::atlas;
+use clippit_gpt::animation::models::AnimationConfig;
+use clippit_gpt::animation::models::AtlasRegion;
+use clippit_gpt::animation::models::FrameInfo;
+use clippit_gpt::animation::service::split_into_pages;
+use image::Rgba;
+use image::RgbaImage;
+
+const RED: Rgba<u8> = Rgba([255, 0, 0, 255]);
+const BLUE: Rgba<u8> = Rgba([0, 0, 255, 255]);
+
+/// one animation showing each of `cells` in turn, on a grid of 4x4 cells
+fn config(columns: usize, cells: &[usize]) -> AnimationConfig {
+    let frames: Vec<String> = cells
+        .iter()
+        .map(|c| format!("{{ duration: 10, info: {{ column: {}, row: 0 }} }}", c))
+        .collect();
+    serde_yaml::from_str(&format!(
+        "animations: {{ idle: [{{ name: Idle, frames: [{}] }}], action: [] }}\nsprite_sheet_info: {{ columns: {}, rows: 1 }}",
+        frames.join(", "),
+        columns
+    ))
+    .unwrap()
+}
+
+/// cell 0: a red 2x2 square at 1,1. cell 1: solid blue. cell 2: the same as cell 0. cell 3: empty
+fn sheet() -> RgbaImage {
+    RgbaImage::from_fn(16, 4, |x, y| match (x / 4, x % 4, y) {
+        (0 | 2, 1..=2, 1..=2) => RED,
+        (1, ..) => BLUE,
+        _ => Rgba([0, 0, 0, 0]),
+    })
+}
+
+fn frame(config: &AnimationConfig, i: usize) -> &FrameInfo {
+    &config.animations.idle[0].frames[i].info
+}
+
+#[test]
+fn trims_transparent_borders() {
+    let atlas = atlas::pack(&config(4, &[0, 1]), &[sheet()], [64, 64]).unwrap();
+    let square = frame(&atlas.config, 0).region.clone().unwrap();
+    assert_eq!(
+        (
+            square.width,
+            square.height,
+            square.offset_x,
+            square.offset_y
+        ),
+        (2, 2, 1, 1)
+    );
+    let page = &atlas.pages[0];
+    assert_eq!(page.get_pixel(square.x as u32, square.y as u32), &RED);
+
+    let solid = frame(&atlas.config, 1).region.clone().unwrap();
+    assert_eq!(
+        (solid.width, solid.height, solid.offset_x, solid.offset_y),
+        (4, 4, 0, 0)
+    );
+    assert_eq!(
+        page.get_pixel(solid.x as u32 + 3, solid.y as u32 + 3),
+        &BLUE
+    );
+
+    // drawing still uses the original cell size
+    assert_eq!(atlas.config.sprite_sheet_info.frame_size, Some([4, 4]));
+}
+
+#[test]
+fn identical_frames_share_pixels() {
+    let atlas = atlas::pack(&config(4, &[0, 2, 0, 1]), &[sheet()], [64, 64]).unwrap();
+    let regions: Vec<AtlasRegion> = (0..4)
+        .map(|i| frame(&atlas.config, i).region.clone().unwrap())
+        .collect();
+    assert_eq!(regions[0], regions[1]);
+    assert_eq!(regions[0], regions[2]);
+    assert_ne!(regions[0], regions[3]);
+    // a 4x4 and a 2x2 side by side with a pixel between them
+    assert_eq!(atlas.pages[0].dimensions(), (7, 4));
+}
+
+#[test]
+fn empty_frames_take_no_space() {
+    let atlas = atlas::pack(&config(4, &[3, 1]), &[sheet()], [64, 64]).unwrap();
+    let empty = frame(&atlas.config, 0).region.clone().unwrap();
+    assert_eq!((empty.width, empty.height), (0, 0));
+    assert_eq!(atlas.pages[0].dimensions(), (4, 4));
+}
+
+#[test]
+fn full_pages_start_new_ones() {
+    let atlas = atlas::pack(&config(4, &[0, 1]), &[sheet()], [4, 4]).unwrap();
+    assert_eq!(atlas.pages.len(), 2);
+    assert_eq!(frame(&atlas.config, 0).page, 1);
+    assert_eq!(frame(&atlas.config, 1).page, 0);
+}
+
+#[test]
+fn frames_bigger_than_a_page_are_errors() {
+    assert!(atlas::pack(&config(4, &[1]), &[sheet()], [3, 64]).is_err());
+    assert!(atlas::pack(&config(4, &[1]), &[sheet()], [64, 3]).is_err());
+    // off the grid
+    assert!(atlas::pack(&config(4, &[5]), &[sheet()], [64, 64]).is_err());
+}
+
+#[test]
+fn sheets_over_the_texture_limit_are_split() {
+    // fits, left alone
+    let (same, pages) = split_into_pages(config(4, &[0, 1]), sheet(), 16).unwrap();
+    assert_eq!(pages.len(), 1);
+    assert_eq!(pages[0].size, [16, 4]);
+    assert!(frame(&same, 0).region.is_none());
+
+    let (split, pages) = split_into_pages(config(4, &[0, 1]), sheet(), 4).unwrap();
+    assert_eq!(pages.len(), 2);
+    assert!(pages.iter().all(|p| p.size[0] <= 4 && p.size[1] <= 4));
+    assert_eq!(frame(&split, 0).page, 1);
+    assert!(frame(&split, 1).region.is_some());
+}
@@ -0,0 +1,182 @@
+use std::time::Duration;
+
+use clippit_gpt::animation::error::AnimationError;
+use clippit_gpt::animation::import::clippyjs;
+use clippit_gpt::animation::import::encode_png;
+use clippit_gpt::animation::models::AnimationServiceMode;
+use clippit_gpt::animation::models::AtlasRegion;
+use clippit_gpt::animation::scheduler::AnimationState;
+use image::Rgba;
+use image::RgbaImage;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+const AGENT_JS: &str = r#"clippy.ready('Tiny', {
+    "overlayCount": 1,
+    "framesize": [2, 2],
+    "animations": {
+        "Wave": {
+            "frames": [
+                { "duration": 100, "images": [[2, 0]], "sound": "3" },
+                { "duration": 100, "images": [[0, 0], [2, 0], [1, 1]], "exitBranch": 2 },
+                { "duration": 0, "branching": { "branches": [{ "frameIndex": 0, "weight": 40 }] } }
+            ]
+        },
+        "IdleBlink": {
+            "frames": [{ "duration": 50, "images": [[0, 0]] }]
+        }
+    }
+});"#;
+
+/// a full 2x1 grid of 2x2 cells, no room for blank frames
+fn map_png() -> Vec<u8> {
+    encode_png(&RgbaImage::from_pixel(4, 2, Rgba([255, 0, 0, 255]))).unwrap()
+}
+
+#[test]
+fn reads_the_agent_name() {
+    assert_eq!(clippyjs::agent_name(AGENT_JS), "Tiny");
+    assert_eq!(clippyjs::agent_name("clippy.ready(\"Rover\", {})"), "Rover");
+    assert_eq!(clippyjs::agent_name("clippy.ready('', {})"), "Agent");
+    assert_eq!(clippyjs::agent_name("nothing here"), "Agent");
+    // not a quote, but it mustn't split a character either
+    assert_eq!(clippyjs::agent_name("clippy.ready(«Rover«, {})"), "Rover");
+}
+
+#[test]
+fn converts_frames_overlays_and_branches() {
+    let character = clippyjs::import(AGENT_JS, &map_png()).unwrap();
+    let config = character.config;
+    assert_eq!(character.name, "Tiny");
+
+    // "Idle" names go to the idle category without it
+    assert_eq!(config.animations.idle[0].name, "Blink");
+    let wave = &config.animations.action[0];
+    assert_eq!(wave.name, "Wave");
+
+    let frames = &wave.frames;
+    assert_eq!((frames[0].info.column, frames[0].info.row), (1, 0));
+    assert_eq!(frames[0].sound.as_deref(), Some("3"));
+    // overlays become layers, ones off the grid use a region
+    assert_eq!(frames[1].info.layers.len(), 2);
+    assert_eq!(
+        (
+            frames[1].info.layers[0].column,
+            frames[1].info.layers[0].row
+        ),
+        (1, 0)
+    );
+    assert_eq!(
+        frames[1].info.layers[1].region,
+        Some(AtlasRegion {
+            x: 1,
+            y: 1,
+            width: 2,
+            height: 2,
+            offset_x: 0,
+            offset_y: 0
+        })
+    );
+    assert_eq!(frames[1].exit_branch, Some(2));
+    assert_eq!(frames[2].branching[0].frame_index, 0);
+    assert_eq!(frames[2].branching[0].weight, 40);
+}
+
+#[test]
+fn unaligned_images_keep_their_pixels() {
+    let unaligned = AGENT_JS.replace(r#""images": [[2, 0]]"#, r#""images": [[1, 0]]"#);
+    let config = clippyjs::import(&unaligned, &map_png()).unwrap().config;
+    let info = &config.animations.action[0].frames[0].info;
+    assert_eq!(
+        info.region,
+        Some(AtlasRegion {
+            x: 1,
+            y: 0,
+            width: 2,
+            height: 2,
+            offset_x: 0,
+            offset_y: 0
+        })
+    );
+    // aligned ones stay on the grid
+    assert_eq!(config.animations.action[0].frames[1].info.region, None);
+}
+
+#[test]
+fn blank_frames_get_an_empty_cell() {
+    let character = clippyjs::import(AGENT_JS, &map_png()).unwrap();
+    // the grid was full, so the sheet grew a row
+    assert_eq!(
+        (
+            character.config.sprite_sheet_info.columns,
+            character.config.sprite_sheet_info.rows
+        ),
+        (2, 2)
+    );
+    let blank = &character.config.animations.action[0].frames[2].info;
+    assert_eq!((blank.column, blank.row), (0, 1));
+
+    let sheet = image::load_from_memory(&character.image_data)
+        .unwrap()
+        .to_rgba8();
+    assert_eq!(sheet.dimensions(), (4, 4));
+    assert_eq!(sheet.get_pixel(0, 3)[3], 0);
+}
+
+#[test]
+fn broken_agents_are_errors() {
+    let no_json = clippyjs::import("clippy.ready('Tiny')", &map_png());
+    assert!(matches!(no_json, Err(AnimationError::Format(_))));
+    let zero = AGENT_JS.replace("[2, 2]", "[0, 2]");
+    assert!(matches!(
+        clippyjs::import(&zero, &map_png()),
+        Err(AnimationError::Format(_))
+    ));
+    assert!(matches!(
+        clippyjs::import(AGENT_JS, b"not a png"),
+        Err(AnimationError::Image(_))
+    ));
+}
+
+#[test]
+fn agents_without_animations_play_nothing() {
+    let config = clippyjs::import(
+        r#"clippy.ready('Empty', { "framesize": [2, 2], "animations": {} });"#,
+        &map_png(),
+    )
+    .unwrap()
+    .config;
+    let mut state = AnimationState::default();
+    assert!(state
+        .next_frame(&config, Duration::ZERO, &mut StdRng::seed_from_u64(7))
+        .is_none());
+}
+
+#[test]
+fn exit_branch_cycles_still_end() {
+    let looping = AGENT_JS
+        .replace(r#""exitBranch": 2"#, r#""exitBranch": 0"#)
+        .replace(r#""sound": "3""#, r#""exitBranch": 1"#);
+    let config = clippyjs::import(&looping, &map_png()).unwrap().config;
+    let mut state = AnimationState::default();
+    let mut rng = StdRng::seed_from_u64(7);
+    state.set_mode(AnimationServiceMode::Active);
+    assert_eq!(
+        state
+            .next_frame(&config, Duration::ZERO, &mut rng)
+            .unwrap()
+            .started_animation
+            .as_deref(),
+        Some("Wave")
+    );
+
+    state.set_mode(AnimationServiceMode::Idle);
+    assert!(state.is_exiting());
+    let started = (0..10).find_map(|_| {
+        state
+            .next_frame(&config, Duration::ZERO, &mut rng)
+            .unwrap()
+            .started_animation
+    });
+    assert_eq!(started.as_deref(), Some("Blink"));
+}
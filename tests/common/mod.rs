@@ -0,0 +1,69 @@
+//! Helpers shared by the scheduler tests, each test file uses a few of them.
+#![allow(dead_code)]
+
+use std::time::Duration;
+
+use clippit_gpt::animation::models::AnimationConfig;
+use clippit_gpt::animation::models::AnimationFrame;
+use clippit_gpt::animation::models::AnimationInfo;
+use clippit_gpt::animation::models::AnimationSets;
+use clippit_gpt::animation::models::FrameInfo;
+use clippit_gpt::animation::models::SpriteSheetInfo;
+use clippit_gpt::animation::scheduler::AnimationState;
+use rand::rngs::StdRng;
+use rand::Rng;
+use rand::SeedableRng;
+
+pub fn frame(column: usize, duration: usize) -> AnimationFrame {
+    AnimationFrame {
+        duration,
+        info: FrameInfo {
+            column,
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+pub fn animation(name: &str, frames: Vec<AnimationFrame>) -> AnimationInfo {
+    AnimationInfo {
+        name: name.to_owned(),
+        frames,
+        ..Default::default()
+    }
+}
+
+/// the animations on a single row of 4 cells
+pub fn config(idle: Vec<AnimationInfo>, action: Vec<AnimationInfo>) -> AnimationConfig {
+    AnimationConfig {
+        animations: AnimationSets { idle, action },
+        sprite_sheet_info: SpriteSheetInfo {
+            columns: 4,
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+/// the same sequence on every run
+pub fn rng() -> StdRng {
+    StdRng::seed_from_u64(7)
+}
+
+/// play frames until an animation starts, returns its name
+pub fn started<R: Rng>(
+    state: &mut AnimationState,
+    config: &AnimationConfig,
+    now: Duration,
+    rng: &mut R,
+) -> String {
+    loop {
+        if let Some(name) = state
+            .next_frame(config, now, rng)
+            .unwrap()
+            .started_animation
+        {
+            return name;
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use clippit_gpt::animation::editor::duplicate_frame;
+use clippit_gpt::animation::editor::remap_frame_refs;
+use clippit_gpt::animation::editor::remove_frame;
+use clippit_gpt::animation::editor::swap_frames;
+use clippit_gpt::animation::models::AnimationFrame;
+use clippit_gpt::animation::models::AnimationInfo;
+use clippit_gpt::animation::models::FrameBranch;
+use clippit_gpt::animation::models::FrameInfo;
+
+/// frames in columns 0..4, frame 1 branches to 3 and exits through 2, frame 3 exits through 0
+fn animation() -> AnimationInfo {
+    let mut frames: Vec<AnimationFrame> = (0..4)
+        .map(|column| AnimationFrame {
+            duration: 10,
+            info: FrameInfo {
+                column,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .collect();
+    frames[1].branching = vec![FrameBranch {
+        frame_index: 3,
+        weight: 50,
+    }];
+    frames[1].exit_branch = Some(2);
+    frames[3].exit_branch = Some(0);
+    AnimationInfo {
+        name: "Wave".to_owned(),
+        frames,
+        ..Default::default()
+    }
+}
+
+fn columns(animation: &AnimationInfo) -> Vec<usize> {
+    animation.frames.iter().map(|f| f.info.column).collect()
+}
+
+#[test]
+fn remapping_drops_what_maps_to_nothing() {
+    let mut animation = animation();
+    remap_frame_refs(&mut animation, |i| if i == 3 { None } else { Some(i * 10) });
+    assert!(animation.frames[1].branching.is_empty());
+    assert_eq!(animation.frames[1].exit_branch, Some(20));
+    assert_eq!(animation.frames[3].exit_branch, Some(0));
+}
+
+#[test]
+fn swapping_keeps_branches_on_their_frames() {
+    let mut animation = animation();
+    swap_frames(&mut animation, 2, 3);
+    assert_eq!(columns(&animation), [0, 1, 3, 2]);
+    assert_eq!(animation.frames[1].branching[0].frame_index, 2);
+    assert_eq!(animation.frames[1].exit_branch, Some(3));
+    assert_eq!(animation.frames[2].exit_branch, Some(0));
+}
+
+#[test]
+fn duplicates_go_after_the_original_without_branches() {
+    let mut animation = animation();
+    duplicate_frame(&mut animation, 1);
+    assert_eq!(columns(&animation), [0, 1, 1, 2, 3]);
+    assert_eq!(animation.frames[1].branching[0].frame_index, 4);
+    assert_eq!(animation.frames[1].exit_branch, Some(3));
+    assert!(animation.frames[2].branching.is_empty());
+    assert_eq!(animation.frames[2].exit_branch, None);
+    assert_eq!(animation.frames[4].exit_branch, Some(0));
+}
+
+#[test]
+fn removing_drops_branches_to_the_frame() {
+    let mut animation = animation();
+    remove_frame(&mut animation, 2);
+    assert_eq!(columns(&animation), [0, 1, 3]);
+    assert_eq!(animation.frames[1].branching[0].frame_index, 2);
+    assert_eq!(animation.frames[1].exit_branch, None);
+
+    remove_frame(&mut animation, 0);
+    assert_eq!(columns(&animation), [1, 3]);
+    assert_eq!(animation.frames[1].exit_branch, None);
+    assert_eq!(animation.frames[0].branching[0].frame_index, 1);
+}
@@ -0,0 +1,108 @@
+use std::io::Cursor;
+
+use clippit_gpt::animation::error::AnimationError;
+use clippit_gpt::animation::models::AnimationConfig;
+use clippit_gpt::animation::service::export_animation;
+use clippit_gpt::animation::service::render_animation_frames;
+use clippit_gpt::animation::service::ExportFormat;
+use image::codecs::gif::GifDecoder;
+use image::codecs::png::PngDecoder;
+use image::AnimationDecoder;
+use image::Frame;
+use image::Rgba;
+use image::RgbaImage;
+
+const RED: Rgba<u8> = Rgba([255, 0, 0, 255]);
+const BLUE: Rgba<u8> = Rgba([0, 0, 255, 255]);
+const CLEAR: Rgba<u8> = Rgba([0, 0, 0, 0]);
+
+/// Wave shows cell 0, branches on a zero length frame, then shows cell 1
+const CONFIG: &str = "
+animations:
+  idle: []
+  action:
+    - name: Wave
+      frames:
+        - { duration: 100, info: { column: 0, row: 0 } }
+        - { duration: 0, info: { column: 1, row: 0 }, branching: [{ frame_index: 0, weight: 50 }] }
+        - { duration: 50, info: { column: 1, row: 0 } }
+    - name: Blank
+      frames:
+        - { duration: 0, info: { column: 0, row: 0 } }
+sprite_sheet_info: { columns: 2, rows: 1 }
+";
+
+fn config() -> AnimationConfig {
+    serde_yaml::from_str(CONFIG).unwrap()
+}
+
+/// cell 0 is solid red, cell 1 has a blue left column and is clear on the right
+fn sheet() -> RgbaImage {
+    RgbaImage::from_fn(4, 2, |x, _| match x {
+        0 | 1 => RED,
+        2 => BLUE,
+        _ => CLEAR,
+    })
+}
+
+fn delays(frames: &[Frame]) -> Vec<u32> {
+    frames
+        .iter()
+        .map(|f| {
+            let (numer, denom) = f.delay().numer_denom_ms();
+            numer / denom
+        })
+        .collect()
+}
+
+#[test]
+fn zero_length_frames_are_left_out() {
+    let frames = render_animation_frames(&sheet(), &config(), "Wave").unwrap();
+    let durations: Vec<usize> = frames.iter().map(|(_, d)| *d).collect();
+    assert_eq!(durations, [100, 50]);
+    assert_eq!(frames[0].0.dimensions(), (2, 2));
+    assert!(frames[0].0.pixels().all(|p| *p == RED));
+    assert_eq!(frames[1].0.get_pixel(0, 1), &BLUE);
+    assert_eq!(frames[1].0.get_pixel(1, 1), &CLEAR);
+}
+
+#[test]
+fn mirrored_sheets_export_mirrored() {
+    let mut config = config();
+    config.sprite_sheet_info.mirror = true;
+    let frames = render_animation_frames(&sheet(), &config, "Wave").unwrap();
+    assert_eq!(frames[1].0.get_pixel(0, 0), &CLEAR);
+    assert_eq!(frames[1].0.get_pixel(1, 0), &BLUE);
+}
+
+#[test]
+fn gifs_keep_the_frames_and_delays() {
+    let gif = export_animation(&sheet(), &config(), "Wave", ExportFormat::Gif).unwrap();
+    let frames = GifDecoder::new(Cursor::new(gif))
+        .unwrap()
+        .into_frames()
+        .collect_frames()
+        .unwrap();
+    assert_eq!(delays(&frames), [100, 50]);
+    assert_eq!(frames[0].buffer().get_pixel(1, 1), &RED);
+    assert_eq!(frames[1].buffer().get_pixel(0, 0), &BLUE);
+}
+
+#[test]
+fn apngs_keep_the_frames_and_delays() {
+    let png = export_animation(&sheet(), &config(), "Wave", ExportFormat::Apng).unwrap();
+    let decoder = PngDecoder::new(Cursor::new(png)).unwrap();
+    assert!(decoder.is_apng());
+    let frames = decoder.apng().into_frames().collect_frames().unwrap();
+    assert_eq!(delays(&frames), [100, 50]);
+    assert_eq!(frames[0].buffer().get_pixel(1, 1), &RED);
+    assert_eq!(frames[1].buffer().get_pixel(1, 0), &CLEAR);
+}
+
+#[test]
+fn nothing_to_export_is_an_error() {
+    let unknown = export_animation(&sheet(), &config(), "Dance", ExportFormat::Gif);
+    assert!(matches!(unknown, Err(AnimationError::Format(_))));
+    let blank = export_animation(&sheet(), &config(), "Blank", ExportFormat::Apng);
+    assert!(matches!(blank, Err(AnimationError::Format(_))));
+}
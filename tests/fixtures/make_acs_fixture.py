@@ -0,0 +1,132 @@
+"""Writes tiny.acs, a minimal Microsoft Agent character used by tests/acs_import.rs.
+
+4x3 character, three palette entries (0 transparent, 1 red, 2 blue) and two images,
+one stored raw and one using the acs bit level compression. "Wave" plays the one sound.
+"""
+from struct import pack
+
+
+def string(s):
+    if not s:
+        return pack("<I", 0)
+    return pack("<I", len(s)) + s.encode("utf-16-le") + b"\0\0"
+
+
+class Bits:
+    def __init__(self):
+        self.bits = []
+
+    def put(self, value, count):
+        self.bits += [(value >> i) & 1 for i in range(count)]
+
+    def bytes(self):
+        out = bytearray(b"\0")
+        bits = self.bits + [0] * (-len(self.bits) % 8)
+        for i in range(0, len(bits), 8):
+            out.append(sum(b << n for n, b in enumerate(bits[i:i + 8])))
+        return bytes(out) + b"\xff" * 4
+
+
+# stored bottom row first: a row of red then two rows of blue
+compressed = Bits()
+compressed.put(0, 1); compressed.put(1, 8)                 # literal 1
+compressed.put(1, 1); compressed.put(0, 1); compressed.put(0, 6)  # back 1
+compressed.put(1, 1); compressed.put(0, 1); compressed.put(0, 1)  # 3 bytes
+compressed.put(0, 1); compressed.put(2, 8)                 # literal 2
+compressed.put(1, 1); compressed.put(0, 1); compressed.put(0, 6)  # back 1
+compressed.put(0b011, 3); compressed.put(2, 2)             # 7 bytes
+compressed.put(1, 1); compressed.put(0b111, 3); compressed.put(0xFFFFF, 20)  # end
+
+images = [
+    # 2x2 red with a transparent top right pixel, rows padded to 4 bytes and stored bottom up
+    pack("<BHHB", 1, 2, 2, 0) + pack("<I", 8) + bytes([1, 1, 0, 0, 1, 0, 0, 0]) + pack("<II", 0, 0),
+    pack("<BHHB", 1, 4, 3, 1) + pack("<I", len(compressed.bytes())) + compressed.bytes() + pack("<II", 0, 0),
+]
+
+
+# an 8 bit mono wave file with two samples, the acs stores them whole
+sounds = [
+    b"RIFF" + pack("<I", 38) + b"WAVEfmt " + pack("<IHHIIHH", 16, 1, 1, 8000, 8000, 1, 8)
+    + b"data" + pack("<I", 2) + bytes([128, 255]),
+]
+
+
+def frame(images, duration, exit_branch=-1, branches=(), audio=0xFFFF):
+    out = pack("<H", len(images))
+    for index, x, y in images:
+        out += pack("<Ihh", index, x, y)
+    out += pack("<HHh", audio, duration, exit_branch)
+    out += pack("<B", len(branches))
+    for index, probability in branches:
+        out += pack("<HH", index, probability)
+    return out + pack("<B", 0)
+
+
+def animation(name, frames):
+    return string(name) + pack("<B", 2) + string("") + pack("<H", len(frames)) + b"".join(frames)
+
+
+animations = [
+    animation("IdleBlink", [
+        frame([(1, 0, 0)], 10, branches=[(0, 50)]),
+        frame([(0, 1, 1), (1, 0, 0)], 20, exit_branch=0),
+    ]),
+    animation("Wave", [frame([(1, 0, 0)], 5, audio=0)]),
+]
+
+localized = pack("<H", 1) + pack("<H", 0x0409) + string("Tiny") + string("test character") + string("")
+
+
+def character_info(localized_at):
+    return (
+        pack("<HH", 0, 2)
+        + pack("<II", localized_at, len(localized))
+        + b"\0" * 16
+        + pack("<HHBI", 4, 3, 0, 0)
+        + pack("<HH", 2, 0)
+        + pack("<I", 3) + bytes([255, 0, 255, 0, 0, 0, 255, 0, 255, 0, 0, 0])
+        + pack("<B", 0)
+    )
+
+
+header_size = 4 + 8 * 4
+body = bytearray()
+
+
+def place(data):
+    at = header_size + len(body)
+    body.extend(data)
+    return at, len(data)
+
+
+localized_at, _ = place(localized)
+character_at, character_size = place(character_info(localized_at))
+
+animation_list = pack("<I", len(animations))
+for data in animations:
+    at, size = place(data)
+    animation_list += string("x") + pack("<II", at, size)
+animations_at, animations_size = place(animation_list)
+
+image_list = pack("<I", len(images))
+for data in images:
+    at, size = place(data)
+    image_list += pack("<III", at, size, 0)
+images_at, images_size = place(image_list)
+
+audio_list = pack("<I", len(sounds))
+for data in sounds:
+    at, size = place(data)
+    audio_list += pack("<III", at, size, 0)
+audio_at, audio_size = place(audio_list)
+
+header = pack(
+    "<IIIIIIIII", 0xABCDABC3,
+    character_at, character_size,
+    animations_at, animations_size,
+    images_at, images_size,
+    audio_at, audio_size,
+)
+
+with open("tiny.acs", "wb") as f:
+    f.write(header + bytes(body))
@@ -0,0 +1,140 @@
+use clippit_gpt::animation::atlas;
+use clippit_gpt::animation::import::remap::remap;
+use clippit_gpt::animation::import::CategoryRules;
+use clippit_gpt::animation::models::AnimationConfig;
+use clippit_gpt::animation::models::AtlasRegion;
+use clippit_gpt::animation::models::FrameInfo;
+use clippit_gpt::animation::models::FrameLayer;
+use clippit_gpt::animation::service::render_frame_image;
+use image::Rgba;
+use image::RgbaImage;
+
+const RED: Rgba<u8> = Rgba([255, 0, 0, 255]);
+const BLUE: Rgba<u8> = Rgba([0, 0, 255, 255]);
+const GREEN: Rgba<u8> = Rgba([0, 255, 0, 255]);
+const CLEAR: Rgba<u8> = Rgba([0, 0, 0, 0]);
+
+/// three 2x2 cells: a red left column, a blue top row and solid green
+fn sheet() -> RgbaImage {
+    RgbaImage::from_fn(6, 2, |x, y| match (x / 2, x % 2, y) {
+        (0, 0, _) => RED,
+        (1, _, 0) => BLUE,
+        (2, ..) => GREEN,
+        _ => CLEAR,
+    })
+}
+
+/// one idle animation showing `frame`
+fn config(frame: &str) -> AnimationConfig {
+    serde_yaml::from_str(&format!(
+        "animations: {{ idle: [{{ name: Idle, frames: [{{ duration: 10, info: {} }}] }}], action: [] }}\nsprite_sheet_info: {{ columns: 3, rows: 1 }}",
+        frame
+    ))
+    .unwrap()
+}
+
+fn render(config: &AnimationConfig, sheet: &RgbaImage) -> RgbaImage {
+    render_frame_image(sheet, config, &config.animations.idle[0].frames[0].info).unwrap()
+}
+
+fn pixels(image: &RgbaImage) -> Vec<Rgba<u8>> {
+    image.pixels().copied().collect()
+}
+
+#[test]
+fn layers_draw_over_the_frame_in_order() {
+    let config = config("{ column: 0, row: 0, layers: [{ column: 1, row: 0 }] }");
+    assert_eq!(pixels(&render(&config, &sheet())), [BLUE, BLUE, RED, CLEAR]);
+
+    let config = self::config(
+        "{ column: 0, row: 0, layers: [{ column: 2, row: 0 }, { column: 1, row: 0 }] }",
+    );
+    assert_eq!(
+        pixels(&render(&config, &sheet())),
+        [BLUE, BLUE, GREEN, GREEN]
+    );
+}
+
+#[test]
+fn layer_offsets_add_to_the_frame_offset() {
+    let config = config("{ column: 0, row: 0, layers: [{ column: 1, row: 0, offset_x: 1 }] }");
+    assert_eq!(pixels(&render(&config, &sheet())), [RED, BLUE, RED, CLEAR]);
+
+    // the frame offset moves every layer, what falls outside the cell is cut off
+    let config = self::config(
+        "{ column: 0, row: 0, offset_y: 1, layers: [{ column: 1, row: 0, offset_x: 1 }] }",
+    );
+    assert_eq!(
+        pixels(&render(&config, &sheet())),
+        [CLEAR, CLEAR, RED, BLUE]
+    );
+}
+
+#[test]
+fn layers_at_pixels_use_the_grid_when_they_can() {
+    let aligned = FrameLayer::at_pixel(4, 2, [2, 2]);
+    assert_eq!((aligned.column, aligned.row, aligned.region), (2, 1, None));
+
+    let unaligned = FrameLayer::at_pixel(3, 2, [2, 2]);
+    assert_eq!(
+        unaligned.region,
+        Some(AtlasRegion {
+            x: 3,
+            y: 2,
+            width: 2,
+            height: 2,
+            offset_x: 0,
+            offset_y: 0
+        })
+    );
+    let region = FrameInfo {
+        layers: vec![unaligned],
+        ..Default::default()
+    }
+    .images()
+    .nth(1)
+    .unwrap()
+    .source_region([2, 2]);
+    assert_eq!((region.x, region.y), (3, 2));
+
+    // no cell size, no grid
+    assert!(FrameLayer::at_pixel(0, 0, [0, 0]).region.is_some());
+}
+
+#[test]
+fn atlases_keep_layers_where_they_were() {
+    let config = config("{ column: 0, row: 0, layers: [{ column: 1, row: 0, offset_x: 1 }, { column: 0, row: 0, region: { x: 1, y: 0, width: 2, height: 2 } }] }");
+    let before = render(&config, &sheet());
+
+    let atlas = atlas::pack(&config, &[sheet()], [64, 64]).unwrap();
+    let info = &atlas.config.animations.idle[0].frames[0].info;
+    assert_eq!(info.layers.len(), 2);
+    assert!(info.layers.iter().all(|l| l.region.is_some()));
+    assert_eq!(render(&atlas.config, &atlas.pages[0]), before);
+}
+
+#[test]
+fn images_offsets_become_layers() {
+    let json = r#"[{ "Name": "Idle", "Frames": [{ "dur": 10, "col": 1, "row": 0, "ImagesOffsets": [[2, 0], [4, 0], [1, 0], [-2, 0]] }] }]"#;
+    let config = remap(json, [6, 2], [2, 2], &CategoryRules::default()).unwrap();
+    let info = &config.animations.idle[0].frames[0].info;
+    // the frame's own cell and the one off the sheet are dropped
+    assert_eq!(info.layers.len(), 2);
+    assert_eq!(
+        (
+            info.layers[0].column,
+            info.layers[0].row,
+            info.layers[0].region.clone()
+        ),
+        (2, 0, None)
+    );
+    assert_eq!(
+        info.layers[1].region.as_ref().map(|r| (r.x, r.y)),
+        Some((1, 0))
+    );
+
+    assert_eq!(
+        pixels(&render(&config, &sheet())),
+        [GREEN, BLUE, GREEN, GREEN]
+    );
+}
@@ -0,0 +1,114 @@
+mod common;
+
+use std::time::Duration;
+
+use clippit_gpt::animation::models::MoveDirection;
+use clippit_gpt::animation::scheduler::AnimationState;
+use clippit_gpt::movement;
+use clippit_gpt::movement::Movement;
+use common::animation;
+use common::config;
+use common::frame;
+use common::rng;
+use egui::pos2;
+use egui::vec2;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+#[test]
+fn follows_the_path_at_a_steady_speed() {
+    let path = Movement::new(
+        pos2(0.0, 0.0),
+        vec![pos2(100.0, 0.0), pos2(100.0, 50.0)],
+        100.0,
+        10.0,
+    );
+    assert_eq!(path.position(10.0), pos2(0.0, 0.0));
+    assert_eq!(path.position(10.5), pos2(50.0, 0.0));
+    assert_eq!(path.direction(10.5), Some(MoveDirection::Right));
+    assert_eq!(path.position(11.25), pos2(100.0, 25.0));
+    assert_eq!(path.direction(11.25), Some(MoveDirection::Down));
+    assert!(!path.is_done(11.25));
+
+    assert_eq!(path.position(20.0), pos2(100.0, 50.0));
+    assert_eq!(path.direction(20.0), None);
+    assert!(path.is_done(20.0));
+}
+
+#[test]
+fn directions_follow_the_longer_axis() {
+    assert_eq!(
+        MoveDirection::from_offset(-10.0, 3.0),
+        Some(MoveDirection::Left)
+    );
+    assert_eq!(
+        MoveDirection::from_offset(2.0, -30.0),
+        Some(MoveDirection::Up)
+    );
+    assert_eq!(MoveDirection::from_offset(0.0, 0.0), None);
+    assert_eq!(MoveDirection::Left.mirrored(), MoveDirection::Right);
+    assert_eq!(MoveDirection::Up.mirrored(), MoveDirection::Up);
+}
+
+#[test]
+fn stays_on_screen() {
+    let screen = vec2(800.0, 600.0);
+    let size = vec2(200.0, 100.0);
+    assert_eq!(
+        movement::clamp_to_screen(pos2(-50.0, 700.0), size, screen),
+        pos2(0.0, 500.0)
+    );
+    assert_eq!(
+        movement::clamp_to_screen(pos2(700.0, 20.0), size, screen),
+        pos2(600.0, 20.0)
+    );
+    // too big to fit, the top left stays visible
+    assert_eq!(
+        movement::clamp_to_screen(pos2(30.0, 30.0), vec2(900.0, 700.0), screen),
+        pos2(0.0, 0.0)
+    );
+
+    let mut rng = StdRng::seed_from_u64(7);
+    for _ in 0..100 {
+        let to = movement::wander_target(&mut rng, pos2(590.0, 490.0), size, screen);
+        assert!(
+            (0.0..=600.0).contains(&to.x) && (0.0..=500.0).contains(&to.y),
+            "{:?}",
+            to
+        );
+    }
+}
+
+#[test]
+fn walks_while_moving() {
+    let config = config(
+        vec![animation("Idle", vec![frame(0, 10)])],
+        vec![
+            animation("MoveLeft", vec![frame(1, 10)]),
+            animation("MoveUp", vec![frame(2, 10)]),
+        ],
+    );
+    let mut state = AnimationState::default();
+    let mut rng = rng();
+    let mut column = |state: &mut AnimationState| {
+        state
+            .next_frame(&config, Duration::ZERO, &mut rng)
+            .unwrap()
+            .frame
+            .info
+            .column
+    };
+
+    assert_eq!(column(&mut state), 0);
+    state.set_moving(Some(MoveDirection::Left));
+    for _ in 0..3 {
+        assert_eq!(column(&mut state), 1);
+    }
+    state.set_moving(Some(MoveDirection::Up));
+    assert_eq!(column(&mut state), 2);
+    // no MoveRight, it carries on as usual
+    state.set_moving(Some(MoveDirection::Right));
+    assert_eq!(column(&mut state), 0);
+    state.set_moving(None);
+    assert_eq!(column(&mut state), 0);
+}
@@ -0,0 +1,140 @@
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+
+use clippit_gpt::animation::error::AnimationError;
+use clippit_gpt::animation::import::acs;
+use clippit_gpt::animation::import::encode_png;
+use clippit_gpt::animation::pack::list_packs;
+use clippit_gpt::animation::pack::write_pack;
+use clippit_gpt::animation::pack::CharacterPackInfo;
+use clippit_gpt::animation::pack::PackSource;
+use image::Rgba;
+use image::RgbaImage;
+use zip::write::FileOptions;
+use zip::CompressionMethod;
+use zip::ZipWriter;
+
+const METADATA: &str = "name: Tiny\nauthor: tests\n";
+const ANIMATIONS: &str = "
+animations:
+  idle:
+    - name: Idle
+      frames: [{ duration: 10, info: { column: 0, row: 0 } }]
+  action: []
+sprite_sheet_info: { columns: 1, rows: 1 }
+";
+
+/// an empty directory of its own for each test
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("clippit-packs-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn sheet() -> Vec<u8> {
+    encode_png(&RgbaImage::from_pixel(2, 2, Rgba([255, 0, 0, 255]))).unwrap()
+}
+
+fn write_dir_pack(dir: &PathBuf, image: &[u8]) {
+    fs::create_dir_all(dir).unwrap();
+    fs::write(dir.join("pack.yaml"), METADATA).unwrap();
+    fs::write(dir.join("animations.yaml"), ANIMATIONS).unwrap();
+    fs::write(dir.join("sprite_sheet.png"), image).unwrap();
+}
+
+fn write_zip_pack(path: &PathBuf, prefix: &str) {
+    let mut zip = ZipWriter::new(fs::File::create(path).unwrap());
+    let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+    let files: [(&str, Vec<u8>); 3] = [
+        ("pack.yaml", METADATA.as_bytes().to_vec()),
+        ("animations.yaml", ANIMATIONS.as_bytes().to_vec()),
+        ("sprite_sheet.png", sheet()),
+    ];
+    for (name, data) in files {
+        zip.start_file(format!("{}{}", prefix, name), options)
+            .unwrap();
+        zip.write_all(&data).unwrap();
+    }
+    zip.finish().unwrap();
+}
+
+#[test]
+fn loads_directory_packs() {
+    let dir = temp_dir("directory").join("tiny");
+    write_dir_pack(&dir, &sheet());
+
+    let info = CharacterPackInfo::from_path(&dir).unwrap();
+    assert_eq!(info.metadata.name, "Tiny");
+    assert_eq!(info.source, PackSource::Directory(dir.clone()));
+    let pack = info.load().unwrap();
+    assert_eq!(pack.animation_data, ANIMATIONS);
+    assert_eq!(pack.image_data, sheet());
+}
+
+#[test]
+fn corrupt_sprite_sheets_fail_to_load() {
+    let dir = temp_dir("corrupt").join("tiny");
+    let mut image = sheet();
+    image.truncate(image.len() / 2);
+    write_dir_pack(&dir, &image);
+
+    let info = CharacterPackInfo::from_path(&dir).unwrap();
+    assert!(matches!(info.load(), Err(AnimationError::Image(_))));
+}
+
+#[test]
+fn loads_zip_packs_with_or_without_a_folder() {
+    let dir = temp_dir("zip");
+    for prefix in ["", "tiny/"] {
+        let path = dir.join(format!("tiny{}.zip", prefix.len()));
+        write_zip_pack(&path, prefix);
+        let info = CharacterPackInfo::from_path(&path).unwrap();
+        assert_eq!(info.metadata.name, "Tiny");
+        assert_eq!(info.load().unwrap().image_data, sheet());
+    }
+
+    // only one folder deep
+    let path = dir.join("nested.zip");
+    write_zip_pack(&path, "packs/tiny/");
+    assert!(matches!(
+        CharacterPackInfo::from_path(&path),
+        Err(AnimationError::MissingFile(_))
+    ));
+}
+
+#[test]
+fn lists_the_builtin_pack_first_and_skips_broken_ones() {
+    let dir = temp_dir("list");
+    write_dir_pack(&dir.join("b-tiny"), &sheet());
+    write_zip_pack(&dir.join("c-tiny.zip"), "");
+    fs::create_dir_all(dir.join("a-broken")).unwrap();
+    fs::write(dir.join("notes.txt"), "not a pack").unwrap();
+
+    let packs = list_packs(Some(&dir));
+    let sources: Vec<&PackSource> = packs.iter().map(|p| &p.source).collect();
+    assert_eq!(
+        sources,
+        [
+            &PackSource::BuiltIn,
+            &PackSource::Directory(dir.join("b-tiny")),
+            &PackSource::Zip(dir.join("c-tiny.zip"))
+        ]
+    );
+    assert_eq!(list_packs(None).len(), 1);
+}
+
+#[test]
+fn imported_sounds_are_written_with_the_pack() {
+    let character = acs::import(include_bytes!("fixtures/tiny.acs")).unwrap();
+    let dir = write_pack(&temp_dir("sounds").join("tiny"), &character, "").unwrap();
+    assert_eq!(
+        fs::read(dir.join("sounds").join("0.wav")).unwrap(),
+        character.sounds[0].1
+    );
+    assert_eq!(
+        CharacterPackInfo::from_path(&dir).unwrap().metadata.name,
+        "Tiny"
+    );
+}
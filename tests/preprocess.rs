@@ -0,0 +1,163 @@
+use egui::Color32;
+use egui::ColorImage;
+use image::Rgba;
+use image::RgbaImage;
+
+use clippit_gpt::animation::models::AnimationConfig;
+use clippit_gpt::animation::preprocess;
+use clippit_gpt::animation::validate::parse_and_validate;
+use clippit_gpt::animation::validate::Severity;
+
+const MAGENTA: Rgba<u8> = Rgba([255, 0, 255, 255]);
+const RED: Rgba<u8> = Rgba([255, 0, 0, 255]);
+const BLUE: Rgba<u8> = Rgba([0, 0, 255, 255]);
+const CLEAR: Rgba<u8> = Rgba([0, 0, 0, 0]);
+
+fn config(yaml: &str) -> AnimationConfig {
+    serde_yaml::from_str(yaml).unwrap()
+}
+
+#[test]
+fn color_key_becomes_transparent() {
+    let sheet = RgbaImage::from_fn(2, 1, |x, _| if x == 0 { MAGENTA } else { RED });
+    let config = config(
+        "
+animations: { idle: [], action: [] }
+sprite_sheet_info: { columns: 1, rows: 1 }
+preprocess:
+  color_key: [255, 0, 255]
+",
+    );
+    let image = preprocess::apply(&sheet, &config.preprocess);
+    assert_eq!(image.get_pixel(0, 0), &CLEAR);
+    assert_eq!(image.get_pixel(1, 0), &RED);
+}
+
+#[test]
+fn palette_swaps_colours_and_hue_shifts_the_rest() {
+    let sheet = RgbaImage::from_fn(
+        2,
+        1,
+        |x, _| if x == 0 { RED } else { Rgba([0, 255, 0, 255]) },
+    );
+    let config = config(
+        "
+animations: { idle: [], action: [] }
+sprite_sheet_info: { columns: 1, rows: 1 }
+preprocess:
+  palette:
+    - { from: [255, 0, 0], to: [40, 40, 40, 255] }
+  hue_shift: 120
+",
+    );
+    let image = preprocess::apply(&sheet, &config.preprocess);
+    // greys have no hue to shift
+    assert_eq!(image.get_pixel(0, 0), &Rgba([40, 40, 40, 255]));
+    assert_eq!(image.get_pixel(1, 0), &BLUE);
+}
+
+#[test]
+fn scale2x_follows_diagonal_edges() {
+    // a diagonal: the corner touching both red neighbours turns red
+    let sheet = RgbaImage::from_fn(2, 2, |x, y| if x == y { BLUE } else { RED });
+    let image = preprocess::scale2x(&sheet);
+    assert_eq!(image.dimensions(), (4, 4));
+    assert_eq!(image.get_pixel(0, 0), &BLUE);
+    assert_eq!(image.get_pixel(1, 0), &BLUE);
+    assert_eq!(image.get_pixel(1, 1), &RED);
+    assert_eq!(image.get_pixel(2, 2), &RED);
+}
+
+#[test]
+fn upscaling_scales_the_config_to_match() {
+    let mut config = config(
+        "
+animations:
+  idle:
+    - name: Idle
+      frames:
+        - duration: 100
+          info:
+            column: 0
+            row: 0
+            offset_x: 1
+            region: { x: 2, y: 3, width: 4, height: 5 }
+  action: []
+sprite_sheet_info: { columns: 1, rows: 1, scale: 2.0 }
+preprocess:
+  upscale: { factor: 4, filter: Scale2x }
+",
+    );
+    let sheet = RgbaImage::from_pixel(8, 8, RED);
+    let image = preprocess::apply(&sheet, &config.preprocess);
+    assert_eq!(image.dimensions(), (32, 32));
+
+    preprocess::scale_config(&mut config);
+    assert_eq!(config.sprite_sheet_info.scale, 0.5);
+    let info = &config.animations.idle[0].frames[0].info;
+    assert_eq!(info.offset_x, 4);
+    let region = info.region.as_ref().unwrap();
+    assert_eq!(
+        (region.x, region.y, region.width, region.height),
+        (8, 12, 16, 20)
+    );
+
+    // already scaled, a second pass leaves it alone
+    preprocess::scale_config(&mut config);
+    assert_eq!(config.sprite_sheet_info.scale, 0.5);
+}
+
+#[test]
+fn upscaling_is_capped_by_the_output_size() {
+    let yaml = |factor: u32| {
+        format!("
+animations: {{ idle: [{{ name: Idle, frames: [{{ duration: 10, info: {{ column: 0, row: 0 }} }}] }}], action: [] }}
+sprite_sheet_info: {{ columns: 1, rows: 1 }}
+preprocess:
+  upscale: {{ factor: {}, filter: Nearest }}
+", factor)
+    };
+    // about the size of the built in sheet
+    let sheet = ColorImage::new([3000, 3000], Color32::RED);
+    let (config, diagnostics) = parse_and_validate(&yaml(2), Some(&sheet));
+    assert!(config.is_some(), "{:?}", diagnostics);
+
+    let (config, diagnostics) = parse_and_validate(&yaml(3), Some(&sheet));
+    assert!(config.is_none());
+    let diagnostic = diagnostics
+        .iter()
+        .find(|d| d.path == "preprocess.upscale.factor")
+        .unwrap();
+    assert_eq!(diagnostic.severity, Severity::Error);
+}
+
+#[test]
+fn the_cache_keeps_one_result_per_sheet() {
+    let sheet = RgbaImage::from_pixel(1, 1, RED);
+    let shift = |degrees: u32| {
+        config(&format!(
+            "
+animations: {{ idle: [], action: [] }}
+sprite_sheet_info: {{ columns: 1, rows: 1 }}
+preprocess: {{ hue_shift: {} }}
+",
+            degrees
+        ))
+        .preprocess
+    };
+
+    let before = preprocess::cached_sheets();
+    let blue = preprocess::apply_cached(b"one sheet", &sheet, &shift(240));
+    let green = preprocess::apply_cached(b"one sheet", &sheet, &shift(120));
+    assert_eq!(blue.get_pixel(0, 0), &BLUE);
+    assert_eq!(green.get_pixel(0, 0), &Rgba([0, 255, 0, 255]));
+    assert_eq!(preprocess::cached_sheets(), before + 1);
+    // switching back redoes it rather than keeping every theme around
+    assert_eq!(
+        preprocess::apply_cached(b"one sheet", &sheet, &shift(240)).get_pixel(0, 0),
+        &BLUE
+    );
+
+    preprocess::apply_cached(b"another sheet", &sheet, &shift(240));
+    assert_eq!(preprocess::cached_sheets(), before + 2);
+}
@@ -0,0 +1,873 @@
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use clippit_gpt::actions::DispatchActions;
+use clippit_gpt::actions::Interaction;
+use clippit_gpt::actions::LifecycleEvent;
+use clippit_gpt::animation::audio::AudioSettings;
+use clippit_gpt::animation::audio::RecordingSink;
+use clippit_gpt::animation::import::encode_png;
+use clippit_gpt::animation::models::AnimationConfig;
+use clippit_gpt::animation::models::AnimationFrame;
+use clippit_gpt::animation::models::AnimationInfo;
+use clippit_gpt::animation::models::AnimationServiceMode;
+use clippit_gpt::animation::models::FrameBranch;
+use clippit_gpt::animation::scheduler::AnimationState;
+use clippit_gpt::animation::scheduler::Clock;
+use clippit_gpt::animation::scheduler::LookDirection;
+use clippit_gpt::animation::scheduler::ManualClock;
+use clippit_gpt::animation::scheduler::PlaybackSettings;
+use clippit_gpt::animation::service::AnimationService;
+use clippit_gpt::models::QuestionResponse;
+use common::animation;
+use common::config;
+use common::frame;
+use common::rng;
+use common::started;
+use proptest::prelude::*;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+#[test]
+fn plays_frames_in_order_then_starts_again() {
+    let config = config(
+        vec![animation(
+            "Idle",
+            vec![frame(0, 10), frame(1, 20), frame(2, 30)],
+        )],
+        vec![],
+    );
+    let mut state = AnimationState::default();
+    let mut rng = rng();
+
+    let steps: Vec<_> = (0..7)
+        .map(|_| state.next_frame(&config, Duration::ZERO, &mut rng).unwrap())
+        .collect();
+    let indices: Vec<usize> = steps.iter().map(|s| s.frame_index).collect();
+    assert_eq!(indices, [0, 1, 2, 0, 1, 2, 0]);
+
+    let started: Vec<Option<&str>> = steps
+        .iter()
+        .map(|s| s.started_animation.as_deref())
+        .collect();
+    assert_eq!(
+        started,
+        [
+            Some("Idle"),
+            None,
+            None,
+            Some("Idle"),
+            None,
+            None,
+            Some("Idle")
+        ]
+    );
+    assert_eq!(steps[1].duration(), Duration::from_millis(20));
+}
+
+#[test]
+fn certain_branch_is_always_taken() {
+    let mut looping = frame(1, 10);
+    looping.branching = vec![FrameBranch {
+        frame_index: 0,
+        weight: 100,
+    }];
+    let config = config(
+        vec![animation("Loop", vec![frame(0, 10), looping, frame(2, 10)])],
+        vec![],
+    );
+    let mut state = AnimationState::default();
+    let mut rng = rng();
+
+    for _ in 0..20 {
+        assert_ne!(
+            state
+                .next_frame(&config, Duration::ZERO, &mut rng)
+                .unwrap()
+                .frame_index,
+            2
+        );
+    }
+}
+
+#[test]
+fn ask_question_switches_to_action_animations() {
+    let config = config(
+        vec![animation("Idle", vec![frame(0, 10), frame(1, 10)])],
+        vec![animation("Think", vec![frame(2, 10)])],
+    );
+    let mut state = AnimationState::default();
+    let mut rng = rng();
+
+    assert_eq!(
+        state
+            .next_frame(&config, Duration::ZERO, &mut rng)
+            .unwrap()
+            .started_animation
+            .as_deref(),
+        Some("Idle")
+    );
+    assert!(state.handle_action(
+        &DispatchActions::AskQuestion(None, "hi".to_owned()),
+        Duration::ZERO
+    ));
+    assert_eq!(state.mode(), &AnimationServiceMode::Active);
+    // no exit branch, the idle animation is cut short
+    assert_eq!(
+        state
+            .next_frame(&config, Duration::ZERO, &mut rng)
+            .unwrap()
+            .started_animation
+            .as_deref(),
+        Some("Think")
+    );
+
+    // talking while the answer types out, there's no Explain so it's any action
+    let answer = QuestionResponse {
+        question: "hi".to_owned(),
+        answer: "hello".to_owned(),
+    };
+    assert!(state.handle_action(
+        &DispatchActions::RespondToQuestion("clippit".to_owned(), answer),
+        Duration::ZERO
+    ));
+    assert_eq!(state.machine_state(), "talking");
+    assert_eq!(
+        state
+            .next_frame(&config, Duration::ZERO, &mut rng)
+            .unwrap()
+            .started_animation
+            .as_deref(),
+        Some("Think")
+    );
+
+    assert!(state.handle_action(
+        &DispatchActions::AnswerRevealed("clippit".to_owned()),
+        Duration::ZERO
+    ));
+    assert_eq!(state.mode(), &AnimationServiceMode::Idle);
+    assert_eq!(
+        state
+            .next_frame(&config, Duration::ZERO, &mut rng)
+            .unwrap()
+            .started_animation
+            .as_deref(),
+        Some("Idle")
+    );
+
+    assert!(!state.handle_action(&DispatchActions::NewFrameToRender, Duration::ZERO));
+}
+
+#[test]
+fn assistant_errors_show_for_a_while() {
+    let mut state = AnimationState::default();
+    let error = DispatchActions::AssistantError("clippit".to_owned(), "timed out".to_owned());
+    assert!(!state.handle_action(&error, Duration::from_secs(10)));
+    assert!(state.showing_error(Duration::from_secs(11)));
+    assert!(!state.showing_error(Duration::from_secs(20)));
+
+    state.handle_action(&error, Duration::from_secs(20));
+    let answer = QuestionResponse {
+        question: "hi".to_owned(),
+        answer: "hello".to_owned(),
+    };
+    state.handle_action(
+        &DispatchActions::RespondToQuestion("clippit".to_owned(), answer),
+        Duration::from_secs(21),
+    );
+    assert!(!state.showing_error(Duration::from_secs(21)));
+}
+
+#[test]
+fn waits_from_the_question_to_the_answer_or_an_error() {
+    let mut state = AnimationState::default();
+    let ask = DispatchActions::AskQuestion(None, "hi".to_owned());
+    state.handle_action(&ask, Duration::ZERO);
+    assert!(state.is_waiting());
+    let answer = QuestionResponse {
+        question: "hi".to_owned(),
+        answer: "hello".to_owned(),
+    };
+    state.handle_action(
+        &DispatchActions::RespondToQuestion("clippit".to_owned(), answer),
+        Duration::ZERO,
+    );
+    assert!(!state.is_waiting());
+
+    state.handle_action(&ask, Duration::ZERO);
+    state.handle_action(
+        &DispatchActions::AssistantError("clippit".to_owned(), "timed out".to_owned()),
+        Duration::ZERO,
+    );
+    assert!(!state.is_waiting());
+
+    // the glow comes from waiting, not from the animations
+    let built_in: AnimationConfig =
+        serde_yaml::from_str(include_str!("../assets/animations.yaml")).unwrap();
+    assert!(built_in.effects.waiting.glow);
+    assert!(built_in
+        .animations
+        .idle
+        .iter()
+        .chain(&built_in.animations.action)
+        .all(|a| !a.effects.glow));
+}
+
+#[test]
+fn mode_switch_winds_down_through_exit_branches() {
+    let mut frames: Vec<AnimationFrame> = (0..5).map(|i| frame(i, 10)).collect();
+    frames[0].exit_branch = Some(3);
+    frames[3].exit_branch = Some(4);
+    let config = config(
+        vec![animation("Wave", frames)],
+        vec![animation("Think", vec![frame(0, 10)])],
+    );
+    let mut state = AnimationState::default();
+    let mut rng = rng();
+
+    assert_eq!(
+        state
+            .next_frame(&config, Duration::ZERO, &mut rng)
+            .unwrap()
+            .frame_index,
+        0
+    );
+    state.set_mode(AnimationServiceMode::Active);
+    assert!(state.is_exiting());
+
+    let step = state.next_frame(&config, Duration::ZERO, &mut rng).unwrap();
+    assert_eq!((step.frame_index, step.started_animation), (3, None));
+    assert_eq!(
+        state
+            .next_frame(&config, Duration::ZERO, &mut rng)
+            .unwrap()
+            .frame_index,
+        4
+    );
+    assert_eq!(
+        state
+            .next_frame(&config, Duration::ZERO, &mut rng)
+            .unwrap()
+            .started_animation
+            .as_deref(),
+        Some("Think")
+    );
+}
+
+#[test]
+fn falls_back_to_the_other_category() {
+    let config = config(vec![], vec![animation("Think", vec![frame(0, 10)])]);
+    let mut state = AnimationState::default();
+    assert_eq!(
+        state
+            .next_frame(&config, Duration::ZERO, &mut rng())
+            .unwrap()
+            .started_animation
+            .as_deref(),
+        Some("Think")
+    );
+}
+
+#[test]
+fn nothing_to_play() {
+    let mut state = AnimationState::default();
+    assert!(state
+        .next_frame(&config(vec![], vec![]), Duration::ZERO, &mut rng())
+        .is_none());
+    assert!(state
+        .next_frame(
+            &config(vec![animation("Empty", vec![])], vec![]),
+            Duration::ZERO,
+            &mut rng()
+        )
+        .is_none());
+}
+
+#[test]
+fn same_seed_same_sequence() {
+    let animations: Vec<AnimationInfo> = (0..5)
+        .map(|i| animation(&format!("A{}", i), vec![frame(i, 10), frame(i, 10)]))
+        .collect();
+    let config = config(animations, vec![]);
+    let run = || {
+        let mut state = AnimationState::default();
+        let mut rng = rng();
+        (0..50)
+            .filter_map(|_| {
+                state
+                    .next_frame(&config, Duration::ZERO, &mut rng)
+                    .unwrap()
+                    .started_animation
+            })
+            .collect::<Vec<String>>()
+    };
+    assert_eq!(run(), run());
+}
+
+#[test]
+fn weights_and_no_immediate_repeats() {
+    let mut never = animation("Never", vec![frame(0, 10)]);
+    never.weight = 0.0;
+    let config = config(
+        vec![
+            animation("A", vec![frame(0, 10)]),
+            animation("B", vec![frame(1, 10)]),
+            never,
+        ],
+        vec![],
+    );
+    let mut state = AnimationState::default();
+    let mut rng = rng();
+
+    let names: Vec<String> = (0..40)
+        .map(|_| started(&mut state, &config, Duration::ZERO, &mut rng))
+        .collect();
+    assert!(!names.iter().any(|n| n == "Never"));
+    assert!(names.windows(2).all(|w| w[0] != w[1]), "{:?}", names);
+}
+
+#[test]
+fn cooldowns_hold_animations_back() {
+    let mut rare = animation("Rare", vec![frame(0, 10)]);
+    rare.cooldown = 60_000;
+    let config = config(
+        vec![
+            rare,
+            animation("A", vec![frame(1, 10)]),
+            animation("B", vec![frame(2, 10)]),
+        ],
+        vec![],
+    );
+    let mut state = AnimationState::default();
+    let mut rng = rng();
+
+    let mut now = Duration::ZERO;
+    let mut rare_at = Vec::new();
+    for _ in 0..200 {
+        now += Duration::from_secs(1);
+        if started(&mut state, &config, now, &mut rng) == "Rare" {
+            rare_at.push(now);
+        }
+    }
+    assert!(!rare_at.is_empty());
+    assert!(
+        rare_at
+            .windows(2)
+            .all(|w| w[1] - w[0] >= Duration::from_secs(60)),
+        "{:?}",
+        rare_at
+    );
+}
+
+#[test]
+fn rests_between_idles() {
+    let config = config(
+        vec![
+            animation("A", vec![frame(0, 10)]),
+            animation("B", vec![frame(1, 10)]),
+        ],
+        vec![animation("RestPose", vec![frame(3, 10)])],
+    );
+    let mut state = AnimationState::default();
+    let mut rng = rng();
+
+    let names: Vec<String> = (0..9)
+        .map(|_| started(&mut state, &config, Duration::ZERO, &mut rng))
+        .collect();
+    for (i, name) in names.iter().enumerate() {
+        assert_eq!(name == "RestPose", i % 2 == 1, "{:?}", names);
+    }
+}
+
+#[test]
+fn boredom_escalates_and_input_wakes_it_up() {
+    let config = config(
+        vec![
+            animation("FingerTap", vec![frame(0, 10)]),
+            animation("Snooze", vec![frame(1, 10), frame(1, 10)]),
+        ],
+        vec![
+            animation("LookLeft", vec![frame(2, 10)]),
+            animation("Think", vec![frame(3, 10)]),
+        ],
+    );
+    let mut state = AnimationState::default();
+    let mut rng = rng();
+
+    let at = |secs| Duration::from_secs(secs);
+    assert_eq!(started(&mut state, &config, at(5), &mut rng), "FingerTap");
+    assert!(!state.is_bored());
+    assert_eq!(started(&mut state, &config, at(60), &mut rng), "LookLeft");
+    assert!(state.is_bored());
+    assert_eq!(started(&mut state, &config, at(200), &mut rng), "Snooze");
+
+    // typing interrupts the snooze and starts the clock again
+    assert!(state.handle_action(
+        &DispatchActions::QuestionTextChanged("h".to_owned()),
+        at(201)
+    ));
+    assert_eq!(state.inactive_for(at(202)), at(1));
+    assert_eq!(started(&mut state, &config, at(202), &mut rng), "FingerTap");
+    assert!(!state.handle_action(
+        &DispatchActions::QuestionTextChanged("hi".to_owned()),
+        at(203)
+    ));
+}
+
+#[test]
+fn lifecycle_animations_play_once_and_goodbye_holds() {
+    let config = config(
+        vec![animation("Idle", vec![frame(0, 10)])],
+        vec![
+            animation("Greeting", vec![frame(1, 10), frame(2, 10)]),
+            animation("GoodBye", vec![frame(3, 10)]),
+        ],
+    );
+    let mut state = AnimationState::default();
+    let mut rng = rng();
+
+    assert!(state.handle_action(
+        &DispatchActions::Lifecycle(None, LifecycleEvent::Started),
+        Duration::ZERO
+    ));
+    assert_eq!(
+        started(&mut state, &config, Duration::ZERO, &mut rng),
+        "Greeting"
+    );
+    assert!(state.take_finished_lifecycle().is_empty());
+    assert_eq!(
+        started(&mut state, &config, Duration::ZERO, &mut rng),
+        "Idle"
+    );
+    assert_eq!(state.take_finished_lifecycle(), [LifecycleEvent::Started]);
+
+    // there's no hide animation, it's done straight away
+    state.handle_action(
+        &DispatchActions::Lifecycle(None, LifecycleEvent::Hidden),
+        Duration::ZERO,
+    );
+    state.next_frame(&config, Duration::ZERO, &mut rng);
+    assert_eq!(state.take_finished_lifecycle(), [LifecycleEvent::Hidden]);
+    state.handle_action(
+        &DispatchActions::Lifecycle(None, LifecycleEvent::Shown),
+        Duration::ZERO,
+    );
+
+    state.handle_action(
+        &DispatchActions::Lifecycle(None, LifecycleEvent::QuitRequested),
+        Duration::ZERO,
+    );
+    assert_eq!(
+        started(&mut state, &config, Duration::ZERO, &mut rng),
+        "GoodBye"
+    );
+    assert!(state
+        .next_frame(&config, Duration::ZERO, &mut rng)
+        .is_none());
+    assert!(state
+        .take_finished_lifecycle()
+        .contains(&LifecycleEvent::QuitRequested));
+}
+
+#[test]
+fn clicks_and_drags_play_their_animations() {
+    let config = config(
+        vec![animation("Idle", vec![frame(0, 10), frame(0, 10)])],
+        vec![
+            animation("GetAttention", vec![frame(1, 10)]),
+            animation("Alert", vec![frame(2, 10)]),
+        ],
+    );
+    let mut state = AnimationState::default();
+    let mut rng = rng();
+
+    assert_eq!(
+        started(&mut state, &config, Duration::ZERO, &mut rng),
+        "Idle"
+    );
+    assert!(state.handle_action(
+        &DispatchActions::Interaction("clippit".to_owned(), Interaction::Clicked),
+        Duration::ZERO
+    ));
+    assert_eq!(
+        started(&mut state, &config, Duration::ZERO, &mut rng),
+        "GetAttention"
+    );
+    assert!(state.handle_action(
+        &DispatchActions::Interaction("clippit".to_owned(), Interaction::Dragged),
+        Duration::ZERO
+    ));
+    assert_eq!(
+        started(&mut state, &config, Duration::ZERO, &mut rng),
+        "Alert"
+    );
+    assert!(!state.handle_action(
+        &DispatchActions::Interaction("clippit".to_owned(), Interaction::DoubleClicked),
+        Duration::ZERO
+    ));
+}
+
+#[test]
+fn playback_speed_scales_frame_durations() {
+    let config = config(
+        vec![
+            animation("Idle", vec![frame(0, 100)]),
+            animation("Wave", vec![frame(1, 100)]),
+        ],
+        vec![],
+    );
+    let mut state = AnimationState::default();
+    let mut rng = rng();
+
+    let mut playback = PlaybackSettings {
+        speed: 2.0,
+        ..Default::default()
+    };
+    playback.animation_speeds.insert("Wave".to_owned(), 0.5);
+    state.set_playback(playback);
+    for _ in 0..4 {
+        let step = state.next_frame(&config, Duration::ZERO, &mut rng).unwrap();
+        let expected = if step.frame.info.column == 0 { 50 } else { 200 };
+        assert_eq!(step.duration(), Duration::from_millis(expected));
+    }
+}
+
+#[test]
+fn reduced_motion_holds_the_rest_pose_and_shows_key_poses() {
+    let config = config(
+        vec![
+            animation("Idle", vec![frame(0, 10)]),
+            animation("RestPose", vec![frame(3, 10)]),
+        ],
+        vec![animation(
+            "GetAttention",
+            vec![frame(1, 10), frame(2, 80), frame(1, 10)],
+        )],
+    );
+    let mut state = AnimationState::default();
+    let mut rng = rng();
+    state.set_playback(PlaybackSettings {
+        reduced_motion: true,
+        ..Default::default()
+    });
+
+    assert_eq!(
+        started(&mut state, &config, Duration::ZERO, &mut rng),
+        "RestPose"
+    );
+    assert!(state
+        .next_frame(&config, Duration::ZERO, &mut rng)
+        .is_none());
+    assert!(state
+        .next_frame(&config, Duration::ZERO, &mut rng)
+        .is_none());
+    assert_eq!(state.current_frame().unwrap().info.column, 3);
+
+    // a reaction is its longest frame, held for a moment
+    state.handle_action(
+        &DispatchActions::Interaction("clippit".to_owned(), Interaction::Clicked),
+        Duration::ZERO,
+    );
+    let step = state.next_frame(&config, Duration::ZERO, &mut rng).unwrap();
+    assert_eq!(step.started_animation.as_deref(), Some("GetAttention"));
+    assert_eq!(step.frame.info.column, 2);
+    assert!(step.duration() > Duration::from_millis(80));
+    assert_eq!(
+        started(&mut state, &config, Duration::ZERO, &mut rng),
+        "RestPose"
+    );
+}
+
+#[test]
+fn actions_are_addressed_to_agents() {
+    let to_both = DispatchActions::AskQuestion(None, "hi".to_owned());
+    let to_watcher = DispatchActions::AskQuestion(Some("watcher".to_owned()), "hi".to_owned());
+    let clicked = DispatchActions::Interaction("watcher".to_owned(), Interaction::Clicked);
+    assert!(to_both.is_for("clippit") && to_both.is_for("watcher"));
+    assert!(!to_watcher.is_for("clippit") && to_watcher.is_for("watcher"));
+    assert!(!clicked.is_for("clippit"));
+    assert!(DispatchActions::NewConversation.is_for("clippit"));
+}
+
+#[test]
+fn look_directions() {
+    assert_eq!(
+        LookDirection::from_offset(10.0, 1.0),
+        Some(LookDirection::Right)
+    );
+    assert_eq!(
+        LookDirection::from_offset(-10.0, -9.0),
+        Some(LookDirection::UpLeft)
+    );
+    assert_eq!(
+        LookDirection::from_offset(0.5, 10.0),
+        Some(LookDirection::Down)
+    );
+    assert_eq!(LookDirection::from_offset(0.0, 0.0), None);
+    assert_eq!(LookDirection::DownLeft.mirrored(), LookDirection::DownRight);
+}
+
+#[test]
+fn looks_toward_attention_throttled() {
+    let config = config(
+        vec![animation("Idle", vec![frame(0, 10), frame(0, 10)])],
+        vec![
+            animation("LookLeft", vec![frame(1, 10)]),
+            animation("LookUp", vec![frame(2, 10)]),
+        ],
+    );
+    let mut state = AnimationState::default();
+    let mut rng = rng();
+    let at = |ms| Duration::from_millis(ms);
+
+    assert_eq!(started(&mut state, &config, at(0), &mut rng), "Idle");
+    assert!(state.look_toward(LookDirection::UpLeft, at(10)));
+    // no diagonal, looking sideways will do
+    assert_eq!(started(&mut state, &config, at(10), &mut rng), "LookLeft");
+    // same way again, nothing to do
+    assert!(!state.look_toward(LookDirection::UpLeft, at(20)));
+    // too soon, it waits for the current animation
+    assert!(!state.look_toward(LookDirection::Up, at(500)));
+    assert_eq!(started(&mut state, &config, at(510), &mut rng), "LookUp");
+    assert!(state.look_toward(LookDirection::Left, at(5000)));
+
+    state.set_mode(AnimationServiceMode::Active);
+    assert!(!state.look_toward(LookDirection::Up, at(9000)));
+}
+
+#[tokio::test]
+async fn service_runs_on_an_injected_clock() {
+    let config = config(
+        vec![animation("Idle", vec![frame(0, 100), frame(1, 250)])],
+        vec![],
+    );
+    let sheet = encode_png(&image::RgbaImage::from_pixel(
+        4,
+        1,
+        image::Rgba([255, 0, 0, 255]),
+    ))
+    .unwrap();
+    let (sndr, mut receiver) = tokio::sync::broadcast::channel(50);
+
+    let mut service = AnimationService::new(
+        egui::Context::default(),
+        serde_yaml::to_string(&config).unwrap(),
+        sheet,
+        sndr,
+        2048,
+    )
+    .unwrap();
+    let clock = Arc::new(ManualClock::default());
+    service.set_clock(clock.clone());
+    service.set_rng(rng());
+    service.start();
+
+    let mut frames = 0;
+    while frames < 4 {
+        if let DispatchActions::NewFrameToRender = receiver.recv().await.unwrap() {
+            frames += 1;
+        }
+    }
+    service.stop();
+    // the clock only moves by the frame durations
+    assert!(clock.now() >= Duration::from_millis(100 + 250 + 100));
+    assert!(service.state().current_frame().is_some());
+}
+
+#[tokio::test]
+async fn frames_play_sounds_and_send_events() {
+    let mut noisy = frame(1, 100);
+    noisy.sound = Some("7".to_owned());
+    noisy.event = Some("wave".to_owned());
+    let config = config(vec![animation("Idle", vec![frame(0, 100), noisy])], vec![]);
+    let sheet = encode_png(&image::RgbaImage::from_pixel(
+        4,
+        1,
+        image::Rgba([255, 0, 0, 255]),
+    ))
+    .unwrap();
+    let (sndr, mut receiver) = tokio::sync::broadcast::channel(50);
+
+    let mut service = AnimationService::new(
+        egui::Context::default(),
+        serde_yaml::to_string(&config).unwrap(),
+        sheet,
+        sndr,
+        2048,
+    )
+    .unwrap();
+    let sink = Arc::new(RecordingSink::default());
+    service.set_clock(Arc::new(ManualClock::default()));
+    service.set_audio_sink(sink.clone());
+    service.set_audio_settings(AudioSettings {
+        volume: 0.5,
+        muted: false,
+    });
+    service.start();
+
+    let mut events = Vec::new();
+    while events.len() < 2 {
+        if let DispatchActions::AnimationEvent(agent, event) = receiver.recv().await.unwrap() {
+            events.push((agent, event));
+        }
+    }
+    service.set_audio_settings(AudioSettings {
+        volume: 0.5,
+        muted: true,
+    });
+    let heard = sink.played().len();
+    while events.len() < 4 {
+        if let DispatchActions::AnimationEvent(agent, event) = receiver.recv().await.unwrap() {
+            events.push((agent, event));
+        }
+    }
+    service.stop();
+
+    assert!(events
+        .iter()
+        .all(|e| *e == ("clippit".to_owned(), "wave".to_owned())));
+    assert!(heard >= 2);
+    assert!(sink.played()[..heard]
+        .iter()
+        .all(|p| *p == ("7".to_owned(), 0.5)));
+    // a sound may have been on its way when muting, but not more than one
+    assert!(sink.played().len() <= heard + 1);
+}
+
+#[tokio::test]
+async fn agents_share_the_bus_and_silent_ones_ignore_questions() {
+    let config = config(
+        vec![animation("Idle", vec![frame(0, 100)])],
+        vec![animation("Think", vec![frame(1, 100)])],
+    );
+    let sheet = encode_png(&image::RgbaImage::from_pixel(
+        4,
+        1,
+        image::Rgba([255, 0, 0, 255]),
+    ))
+    .unwrap();
+    let (sndr, mut receiver) = tokio::sync::broadcast::channel(1000);
+    let start = |agent: &str| {
+        let mut service = AnimationService::new(
+            egui::Context::default(),
+            serde_yaml::to_string(&config).unwrap(),
+            sheet.clone(),
+            sndr.clone(),
+            2048,
+        )
+        .unwrap();
+        service.set_agent(agent.to_owned());
+        service.set_clock(Arc::new(ManualClock::default()));
+        service.set_rng(rng());
+        service
+    };
+    let mut talker = start("talker");
+    let mut watcher = start("watcher");
+    watcher.set_answers_questions(false);
+    talker.start();
+    watcher.start();
+
+    sndr.send(DispatchActions::AskQuestion(None, "hi".to_owned()))
+        .unwrap();
+    // both loops flood the bus with frames, the question still gets through straight away
+    let mut after_question = 0;
+    let mut started = Vec::new();
+    while after_question < 200 {
+        match receiver.recv().await {
+            Ok(DispatchActions::NewAnimationStarted(agent, name)) => started.push((agent, name)),
+            Ok(DispatchActions::NewFrameToRender) => after_question += 1,
+            _ => {}
+        }
+    }
+    talker.stop();
+    watcher.stop();
+
+    assert!(started.contains(&("talker".to_owned(), "Think".to_owned())));
+    assert!(!started.contains(&("watcher".to_owned(), "Think".to_owned())));
+    assert_eq!(*talker.state().mode(), AnimationServiceMode::Active);
+    assert_eq!(*watcher.state().mode(), AnimationServiceMode::Idle);
+}
+
+fn arb_frame(max_target: usize) -> impl Strategy<Value = AnimationFrame> {
+    (
+        0..50usize,
+        proptest::collection::vec((0..max_target, 0..120u32), 0..3),
+        proptest::option::of(0..max_target),
+    )
+        .prop_map(|(duration, branches, exit_branch)| AnimationFrame {
+            duration,
+            branching: branches
+                .into_iter()
+                .map(|(frame_index, weight)| FrameBranch {
+                    frame_index,
+                    weight,
+                })
+                .collect(),
+            exit_branch,
+            ..Default::default()
+        })
+}
+
+fn arb_animations() -> impl Strategy<Value = Vec<AnimationInfo>> {
+    // branch targets may point past the end, the scheduler has to cope
+    proptest::collection::vec(proptest::collection::vec(arb_frame(8), 0..6), 0..4).prop_map(
+        |animations| {
+            animations
+                .into_iter()
+                .enumerate()
+                .map(|(i, frames)| animation(&format!("A{}", i), frames))
+                .collect()
+        },
+    )
+}
+
+proptest! {
+    #[test]
+    fn never_shows_a_frame_that_doesnt_exist(
+        idle in arb_animations(),
+        action in arb_animations(),
+        seed in any::<u64>(),
+        switches in proptest::collection::vec(any::<bool>(), 0..40)
+    ) {
+        let config = config(idle, action);
+        let mut state = AnimationState::default();
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        for ask in switches {
+            if ask {
+                state.handle_action(&DispatchActions::AskQuestion(None, String::new()), Duration::ZERO);
+            }
+            for _ in 0..5 {
+                let Some(step) = state.next_frame(&config, Duration::ZERO, &mut rng) else { continue };
+                let animation = state.current_animation().unwrap();
+                prop_assert!(step.frame_index < animation.frames.len());
+                prop_assert_eq!(&animation.frames[step.frame_index].duration, &step.frame.duration);
+            }
+            if !ask {
+                state.set_mode(AnimationServiceMode::Idle);
+            }
+        }
+    }
+
+    #[test]
+    fn animations_come_from_the_mode_category(
+        idle in arb_animations(),
+        action in arb_animations(),
+        seed in any::<u64>()
+    ) {
+        let config = config(idle, action);
+        let mut state = AnimationState::default();
+        state.set_mode(AnimationServiceMode::Active);
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        for _ in 0..30 {
+            let Some(step) = state.next_frame(&config, Duration::ZERO, &mut rng) else { continue };
+            if let Some(name) = step.started_animation {
+                let expected = if config.animations.action.is_empty() { &config.animations.idle } else { &config.animations.action };
+                prop_assert!(expected.iter().any(|a| a.name == name));
+            }
+        }
+    }
+}
@@ -0,0 +1,289 @@
+mod common;
+
+use std::time::Duration;
+
+use clippit_gpt::actions::DispatchActions;
+use clippit_gpt::actions::Interaction;
+use clippit_gpt::animation::models::AnimationConfig;
+use clippit_gpt::animation::models::AnimationServiceMode;
+use clippit_gpt::animation::models::StateMachine;
+use clippit_gpt::animation::models::Trigger;
+use clippit_gpt::animation::scheduler::AnimationState;
+use clippit_gpt::animation::validate::has_errors;
+use clippit_gpt::animation::validate::parse_and_validate;
+use clippit_gpt::models::QuestionResponse;
+use common::rng;
+use common::started;
+
+const CONFIG: &str = "
+animations:
+  idle:
+    - name: Idle
+      frames: [{ duration: 10, info: { column: 0, row: 0 } }]
+  action:
+    - name: Thinking
+      frames: [{ duration: 10, info: { column: 1, row: 0 } }]
+    - name: Explain
+      frames: [{ duration: 10, info: { column: 2, row: 0 } }]
+    - name: Alert
+      frames: [{ duration: 10, info: { column: 3, row: 0 } }]
+sprite_sheet_info: { columns: 4, rows: 1 }
+state_machine:
+  initial: waiting
+  states:
+    waiting:
+      category: idle
+      on: { question_asked: thinking, click: upset }
+    thinking:
+      animations: [Thinking]
+      on: { answer_chunk: explaining, error: upset }
+    explaining:
+      animations: [Explain]
+      timeout_secs: 5
+      on: { timeout: waiting, question_asked: thinking }
+    upset:
+      animations: [Alert]
+      timeout_secs: 3
+      on: { timeout: waiting }
+";
+
+fn config() -> AnimationConfig {
+    let (config, diagnostics) = parse_and_validate(CONFIG, None);
+    assert!(!has_errors(&diagnostics), "{:?}", diagnostics);
+    config.unwrap()
+}
+
+fn state(config: &AnimationConfig) -> AnimationState {
+    let mut state = AnimationState::default();
+    state.set_state_machine(config.state_machine.clone());
+    state
+}
+
+fn answer() -> DispatchActions {
+    DispatchActions::RespondToQuestion(
+        "clippit".to_owned(),
+        QuestionResponse {
+            question: "hi".to_owned(),
+            answer: "hello".to_owned(),
+        },
+    )
+}
+
+#[test]
+fn transition_table() {
+    let machine = config().state_machine;
+    let table = [
+        ("waiting", Trigger::QuestionAsked, Some("thinking")),
+        ("waiting", Trigger::Click, Some("upset")),
+        ("waiting", Trigger::Answer, None),
+        ("thinking", Trigger::Answer, Some("explaining")),
+        ("thinking", Trigger::Error, Some("upset")),
+        ("thinking", Trigger::Click, None),
+        ("explaining", Trigger::Timeout, Some("waiting")),
+        ("explaining", Trigger::QuestionAsked, Some("thinking")),
+        ("upset", Trigger::Timeout, Some("waiting")),
+        ("upset", Trigger::Error, None),
+        ("nowhere", Trigger::Click, None),
+    ];
+    for (from, trigger, to) in table {
+        assert_eq!(
+            machine.transition(from, trigger),
+            to,
+            "{} on {:?}",
+            from,
+            trigger
+        );
+    }
+}
+
+#[test]
+fn default_machine_talks_through_the_answer() {
+    let machine = StateMachine::default();
+    let table = [
+        ("idle", Trigger::QuestionAsked, Some("active")),
+        ("idle", Trigger::Error, None),
+        ("active", Trigger::Answer, Some("talking")),
+        ("active", Trigger::Click, None),
+        ("talking", Trigger::Revealed, Some("closing")),
+        ("talking", Trigger::QuestionAsked, Some("active")),
+        ("talking", Trigger::Done, None),
+        ("closing", Trigger::Done, Some("idle")),
+        ("closing", Trigger::QuestionAsked, Some("active")),
+    ];
+    for (from, trigger, to) in table {
+        assert_eq!(
+            machine.transition(from, trigger),
+            to,
+            "{} on {:?}",
+            from,
+            trigger
+        );
+    }
+}
+
+#[test]
+fn talks_until_the_answer_is_revealed_then_closes() {
+    let config = config();
+    let mut state = AnimationState::default();
+    state.handle_action(
+        &DispatchActions::AskQuestion(None, "hi".to_owned()),
+        Duration::ZERO,
+    );
+    state.handle_action(&answer(), Duration::ZERO);
+
+    // still typing, the talking animations loop
+    for _ in 0..3 {
+        assert_eq!(
+            started(&mut state, &config, Duration::ZERO, &mut rng()),
+            "Explain"
+        );
+        assert_eq!(state.machine_state(), "talking");
+    }
+
+    // the closing pose falls back to the idle category here, then it's back to idling
+    assert!(state.handle_action(
+        &DispatchActions::AnswerRevealed("clippit".to_owned()),
+        Duration::ZERO
+    ));
+    assert_eq!(
+        started(&mut state, &config, Duration::ZERO, &mut rng()),
+        "Idle"
+    );
+    assert_eq!(state.machine_state(), "closing");
+    assert_eq!(
+        started(&mut state, &config, Duration::ZERO, &mut rng()),
+        "Idle"
+    );
+    assert_eq!(state.machine_state(), "idle");
+}
+
+#[test]
+fn states_play_their_animations() {
+    let config = config();
+    let mut state = state(&config);
+    assert_eq!(state.machine_state(), "waiting");
+    assert_eq!(
+        started(&mut state, &config, Duration::ZERO, &mut rng()),
+        "Idle"
+    );
+
+    assert!(state.handle_action(
+        &DispatchActions::AskQuestion(None, "hi".to_owned()),
+        Duration::ZERO
+    ));
+    assert_eq!(state.machine_state(), "thinking");
+    assert_eq!(state.mode(), &AnimationServiceMode::Active);
+    assert_eq!(
+        started(&mut state, &config, Duration::ZERO, &mut rng()),
+        "Thinking"
+    );
+
+    assert!(state.handle_action(&answer(), Duration::ZERO));
+    assert_eq!(
+        started(&mut state, &config, Duration::ZERO, &mut rng()),
+        "Explain"
+    );
+    // nothing goes anywhere on an answer from here
+    assert!(!state.handle_action(&answer(), Duration::ZERO));
+    assert_eq!(state.machine_state(), "explaining");
+}
+
+#[test]
+fn states_without_their_animations_use_the_category() {
+    // a pack that doesn't have the animation the machine asks for
+    let mut config = config();
+    config
+        .state_machine
+        .states
+        .get_mut("thinking")
+        .unwrap()
+        .animations = vec!["Pondering".to_owned()];
+    let mut state = state(&config);
+    started(&mut state, &config, Duration::ZERO, &mut rng());
+
+    state.handle_action(
+        &DispatchActions::AskQuestion(None, "hi".to_owned()),
+        Duration::ZERO,
+    );
+    assert_eq!(state.machine_state(), "thinking");
+    let action = started(&mut state, &config, Duration::ZERO, &mut rng());
+    assert!(
+        config.animations.action.iter().any(|a| a.name == action),
+        "{} isn't an action",
+        action
+    );
+}
+
+#[test]
+fn errors_and_clicks_move_the_machine() {
+    let config = config();
+    let mut state = state(&config);
+
+    state.handle_action(
+        &DispatchActions::AskQuestion(None, "hi".to_owned()),
+        Duration::ZERO,
+    );
+    let error = DispatchActions::AssistantError("clippit".to_owned(), "timed out".to_owned());
+    assert!(state.handle_action(&error, Duration::ZERO));
+    assert_eq!(state.machine_state(), "upset");
+    assert!(state.showing_error(Duration::ZERO));
+    assert_eq!(
+        started(&mut state, &config, Duration::ZERO, &mut rng()),
+        "Alert"
+    );
+
+    let mut state = self::state(&config);
+    assert!(state.handle_action(
+        &DispatchActions::Interaction("clippit".to_owned(), Interaction::Clicked),
+        Duration::ZERO
+    ));
+    assert_eq!(state.machine_state(), "upset");
+}
+
+#[test]
+fn timeouts_wait_for_inactivity() {
+    let config = config();
+    let mut state = state(&config);
+    let at = Duration::from_secs;
+
+    state.handle_action(
+        &DispatchActions::Interaction("clippit".to_owned(), Interaction::Clicked),
+        at(10),
+    );
+    assert_eq!(state.machine_state(), "upset");
+    started(&mut state, &config, at(11), &mut rng());
+    assert_eq!(state.machine_state(), "upset");
+
+    // typing counts as input and pushes the timeout back
+    state.handle_action(
+        &DispatchActions::QuestionTextChanged("h".to_owned()),
+        at(12),
+    );
+    started(&mut state, &config, at(14), &mut rng());
+    assert_eq!(state.machine_state(), "upset");
+
+    assert_eq!(started(&mut state, &config, at(15), &mut rng()), "Idle");
+    assert_eq!(state.machine_state(), "waiting");
+}
+
+#[test]
+fn broken_machines_fail_validation() {
+    let yaml = CONFIG
+        .replace("initial: waiting", "initial: sleeping")
+        .replace("error: upset", "error: crying");
+    let (config, diagnostics) = parse_and_validate(&yaml, None);
+    assert!(config.is_none());
+    let messages: Vec<String> = diagnostics.iter().map(|d| d.to_string()).collect();
+    assert!(
+        messages
+            .iter()
+            .any(|m| m.contains("no state called sleeping")),
+        "{:?}",
+        messages
+    );
+    assert!(
+        messages.iter().any(|m| m.contains("crying")),
+        "{:?}",
+        messages
+    );
+}
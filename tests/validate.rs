@@ -0,0 +1,197 @@
+use clippit_gpt::animation::validate::has_errors;
+use clippit_gpt::animation::validate::parse_and_validate;
+use clippit_gpt::animation::validate::Diagnostic;
+use clippit_gpt::animation::validate::Severity;
+use egui::Color32;
+use egui::ColorImage;
+
+const CONFIG: &str = "animations:
+  idle:
+    - name: Idle
+      frames:
+        - duration: 100
+          info: { column: 0, row: 0 }
+        - duration: 100
+          info: { column: 1, row: 0 }
+  action:
+    - name: Wave
+      weight: 2.0
+      frames:
+        - duration: 100
+          info: { column: 1, row: 0 }
+sprite_sheet_info: { columns: 2, rows: 1 }
+";
+
+/// a 2x1 grid of 2x2 cells, all opaque
+fn sheet() -> ColorImage {
+    ColorImage::new([4, 2], Color32::RED)
+}
+
+fn diagnostics(yaml: &str) -> Vec<Diagnostic> {
+    parse_and_validate(yaml, Some(&sheet())).1
+}
+
+fn find<'a>(diagnostics: &'a [Diagnostic], path: &str) -> &'a Diagnostic {
+    diagnostics
+        .iter()
+        .find(|d| d.path == path)
+        .unwrap_or_else(|| panic!("nothing at {} in {:?}", path, diagnostics))
+}
+
+#[test]
+fn good_configs_pass() {
+    let (config, diagnostics) = parse_and_validate(CONFIG, Some(&sheet()));
+    assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+    assert_eq!(config.unwrap().animations.action[0].weight, 2.0);
+}
+
+#[test]
+fn errors_point_at_their_line() {
+    let yaml = CONFIG.replace(
+        "info: { column: 1, row: 0 }\n  action",
+        "info: { column: 5, row: 0 }\n  action",
+    );
+    let diagnostics = diagnostics(&yaml);
+    assert!(has_errors(&diagnostics));
+    let column = find(&diagnostics, "animations.idle[0].frames[1].info.column");
+    assert_eq!(column.severity, Severity::Error);
+    assert_eq!(column.line, Some(8));
+    assert_eq!(column.to_string(), "error (line 8) animations.idle[0].frames[1].info.column: column 5 is outside the 2 column sprite sheet");
+}
+
+#[test]
+fn weights_have_to_be_finite_and_positive() {
+    for weight in ["-1.0", ".inf", ".nan"] {
+        let (config, diagnostics) =
+            parse_and_validate(&CONFIG.replace("2.0", weight), Some(&sheet()));
+        assert!(config.is_none(), "{} passed", weight);
+        let diagnostic = find(&diagnostics, "animations.action[0].weight");
+        assert_eq!(diagnostic.line, Some(11));
+    }
+}
+
+#[test]
+fn branches_names_and_durations() {
+    let yaml = CONFIG
+        .replace("name: Wave", "name: Idle")
+        .replace("      frames:\n        - duration: 100\n          info: { column: 1, row: 0 }\nsprite", "      frames:\n        - duration: 0\n          exit_branch: 3\n          info: { column: 1, row: 0 }\nsprite");
+    let diagnostics = diagnostics(&yaml);
+
+    let name = find(&diagnostics, "animations.action[0].name");
+    assert_eq!((name.severity, name.line), (Severity::Error, Some(10)));
+    assert!(name.message.contains("already used by animations.idle[0]"));
+    assert_eq!(
+        find(&diagnostics, "animations.action[0].frames[0]").severity,
+        Severity::Error
+    );
+    assert_eq!(
+        find(&diagnostics, "animations.action[0].frames[0].duration").severity,
+        Severity::Warning
+    );
+}
+
+#[test]
+fn sprite_sheet_problems() {
+    let diagnostics = diagnostics(&CONFIG.replace(
+        "{ columns: 2, rows: 1 }",
+        "{ columns: 3, rows: 1, scale: 0 }",
+    ));
+    assert_eq!(
+        find(&diagnostics, "sprite_sheet_info").severity,
+        Severity::Warning
+    );
+    assert_eq!(
+        find(&diagnostics, "sprite_sheet_info.scale").severity,
+        Severity::Error
+    );
+
+    // a clear cell is allowed but flagged
+    let mut image = sheet();
+    image.pixels[0] = Color32::TRANSPARENT;
+    image.pixels[1] = Color32::TRANSPARENT;
+    image.pixels[4] = Color32::TRANSPARENT;
+    image.pixels[5] = Color32::TRANSPARENT;
+    let (config, diagnostics) = parse_and_validate(CONFIG, Some(&image));
+    assert!(config.is_some());
+    assert_eq!(
+        find(&diagnostics, "animations.idle[0].frames[0].info").severity,
+        Severity::Warning
+    );
+}
+
+#[test]
+fn syntax_errors_have_a_line() {
+    let (config, diagnostics) = parse_and_validate("animations:\n  idle: [\n", None);
+    assert!(config.is_none());
+    assert_eq!(diagnostics.len(), 1);
+    assert!(diagnostics[0].line.is_some());
+}
+
+#[test]
+fn packs_only_have_one_page() {
+    let yaml = CONFIG.replace(
+        "info: { column: 1, row: 0 }\nsprite",
+        "info: { column: 1, row: 0, page: 1, layers: [{ column: 0, row: 0, page: 2 }] }\nsprite",
+    );
+    let (config, diagnostics) = parse_and_validate(&yaml, Some(&sheet()));
+    assert!(config.is_none());
+    assert_eq!(
+        find(&diagnostics, "animations.action[0].frames[0].info.page").severity,
+        Severity::Error
+    );
+    assert_eq!(
+        find(
+            &diagnostics,
+            "animations.action[0].frames[0].info.layers[0].page"
+        )
+        .severity,
+        Severity::Error
+    );
+}
+
+#[test]
+fn grid_cells_have_to_fit_the_sheet() {
+    // 3 pixel wide cells only leave room for the first column of the 4 pixel sheet
+    let yaml = CONFIG
+        .replace(
+            "info: { column: 1, row: 0 }\nsprite",
+            "info: { column: 0, row: 0, layers: [{ column: 1, row: 0 }] }\nsprite",
+        )
+        .replace("rows: 1 }", "rows: 1, frame_size: [3, 2] }");
+    let (config, diagnostics) = parse_and_validate(&yaml, Some(&sheet()));
+    assert!(config.is_none());
+    let frame = find(&diagnostics, "animations.idle[0].frames[1].info");
+    assert_eq!(frame.severity, Severity::Error);
+    assert_eq!(
+        frame.message,
+        "region 3x2 at 3,0 is outside the 4x2 sprite sheet"
+    );
+    assert_eq!(
+        find(
+            &diagnostics,
+            "animations.action[0].frames[0].info.layers[0]"
+        )
+        .severity,
+        Severity::Error
+    );
+    assert!(!diagnostics
+        .iter()
+        .any(|d| d.path == "animations.idle[0].frames[0].info"));
+}
+
+#[test]
+fn clear_cells_off_the_sheet_dont_panic() {
+    // the transparency scan used to index past the sheet
+    let yaml = CONFIG.replace("rows: 1 }", "rows: 1, frame_size: [3, 3] }");
+    let image = ColorImage::new([4, 2], Color32::TRANSPARENT);
+    let (config, diagnostics) = parse_and_validate(&yaml, Some(&image));
+    assert!(config.is_none());
+    assert_eq!(
+        find(&diagnostics, "animations.idle[0].frames[0].info").severity,
+        Severity::Error
+    );
+    assert_eq!(
+        find(&diagnostics, "animations.action[0].frames[0].info").severity,
+        Severity::Error
+    );
+}
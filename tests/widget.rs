@@ -0,0 +1,72 @@
+use std::time::Duration;
+
+use clippit_gpt::animation::models::AnimationConfig;
+use clippit_gpt::animation::models::AnimationServiceMode;
+use clippit_gpt::animation::widget::SpriteAnimation;
+use egui::Color32;
+use egui::ColorImage;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+const CONFIG: &str = "
+animations:
+  idle:
+    - name: Idle
+      frames:
+        - { duration: 100, info: { column: 0, row: 0 } }
+        - { duration: 200, info: { column: 1, row: 0 } }
+  action:
+    - name: Think
+      frames: [{ duration: 100, info: { column: 2, row: 0 } }]
+    - name: Wave
+      frames: [{ duration: 100, info: { column: 3, row: 0 } }]
+sprite_sheet_info: { columns: 4, rows: 1, scale: 2.0 }
+";
+
+fn animation() -> SpriteAnimation {
+    let config: AnimationConfig = serde_yaml::from_str(CONFIG).unwrap();
+    let image = ColorImage::new([16, 4], Color32::RED);
+    let mut animation =
+        SpriteAnimation::from_image(&egui::Context::default(), "test", config, image);
+    animation.set_rng(StdRng::seed_from_u64(7));
+    animation
+}
+
+fn columns(steps: &[clippit_gpt::animation::scheduler::FrameStep]) -> Vec<usize> {
+    steps.iter().map(|s| s.frame.info.column).collect()
+}
+
+#[test]
+fn advances_with_the_clock() {
+    let mut animation = animation();
+    let at = Duration::from_millis;
+
+    assert_eq!(columns(&animation.advance(at(0))), [0]);
+    assert!(animation.advance(at(50)).is_empty());
+    assert_eq!(animation.time_to_next_frame(at(50)), at(50));
+    // two frames were due, both are played
+    assert_eq!(columns(&animation.advance(at(310))), [1, 0]);
+    assert_eq!(animation.frame_size(), egui::vec2(8.0, 8.0));
+}
+
+#[test]
+fn play_and_set_mode_take_over_straight_away() {
+    let mut animation = animation();
+    let at = Duration::from_millis;
+    animation.advance(at(0));
+
+    animation.play("Wave");
+    let steps = animation.advance(at(10));
+    assert_eq!(steps[0].started_animation.as_deref(), Some("Wave"));
+    // played once, then back to idling
+    assert_eq!(
+        animation.advance(at(110))[0].started_animation.as_deref(),
+        Some("Idle")
+    );
+
+    animation.set_mode(AnimationServiceMode::Active);
+    assert_eq!(
+        animation.advance(at(120))[0].started_animation.as_deref(),
+        Some("Think")
+    );
+}